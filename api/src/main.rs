@@ -1,7 +1,7 @@
 extern crate common;
 
 use anyhow::Result;
-use common::{api, RawSolution, Solution};
+use common::{api, upper_bound::upper_bound, Problem, RawSolution, Solution};
 use serde_json;
 use std::convert::From;
 
@@ -45,6 +45,36 @@ fn main() -> Result<()> {
         "problems" => println!("{:?}", client.get_problems()),
         "scoreboard" => println!("{:?}", client.get_scoreboard()),
         "userboard" => println!("{:?}", client.get_userboard()),
+        "gap" => {
+            // Our best scores as percentages of the upper bounds.
+            let userboard = client.get_userboard()?;
+            let mut total = (0., 0.);
+            for (i, best) in userboard.problems.iter().enumerate() {
+                let problem_id = i as u32 + 1;
+                let problem = match Problem::read_from_file(format!("problems/{}.json", problem_id))
+                {
+                    Ok(problem) => problem,
+                    Err(_) => client.get_problem(problem_id)?,
+                };
+                let bound = upper_bound(problem_id, &problem);
+                let best = best.unwrap_or(0.);
+                total.0 += best;
+                total.1 += bound;
+                println!(
+                    "{:>3} {:>16.0} {:>16.0} {:>7.2}%",
+                    problem_id,
+                    best,
+                    bound,
+                    best / bound * 100.
+                );
+            }
+            println!(
+                "all {:>16.0} {:>16.0} {:>7.2}%",
+                total.0,
+                total.1,
+                total.0 / total.1 * 100.
+            );
+        }
         "register" => println!("{:?}", client.post_register(&args[2], &args[3], &args[4])),
         "login" => println!("{:?}", client.post_login(&args[2], &args[3])),
         _ => println!("unknown command"),
@@ -3,11 +3,10 @@ use std::{collections::HashMap, path::PathBuf};
 use anyhow::{anyhow, Result};
 use clap::Parser;
 use common::{
-    api::Client, board::Board, create_q_vector, evaluate, evaluate_musician, Placement, Problem,
-    RawSolution,
+    api::Client, board::Board, create_q_vector, evaluate, evaluate_musician,
+    layout::square_lattice, Placement, Problem, RawSolution,
 };
 use euclid::{default::Point2D, point2, Box2D, Vector2D};
-use indexmap::IndexMap;
 use log::{debug, info};
 use lyon_geom::point;
 use rand::{seq::SliceRandom, Rng};
@@ -38,42 +37,18 @@ struct Args {
 
 #[derive(Debug)]
 struct Solution {
-    pub musicians: IndexMap<(u32, u32), usize>,
-}
-
-fn convert_to_real_point(x: u32, y: u32, prob: &Problem) -> Point2D<f64> {
-    Point2D::new(
-        (x * 10) as f64 + prob.stage.min.x + 10.0,
-        (y * 10) as f64 + prob.stage.min.y + 10.0,
-    )
-}
-
-fn generate_grid_points(prob: &Problem) -> Vec<(u32, u32)> {
-    let h = prob.stage.height() as i32;
-    let w = prob.stage.width() as i32;
-    let sh = (h - 20) / 10 + 1;
-    let sw = (w - 20) / 10 + 1;
-
-    let mut grid_points = vec![];
-    for x in 0..sw {
-        for y in 0..sh {
-            grid_points.push((x as u32, y as u32));
-        }
-    }
-    grid_points
+    pub musicians: Vec<(Point2D<f64>, usize)>,
 }
 
 fn generate_random_solution(prob: &Problem) -> Solution {
     let mut rng = rand::thread_rng();
 
-    let mut possible_points = generate_grid_points(prob);
+    let mut possible_points = square_lattice(prob.stage.inflate(-10., -10.), 10.);
     possible_points.shuffle(&mut rng);
 
-    let mut sol = IndexMap::new();
+    let mut sol = vec![];
     for i in 0..prob.musicians.len() {
-        let x = possible_points[i].0;
-        let y = possible_points[i].1;
-        sol.insert((x, y), prob.musicians[i]);
+        sol.push((possible_points[i], prob.musicians[i]));
     }
     Solution { musicians: sol }
 }
@@ -87,7 +62,7 @@ fn convert_solution(prob: &Problem, sol: &Solution, pid: u32) -> common::Solutio
         musicians_by_inst
             .get_mut(v)
             .expect("Should not null")
-            .push(convert_to_real_point(p.0, p.1, prob));
+            .push(*p);
     }
 
     let mut ps = vec![];
@@ -395,11 +370,12 @@ fn particle(
                 touches += 1;
                 touch_point = Some(point2(p.x, stage.max.y + 10.));
             }
-            for placement in cur.placements.iter() {
-                if (placement.position - p).square_length() == 100. {
-                    touches += 1;
-                    touch_point = Some(placement.position);
+            for m2 in board.neighbors_within(p, 10.) {
+                if m2 == m {
+                    continue;
                 }
+                touches += 1;
+                touch_point = Some(board.musicians()[m2].unwrap().0.to_point());
             }
             if touches >= 2 {
                 // Locked. skip
@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+
+use euclid::default::{Box2D, Point2D};
+
+use crate::Attendee;
+
+// Attendees far from the stage merged into weighted super-attendees.
+pub struct AttendeeClusters {
+    // Super-attendees. The tastes are the sums of the tastes of the members.
+    pub attendees: Vec<Attendee>,
+    // ins -> upper bound of |impact on the clusters - impact on the original attendees|
+    // of a single unblocked musician on the stage with instrument ins.
+    pub error_bounds: Vec<f64>,
+}
+
+fn distance_to_box(bb: &Box2D<f64>, p: Point2D<f64>) -> f64 {
+    let dx = (bb.min.x - p.x).max(p.x - bb.max.x).max(0.);
+    let dy = (bb.min.y - p.y).max(p.y - bb.max.y).max(0.);
+    dx.hypot(dy)
+}
+
+// Attendees within min_distance from the stage are kept as they are.
+// The others are bucketed by the angle seen from the center of the stage (in steps of
+// angle radians) and by the log of the distance from the stage (in steps of 1 + angle),
+// so a cluster spans roughly distance * angle in both directions.
+pub fn cluster_attendees(
+    attendees: &[Attendee],
+    stage: Box2D<f64>,
+    min_distance: f64,
+    angle: f64,
+) -> AttendeeClusters {
+    let num_instruments = attendees[0].tastes.len();
+    let center = stage.center();
+
+    let mut singles = vec![];
+    // (angle bin, distance bin) -> members
+    let mut bins = BTreeMap::<(i64, i64), Vec<usize>>::new();
+
+    for (i, a) in attendees.iter().enumerate() {
+        let d = distance_to_box(&stage, a.position);
+        if d < min_distance {
+            singles.push(i);
+            continue;
+        }
+        let r = (a.position - center).angle_from_x_axis().radians + PI;
+        let key = (
+            (r / angle).floor() as i64,
+            ((d / min_distance).ln() / angle.ln_1p()).floor() as i64,
+        );
+        bins.entry(key).or_default().push(i);
+    }
+
+    let mut res = AttendeeClusters {
+        attendees: singles.iter().map(|i| attendees[*i].clone()).collect(),
+        error_bounds: vec![0.; num_instruments],
+    };
+
+    for members in bins.values() {
+        let mut c = Point2D::zero();
+        for i in members.iter() {
+            c += attendees[*i].position.to_vector() / members.len() as f64;
+        }
+        let radius = members
+            .iter()
+            .map(|i| (attendees[*i].position - c).length())
+            .fold(0., f64::max);
+
+        // Both the members and the center are at least dmin away from any musician.
+        let dmin = distance_to_box(&stage, c) - radius;
+        if members.len() == 1 || dmin <= radius {
+            res.attendees
+                .extend(members.iter().map(|i| attendees[*i].clone()));
+            continue;
+        }
+
+        let mut tastes = vec![0.; num_instruments];
+        for i in members.iter() {
+            for (t, t2) in tastes.iter_mut().zip(attendees[*i].tastes.iter()) {
+                *t += t2;
+            }
+        }
+        res.attendees.push(Attendee {
+            position: c,
+            tastes,
+        });
+
+        // For d_a, d_c >= dmin and |d_a - d_c| <= radius,
+        // |1/d_a^2 - 1/d_c^2| <= 2 * radius / dmin^3.
+        // +1 for each member accounts for the rounding of the individual impacts.
+        let e = 2. * radius / dmin.powi(3);
+        for i in members.iter() {
+            for (b, t) in res.error_bounds.iter_mut().zip(attendees[*i].tastes.iter()) {
+                *b += 1_000_000. * t.abs() * e + 1.;
+            }
+        }
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use euclid::default::{Box2D, Point2D};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::Problem;
+
+    use super::cluster_attendees;
+
+    #[test]
+    fn test_cluster_attendees() {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let problem = Problem::read_from_file("../problems/42.json").unwrap();
+        let clusters = cluster_attendees(&problem.attendees, problem.stage, 100., 0.1);
+
+        assert!(clusters.attendees.len() < problem.attendees.len());
+
+        // Tastes are preserved in total.
+        for k in 0..problem.attendees[0].tastes.len() {
+            let expected = problem.attendees.iter().map(|a| a.tastes[k]).sum::<f64>();
+            let actual = clusters.attendees.iter().map(|a| a.tastes[k]).sum::<f64>();
+            assert!((expected - actual).abs() < 1e-6);
+        }
+
+        let impact = |attendees: &[crate::Attendee], p: Point2D<f64>, k: usize| {
+            attendees
+                .iter()
+                .map(|a| (1_000_000. * a.tastes[k] / (a.position - p).square_length()).ceil())
+                .sum::<f64>()
+        };
+
+        let stage: Box2D<f64> = problem.stage;
+        for _ in 0..20 {
+            let p = Point2D::new(
+                rng.gen_range(stage.min.x..stage.max.x),
+                rng.gen_range(stage.min.y..stage.max.y),
+            );
+            for k in 0..problem.attendees[0].tastes.len() {
+                let exact = impact(&problem.attendees, p, k);
+                let approx = impact(&clusters.attendees, p, k);
+                assert!((exact - approx).abs() <= clusters.error_bounds[k]);
+            }
+        }
+    }
+}
@@ -4,13 +4,17 @@ use anyhow::bail;
 use euclid::Vector2D;
 use lyon_geom::{Box2D, LineSegment, Point};
 use pathfinding::{kuhn_munkres::kuhn_munkres, prelude::Matrix};
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    attendee_cluster::cluster_attendees,
     board_options::BoardOptions,
+    constraints::Constraints,
     float::{Float, F32, F64},
     geom::tangent_to_circle,
+    spatial_index::SpatialIndex,
     vec2::Vec2,
-    Pillar, Placement, Problem, Solution,
+    Attendee, Pillar, Placement, Problem, Solution,
 };
 
 use anyhow::Result;
@@ -19,11 +23,18 @@ type P = Vector2D<f64, euclid::UnknownUnit>;
 
 const MUSICIAN_R: f64 = 5.;
 
-#[derive(Clone, Debug)]
+// Hungarian weight of the assignments breaking the constraints, lower than any sum of
+// the others.
+const FORBIDDEN: i64 = -(1 << 50);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct Board<F: Float = F64> {
     pub problem_id: u32,
     pub solver: String,
     // NB: stage is modified
+    // Not in the snapshots, which are given the problem again.
+    #[serde(skip)]
     pub prob: Problem,
 
     walls: Vec<LineSegment<f64>>,
@@ -31,6 +42,8 @@ pub struct Board<F: Float = F64> {
     // m -> position
     // musicians + pillars
     ps: Vec<Option<(P, f64)>>,
+    // placed musicians and pillars near the stage, indexed by position
+    index: SpatialIndex,
     // m -> important audience ids sorted by args
     aids: Vec2<(F, u32)>,
 
@@ -59,6 +72,20 @@ pub struct Board<F: Float = F64> {
     volumes: Vec<f64>,
 
     options: BoardOptions,
+
+    // The original attendees if prob.attendees are clustered.
+    #[serde(skip)]
+    exact_attendees: Option<Vec<Attendee>>,
+    // ins -> error bound of a musician (see AttendeeClusters)
+    clustering_error_bounds: Vec<f64>,
+
+    constraints: Constraints,
+    // m -> whether m is pinned
+    pinned: Vec<bool>,
+    // m -> where pinned m was first placed
+    pinned_positions: Vec<Option<P>>,
+    // m -> indices of constraints.regions restricting m
+    rules: Vec<Vec<usize>>,
 }
 
 impl Board<F64> {
@@ -102,13 +129,15 @@ impl Board<F32> {
 impl<F: Float> Board<F> {
     pub fn new_with_options<T: AsRef<str>>(
         problem_id: u32,
-        mut prob: Problem,
+        prob: Problem,
         solver: T,
         use_visibility: bool,
         walls: Vec<LineSegment<f64>>,
         extra_pillars: Vec<Pillar>,
         options: BoardOptions,
     ) -> Self {
+        let (prob, exact_attendees, clustering_error_bounds) = Self::board_problem(prob, &options);
+
         let n = prob.musicians.len();
         let m = prob.attendees.len();
 
@@ -134,10 +163,16 @@ impl<F: Float> Board<F> {
             available_musician[*i] = Some(m);
         }
 
-        prob.stage = Box2D::new(
-            prob.stage.min + P::new(10., 10.),
-            prob.stage.max - P::new(10., 10.),
-        );
+        let mut index = SpatialIndex::new(prob.stage, MUSICIAN_R * 2.);
+        let reach = prob.stage.inflate(MUSICIAN_R * 4., MUSICIAN_R * 4.);
+        for (i, p) in ps.iter().enumerate() {
+            // Pillars far from the stage never collide with musicians.
+            if let Some((p, _)) = p {
+                if reach.contains(p.to_point()) {
+                    index.insert(i, *p);
+                }
+            }
+        }
 
         Self {
             problem_id,
@@ -145,6 +180,7 @@ impl<F: Float> Board<F> {
             prob,
             walls,
             ps,
+            index,
             aids,
             aids_rev,
             blocks,
@@ -156,7 +192,157 @@ impl<F: Float> Board<F> {
             available_musician,
             volumes: vec![1.; n],
             options,
+            exact_attendees,
+            clustering_error_bounds,
+            constraints: Constraints::default(),
+            pinned: vec![false; n],
+            pinned_positions: vec![None; n],
+            rules: vec![vec![]; n],
+        }
+    }
+
+    // The problem as the board keeps it: the stage is shrunk to where the centers can be,
+    // and the attendees are clustered if the options say so. Returns also the exact
+    // attendees and the error bounds of the clustering.
+    fn board_problem(
+        mut prob: Problem,
+        options: &BoardOptions,
+    ) -> (Problem, Option<Vec<Attendee>>, Vec<f64>) {
+        let mut exact_attendees = None;
+        let mut clustering_error_bounds = vec![];
+        if let Some((min_distance, angle)) = options.attendee_clustering {
+            let clusters = cluster_attendees(&prob.attendees, prob.stage, min_distance, angle);
+            exact_attendees = Some(std::mem::replace(&mut prob.attendees, clusters.attendees));
+            clustering_error_bounds = clusters.error_bounds;
+        }
+
+        prob.stage = Box2D::new(
+            prob.stage.min + P::new(10., 10.),
+            prob.stage.max - P::new(10., 10.),
+        );
+
+        (prob, exact_attendees, clustering_error_bounds)
+    }
+
+    // Gives the problem back to a board restored from a snapshot.
+    pub(crate) fn attach_problem(&mut self, prob: Problem) {
+        let (prob, exact_attendees, _) = Self::board_problem(prob, &self.options);
+        self.prob = prob;
+        self.exact_attendees = exact_attendees;
+    }
+
+    // Fails if the constraints refer to musicians or instruments of another problem.
+    // The pinned musicians already placed are pinned where they are.
+    pub fn set_constraints(&mut self, constraints: Constraints) -> Result<()> {
+        constraints.validate(&self.prob)?;
+
+        let n = self.prob.musicians.len();
+        self.pinned = vec![false; n];
+        self.pinned_positions = vec![None; n];
+        for m in constraints.pinned.iter() {
+            self.pinned[*m] = true;
+            self.pinned_positions[*m] = self.ps[*m].map(|(p, _)| p);
+        }
+        self.rules = constraints.rules_of(&self.prob.musicians);
+        self.constraints = constraints;
+        Ok(())
+    }
+
+    pub fn constraints(&self) -> &Constraints {
+        &self.constraints
+    }
+
+    pub fn is_pinned(&self, m: usize) -> bool {
+        self.pinned.get(m).copied().unwrap_or(false)
+    }
+
+    // Returns whether the region rules allow m at the position.
+    pub fn allows(&self, m: usize, position: Point<f64>) -> bool {
+        let Some(rules) = self.rules.get(m) else { return true };
+        rules.is_empty()
+            || rules
+                .iter()
+                .any(|i| self.constraints.regions[*i].region.contains(position))
+    }
+
+    // Returns whether m can take the position of m2 under the constraints, e.g. by an
+    // instrument reassignment.
+    fn can_take(&self, m: usize, m2: usize) -> bool {
+        if m == m2 {
+            return true;
+        }
+        if self.is_pinned(m) || self.is_pinned(m2) {
+            return false;
+        }
+        self.ps[m2].is_none_or(|(p, _)| self.allows(m, p.to_point()))
+    }
+
+    // Returns whether m and m2 can exchange their positions under the constraints.
+    pub fn can_exchange(&self, m: usize, m2: usize) -> bool {
+        self.can_take(m, m2) && self.can_take(m2, m)
+    }
+
+    pub fn is_approximate(&self) -> bool {
+        self.exact_attendees.is_some()
+    }
+
+    // Upper bound of |score() - exact score| caused by the attendee clustering.
+    // Differences in blocking are not taken into account, i.e. it assumes that a
+    // cluster is blocked iff all of its members are blocked.
+    pub fn clustering_error_bound(&self) -> f64 {
+        if !self.is_approximate() {
+            return 0.;
+        }
+        let mut res = 0.;
+        for (m, p) in self.musicians().iter().enumerate() {
+            if p.is_some() {
+                let e = self.clustering_error_bounds[self.prob.musicians[m]];
+                res += self.volumes[m] * self.qs[m] * e + 1.;
+            }
+        }
+        res
+    }
+
+    // Returns the board with the exact attendees and the same placements and volumes.
+    pub fn to_exact(&self) -> Self {
+        let Some(attendees) = &self.exact_attendees else {
+            return self.clone();
+        };
+
+        let mut prob = self.prob.clone();
+        prob.attendees = attendees.clone();
+        prob.stage = Box2D::new(
+            prob.stage.min - P::new(10., 10.),
+            prob.stage.max + P::new(10., 10.),
+        );
+        let n = prob.musicians.len();
+        let extra_pillars = self.ps[n + prob.pillars.len()..]
+            .iter()
+            .map(|p| {
+                let (c, r) = p.unwrap();
+                Pillar {
+                    center: c.to_point(),
+                    radius: r,
+                }
+            })
+            .collect();
+
+        let mut board = Self::new_with_options(
+            self.problem_id,
+            prob,
+            &self.solver,
+            self.use_visibility,
+            self.walls.clone(),
+            extra_pillars,
+            self.options.clone().without_attendee_clustering(),
+        );
+        for (m, p) in self.musicians().iter().enumerate() {
+            if let Some((p, _)) = p {
+                board.try_place(m, p.to_point()).unwrap();
+            }
+            board.set_volume(m, self.volumes[m]);
         }
+        board
     }
 
     pub fn score(&self) -> f64 {
@@ -223,6 +409,57 @@ impl<F: Float> Board<F> {
         res
     }
 
+    // Weight of attendee a in the impact of m, i.e. the visibility in the visibility
+    // mode, and 1 if m sees a and 0 otherwise in the normal mode.
+    pub fn seeing_weight(&self, m: usize, a: usize) -> f64 {
+        let Some(j) = self.aids_rev.get(m, a) else { return 0. };
+        if self.use_visibility {
+            self.visibility[m][a]
+        } else if *self.blocks.get(m, *j) > 0 {
+            0.
+        } else {
+            1.
+        }
+    }
+
+    // Derivative of the score with respect to the position of m, ignoring the rounding
+    // and treating the seeing weights of m as fixed.
+    // For v2 problems, it includes the change of the closeness factors of m and of the
+    // musicians with the same instrument.
+    pub fn gradient(&self, m: usize) -> P {
+        let (p, _) = self.ps[m].unwrap();
+        let ins = self.prob.musicians[m];
+
+        let mut res = P::zero();
+        for j in 0..self.aids.row_len(m) {
+            let a = self.aids.get(m, j).1 as usize;
+            let w = self.seeing_weight(m, a);
+            if w == 0. {
+                continue;
+            }
+            let a = &self.prob.attendees[a];
+            let d = a.position.to_vector() - p;
+            // d/dp 1/|a - p|^2 = 2 (a - p) / |a - p|^4
+            res += d * (w * 2_000_000. * a.tastes[ins] / d.square_length().powi(2));
+        }
+        res *= self.volumes[m] * self.qs[m];
+
+        if self.prob.is_v2() {
+            for i in 0..self.prob.musicians.len() {
+                if i == m || self.prob.musicians[i] != ins {
+                    continue;
+                }
+                let Some((q, _)) = self.ps[i] else { continue };
+                let d = p - q;
+                // 1/|p - q| appears in both q_m and q_i.
+                let w = self.volumes[m] * self.impacts[m] + self.volumes[i] * self.impacts[i];
+                res -= d * (w / d.length().powi(3));
+            }
+        }
+
+        res
+    }
+
     pub fn contribution_for(&self, m: usize, a: usize) -> f64 {
         let Some(j) = self.aids_rev.get(m, a) else {return 0.0};
 
@@ -270,12 +507,12 @@ impl<F: Float> Board<F> {
         if !bb.contains(position) {
             bail!("not on stage {:?} {:?}", position, self.prob.stage);
         }
-        for p in self.ps.iter() {
-            if let Some((p, _)) = p {
-                if (*p - position.to_vector()).square_length() < 100. {
-                    bail!("too close to another musician {:?}: {:?}", p, position);
-                }
-            }
+        if let Some((_, p)) = self
+            .index
+            .near(position.to_vector(), MUSICIAN_R * 2.)
+            .find(|(_, p)| (*p - position.to_vector()).square_length() < 100.)
+        {
+            bail!("too close to another musician {:?}: {:?}", p, position);
         }
         if self.ps[i].is_some() {
             bail!("already placed");
@@ -285,8 +522,13 @@ impl<F: Float> Board<F> {
     }
 
     fn place(&mut self, m: usize, p: P) {
+        if self.is_pinned(m) && self.pinned_positions[m].is_none() {
+            self.pinned_positions[m] = Some(p);
+        }
+
         // Update ps and impacts
         self.ps[m] = Some((p, MUSICIAN_R));
+        self.index.insert(m, p);
 
         // Update qs
         self.update_qs(m, true);
@@ -356,23 +598,35 @@ impl<F: Float> Board<F> {
     }
 
     // Returns whether i can be moved to the position.
+    // The constraints are checked here, but not by try_place() to restore positions.
     pub fn can_place(&self, i: usize, position: Point<f64>) -> bool {
         let mut bb = self.prob.stage;
         bb.max += P::new(1e-9, 1e-9);
         if !bb.contains(position) {
             return false;
         }
-        for (ix, p) in self.ps.iter().enumerate() {
-            if ix == i {
-                continue;
-            }
-            if let Some((p, _)) = p {
-                if (*p - position.to_vector()).square_length() < 100. {
-                    return false;
-                }
-            }
+        // Also when i is unplaced to be moved.
+        if self.is_pinned(i) && self.pinned_positions[i].is_some_and(|p| p != position.to_vector())
+        {
+            return false;
         }
-        true
+        if !self.allows(i, position) {
+            return false;
+        }
+        !self
+            .index
+            .near(position.to_vector(), MUSICIAN_R * 2.)
+            .any(|(ix, p)| ix != i && (p - position.to_vector()).square_length() < 100.)
+    }
+
+    // Returns the placed musicians whose centers are within distance r from p (inclusive).
+    // e.g. neighbors_within(p, 10.) finds the musicians touching a musician at p.
+    pub fn neighbors_within(&self, p: Point<f64>, r: f64) -> Vec<usize> {
+        let n = self.prob.musicians.len();
+
+        let mut res = self.index.within(p.to_vector(), r);
+        res.retain(|i| *i < n);
+        res
     }
 
     pub fn unplace(&mut self, m: usize) {
@@ -385,6 +639,7 @@ impl<F: Float> Board<F> {
         self.update_qs(m, false);
 
         // Update ps and impacts.
+        self.index.remove(m, p);
         self.ps[m] = None;
         self.impacts[m] = 0.;
 
@@ -697,6 +952,10 @@ impl<F: Float> Board<F> {
 
         for m in 0..self.musicians().len() {
             for m2 in 0..self.musicians().len() {
+                if !self.can_take(m, m2) {
+                    weights[m][m2] = FORBIDDEN;
+                    continue;
+                }
                 if self.musicians()[m2].is_none() {
                     continue;
                 }
@@ -711,21 +970,128 @@ impl<F: Float> Board<F> {
 
         let (_, assignment) = kuhn_munkres(&matrix);
 
-        let ps: Vec<_> = self
-            .musicians()
+        let all = (0..self.prob.musicians.len()).collect::<Vec<_>>();
+        let ps = self.positions();
+        self.reassign(&all, &ps, &assignment);
+    }
+
+    // Instrument reassignment aware of the closeness factor q of v2 problems.
+    // The hungarian weights are linearized around the current q values, i.e. the q of
+    // instrument ins on the position of m2 is computed from the current instruments of
+    // the other positions. This is repeated up to max_rounds times while the score
+    // improves, and the score never gets worse than the input.
+    pub fn hungarian_v2(&mut self, max_rounds: usize) {
+        let all = (0..self.prob.musicians.len()).collect::<Vec<_>>();
+        for _ in 0..max_rounds {
+            if !self.hungarian_among(&all) {
+                break;
+            }
+        }
+    }
+
+    // Same as hungarian_v2 with a single round, but only the musicians in ms exchange
+    // their positions and the others are kept fixed. O(|ms| * |musicians| + |ms|^3).
+    // Returns true if the score improved.
+    pub fn hungarian_subset(&mut self, ms: &[usize]) -> bool {
+        self.hungarian_among(ms)
+    }
+
+    // Runs hungarian_subset on the musicians placed inside region.
+    pub fn hungarian_in_region(&mut self, region: Box2D<f64>) -> bool {
+        let ms = (0..self.prob.musicians.len())
+            .filter(|m| self.ps[*m].is_some_and(|(p, _)| region.contains(p.to_point())))
+            .collect::<Vec<_>>();
+        self.hungarian_among(&ms)
+    }
+
+    fn hungarian_among(&mut self, ms: &[usize]) -> bool {
+        if ms.len() < 2 {
+            return false;
+        }
+
+        let prev_score = self.score();
+
+        let ps = ms
+            .iter()
+            .map(|m| self.ps[*m].map(|(p, _)| p.to_point()))
+            .collect::<Vec<_>>();
+
+        let mut inss = ms
+            .iter()
+            .map(|m| self.prob.musicians[*m])
+            .collect::<Vec<_>>();
+        inss.sort();
+        inss.dedup();
+
+        // i -> k -> weight if the position of ms[i] gets inss[k].
+        let mut ws = vec![vec![0; inss.len()]; ms.len()];
+        for (i, m2) in ms.iter().enumerate() {
+            let Some(p2) = ps[i] else { continue };
+
+            // ins -> q
+            let mut qs = vec![1.; self.available_musician.len()];
+            if self.prob.is_v2() {
+                for (m3, q) in self.ps[..self.prob.musicians.len()].iter().enumerate() {
+                    let Some((p3, _)) = q else { continue };
+                    if m3 != *m2 {
+                        qs[self.prob.musicians[m3]] += 1. / (p2.to_vector() - *p3).length();
+                    }
+                }
+            }
+
+            for (k, ins) in inss.iter().enumerate() {
+                ws[i][k] = (qs[*ins] * self.contribution_if_instrument(*m2, *ins)).max(0.) as i64;
+            }
+        }
+
+        let weights = ms
+            .iter()
+            .map(|m| {
+                let k = inss.binary_search(&self.prob.musicians[*m]).unwrap();
+                (0..ms.len())
+                    .map(|i| {
+                        if self.can_take(*m, ms[i]) {
+                            ws[i][k]
+                        } else {
+                            FORBIDDEN
+                        }
+                    })
+                    .collect()
+            })
+            .collect::<Vec<Vec<i64>>>();
+
+        let matrix = Matrix::from_rows(weights).unwrap();
+
+        let (_, assignment) = kuhn_munkres(&matrix);
+
+        self.reassign(ms, &ps, &assignment);
+
+        if self.score() <= prev_score {
+            let identity = (0..ms.len()).collect::<Vec<_>>();
+            self.reassign(ms, &ps, &identity);
+            return false;
+        }
+        true
+    }
+
+    fn positions(&self) -> Vec<Option<Point<f64>>> {
+        self.musicians()
             .iter()
             .map(|p| p.map(|(p, _)| p.to_point()))
-            .collect();
+            .collect()
+    }
 
-        for m in 0..ps.len() {
-            if ps[m].is_some() {
-                self.unplace(m);
+    // Moves musician ms[i] to ps[assignment[i]].
+    fn reassign(&mut self, ms: &[usize], ps: &[Option<Point<f64>>], assignment: &[usize]) {
+        for m in ms.iter() {
+            if self.ps[*m].is_some() {
+                self.unplace(*m);
             }
         }
 
-        for (m, m2) in assignment.into_iter().enumerate() {
-            if let Some(p) = ps[m2] {
-                self.try_place(m, p).unwrap();
+        for (i, j) in assignment.iter().enumerate() {
+            if let Some(p) = ps[*j] {
+                self.try_place(ms[i], p).unwrap();
             }
         }
     }
@@ -744,6 +1110,17 @@ impl<F: Float> Board<F> {
             panic!("cannot swap musicians if use_visibility is set");
         }
 
+        for i in [m, m2] {
+            if let Some((p, _)) = self.ps[i] {
+                self.index.remove(i, p);
+            }
+        }
+        for (i, j) in [(m, m2), (m2, m)] {
+            if let Some((p, _)) = self.ps[j] {
+                self.index.insert(i, p);
+            }
+        }
+
         // Swap ps, aids, aids_rev, volumes, blocks
         self.ps.swap(m, m2);
         self.aids.swap_rows(m, m2);
@@ -791,7 +1168,11 @@ mod tests {
     use lyon_geom::{Box2D, Point};
     use rand::{rngs::StdRng, Rng, SeedableRng};
 
-    use crate::{board::Board, board_options::BoardOptions, evaluate, Attendee, Problem, Solution};
+    use crate::{
+        board::{Board, P},
+        board_options::BoardOptions,
+        evaluate, Attendee, Pillar, Problem, Solution,
+    };
 
     #[test]
     fn test_board() {
@@ -843,6 +1224,276 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_can_place() {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let problem = Problem::read_from_file("../problems/42.json").unwrap();
+        let mut board = Board::new(42, problem.clone(), "test_solver", false);
+
+        let random_point = |rng: &mut StdRng, board: &Board| {
+            let x: f64 = rng.gen_range(board.prob.stage.min.x..board.prob.stage.max.x);
+            let y: f64 = rng.gen_range(board.prob.stage.min.y..board.prob.stage.max.y);
+            Point::new(x, y)
+        };
+
+        for i in 0..board.prob.musicians.len() {
+            while board.try_place(i, random_point(&mut rng, &board)).is_err() {}
+        }
+        board.unplace(0);
+
+        for _ in 0..1000 {
+            let p = random_point(&mut rng, &board);
+
+            let expected = board.musicians().iter().enumerate().all(|(i, q)| {
+                i == 1 || q.map_or(true, |(q, _)| (q - p.to_vector()).square_length() >= 100.)
+            });
+            assert_eq!(board.can_place(1, p), expected);
+
+            let mut expected = (0..board.prob.musicians.len())
+                .filter(|i| {
+                    board.musicians()[*i]
+                        .map_or(false, |(q, _)| (q - p.to_vector()).square_length() <= 400.)
+                })
+                .collect::<Vec<_>>();
+            let mut actual = board.neighbors_within(p, 20.);
+            expected.sort();
+            actual.sort();
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_hungarian_v2() {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let problem = Problem::read_from_file("../problems/85.json").unwrap();
+        let mut board = Board::new(85, problem, "test_solver", false);
+
+        for i in 0..board.prob.musicians.len() {
+            loop {
+                let x: f64 = rng.gen_range(board.prob.stage.min.x..board.prob.stage.max.x);
+                let y: f64 = rng.gen_range(board.prob.stage.min.y..board.prob.stage.max.y);
+                if board.try_place(i, Point::new(x, y)).is_ok() {
+                    break;
+                }
+            }
+        }
+
+        let prev_score = board.score();
+        board.hungarian_v2(5);
+        assert!(board.score() >= prev_score);
+
+        // Musicians outside the region must stay.
+        let stage = board.prob.stage;
+        let region = Box2D::new(stage.min, stage.center());
+        let before = board.musicians().to_vec();
+        let prev_score = board.score();
+        board.hungarian_in_region(region);
+        assert!(board.score() >= prev_score);
+        for (p, q) in before.iter().zip(board.musicians()) {
+            let (p, q) = (p.unwrap().0.to_point(), q.unwrap().0.to_point());
+            assert!(region.contains(p) || p == q);
+            assert_eq!(region.contains(p), region.contains(q));
+        }
+    }
+
+    #[test]
+    fn test_hungarian_v2_small() {
+        for seed in 0..20 {
+            let (prev_score, score, best) = hungarian_v2_small(seed);
+            assert!(prev_score <= score && score <= best);
+        }
+
+        // An instance on which the closeness factors change the best assignment.
+        let (prev_score, score, best) = hungarian_v2_small(55);
+        assert!(prev_score < score);
+        assert_eq!(score, best);
+    }
+
+    // Places 5 musicians in a row on a random v2 instance and assigns the instruments by
+    // hungarian, which ignores q, and then by hungarian_v2. Returns the scores after
+    // them, and the best score found by brute force over the permutations.
+    fn hungarian_v2_small(seed: u64) -> (f64, f64, f64) {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let n = 5;
+        let problem = Problem {
+            room: Box2D::new(Point::new(0.0, 0.0), Point::new(400.0, 400.0)),
+            stage: Box2D::new(Point::new(100.0, 100.0), Point::new(300.0, 300.0)),
+            musicians: (0..n).map(|i| i % 2).collect(),
+            attendees: (0..4)
+                .map(|i| Attendee {
+                    position: Point::new(50. + 100. * i as f64, 50.),
+                    tastes: (0..2).map(|_| rng.gen_range(-1000.0..1000.0)).collect(),
+                })
+                .collect(),
+            // v2, but the pillar blocks nothing.
+            pillars: vec![Pillar {
+                center: Point::new(390., 390.),
+                radius: 1.,
+            }],
+        };
+        let ps = (0..n)
+            .map(|i| Point::new(150. + 10. * i as f64, 150. + rng.gen_range(0.0..100.0)))
+            .collect::<Vec<_>>();
+        let score_of = |perm: &[usize]| {
+            let mut board = Board::new(0, problem.clone(), "test_solver", false);
+            for (m, i) in perm.iter().enumerate() {
+                board.try_place(m, ps[*i]).unwrap();
+            }
+            board.score()
+        };
+
+        let mut best = f64::MIN;
+        let mut perm = vec![0; n];
+        for k in 0..n.pow(n as u32) {
+            let mut k = k;
+            for i in perm.iter_mut() {
+                *i = k % n;
+                k /= n;
+            }
+            let mut used = perm.clone();
+            used.sort();
+            used.dedup();
+            if used.len() == n {
+                best = best.max(score_of(&perm));
+            }
+        }
+
+        let mut board = Board::new(0, problem.clone(), "test_solver", false);
+        for (m, p) in ps.iter().enumerate() {
+            board.try_place(m, *p).unwrap();
+        }
+        board.hungarian();
+        let prev_score = board.score();
+        board.hungarian_v2(5);
+        (prev_score, board.score(), best)
+    }
+
+    #[test]
+    fn test_attendee_clustering() {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let problem = Problem::read_from_file("../problems/42.json").unwrap();
+        let options = BoardOptions::default().with_attendee_clustering(100., 0.1);
+        let mut board = Board::new_with_options(
+            42,
+            problem.clone(),
+            "test_solver",
+            false,
+            vec![],
+            vec![],
+            options,
+        );
+        assert!(board.is_approximate());
+
+        // Without blockers the error is within the bound.
+        for i in 0..board.prob.musicians.len() {
+            loop {
+                let x: f64 = rng.gen_range(board.prob.stage.min.x..board.prob.stage.max.x);
+                let y: f64 = rng.gen_range(board.prob.stage.min.y..board.prob.stage.max.y);
+                if board.try_place(i, Point::new(x, y)).is_ok() {
+                    break;
+                }
+            }
+            let exact = board.to_exact();
+            assert!(!exact.is_approximate());
+            assert!((board.score() - exact.score()).abs() <= board.clustering_error_bound());
+            board.unplace(i);
+        }
+
+        for i in 0..board.prob.musicians.len() {
+            loop {
+                let x: f64 = rng.gen_range(board.prob.stage.min.x..board.prob.stage.max.x);
+                let y: f64 = rng.gen_range(board.prob.stage.min.y..board.prob.stage.max.y);
+                if board.try_place(i, Point::new(x, y)).is_ok() {
+                    break;
+                }
+            }
+        }
+
+        let solution: Solution = board.to_exact().try_into().unwrap();
+        assert_eq!(board.to_exact().score(), evaluate(&problem, &solution));
+    }
+
+    #[test]
+    fn test_gradient() {
+        let problem = Problem {
+            room: Box2D::new(Point::new(0.0, 0.0), Point::new(1000.0, 1000.0)),
+            stage: Box2D::new(Point::new(100.0, 100.0), Point::new(300.0, 300.0)),
+            musicians: vec![0, 0, 1, 0],
+            attendees: vec![
+                Attendee {
+                    position: Point::new(50., 120.),
+                    tastes: vec![300.0, -100.0],
+                },
+                Attendee {
+                    position: Point::new(400., 250.),
+                    tastes: vec![-200.0, 500.0],
+                },
+                Attendee {
+                    position: Point::new(200., 50.),
+                    tastes: vec![1000.0, 10.0],
+                },
+            ],
+            // v2, but the pillar blocks nothing.
+            pillars: vec![Pillar {
+                center: Point::new(900., 900.),
+                radius: 5.,
+            }],
+        };
+        // No musician blocks another.
+        let ps = [
+            Point::new(150., 160.),
+            Point::new(230., 270.),
+            Point::new(260., 120.),
+            Point::new(180., 250.),
+        ];
+
+        // Score without rounding computed from scratch.
+        let score = |ps: &[Point<f64>]| {
+            let mut res = 0.;
+            for (m, p) in ps.iter().enumerate() {
+                let ins = problem.musicians[m];
+                let mut q = 1.;
+                for (m2, p2) in ps.iter().enumerate() {
+                    if m2 != m && problem.musicians[m2] == ins {
+                        q += 1. / (*p - *p2).length();
+                    }
+                }
+                for a in problem.attendees.iter() {
+                    res += q * 1e6 * a.tastes[ins] / (a.position - *p).square_length();
+                }
+            }
+            res
+        };
+
+        let mut board = Board::new(100, problem.clone(), "test_solver", false);
+        for (m, p) in ps.iter().enumerate() {
+            board.try_place(m, *p).unwrap();
+        }
+
+        for m in 0..ps.len() {
+            let g = board.gradient(m);
+            let h = 1e-4;
+            for (dx, dy, expected) in [(h, 0., g.x), (0., h, g.y)] {
+                let mut ps1 = ps;
+                let mut ps2 = ps;
+                ps1[m] += P::new(dx, dy);
+                ps2[m] -= P::new(dx, dy);
+                let actual = (score(&ps1) - score(&ps2)) / (2. * h);
+                assert!(
+                    (actual - expected).abs() <= 1e-3 * actual.abs().max(1.),
+                    "m = {}: {} vs {}",
+                    m,
+                    actual,
+                    expected
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_tangent() {
         for flip in [false, true] {
@@ -1,7 +1,11 @@
-#[derive(Debug, Clone)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BoardOptions {
     pub(crate) important_attendees_ratio: f64,
     pub(crate) important_musician_range_squared: f64,
+    // (min_distance, angle) of cluster_attendees
+    pub(crate) attendee_clustering: Option<(f64, f64)>,
 }
 
 impl Default for BoardOptions {
@@ -9,6 +13,7 @@ impl Default for BoardOptions {
         Self {
             important_attendees_ratio: 1.0,
             important_musician_range_squared: f64::INFINITY,
+            attendee_clustering: None,
         }
     }
 }
@@ -23,4 +28,16 @@ impl BoardOptions {
         self.important_musician_range_squared = range * range;
         self
     }
+
+    // Approximates the attendees farther than min_distance from the stage by clusters.
+    // See attendee_cluster::cluster_attendees.
+    pub fn with_attendee_clustering(mut self, min_distance: f64, angle: f64) -> Self {
+        self.attendee_clustering = Some((min_distance, angle));
+        self
+    }
+
+    pub fn without_attendee_clustering(mut self) -> Self {
+        self.attendee_clustering = None;
+        self
+    }
 }
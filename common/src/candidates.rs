@@ -0,0 +1,372 @@
+use euclid::Vector2D;
+
+use crate::{
+    board::Board,
+    float::Float,
+    geom::{
+        circles_tangenting_circles, circles_tangenting_line_and_circle, circles_tangenting_lines,
+        tangent_circle,
+    },
+};
+
+type P = Vector2D<f64, euclid::UnknownUnit>;
+
+const R: f64 = 5.0;
+const EPS: f64 = 1e-9;
+
+fn is_narrow<F: Float>(board: &Board<F>) -> bool {
+    let stage = board.prob.stage;
+    stage.min.x == stage.max.x || stage.min.y == stage.max.y
+}
+
+fn can_place<F: Float>(board: &Board<F>, m: usize, p: P) -> bool {
+    let stage = board.prob.stage;
+    p.x >= stage.min.x
+        && p.x <= stage.max.x
+        && p.y >= stage.min.y
+        && p.y <= stage.max.y
+        && board.can_place(m, p.to_point())
+}
+
+// The 4 edges of the area musicians can be placed on, moved outwards by the radius of
+// a musician, so that the circles tangent to them have their centers on the edges.
+pub fn stage_segments<F: Float>(board: &Board<F>) -> Vec<(P, P)> {
+    let stage = board.prob.stage;
+    let (left, right) = (stage.min.x - R, stage.max.x + R);
+    let (bottom, top) = (stage.min.y - R, stage.max.y + R);
+
+    let p00 = P::new(left, bottom);
+    let p01 = P::new(left, top);
+    let p10 = P::new(right, bottom);
+    let p11 = P::new(right, top);
+
+    vec![(p00, p01), (p00, p10), (p01, p11), (p10, p11)]
+}
+
+// Segments between the musicians in ms and the attendees they earn the most from, in
+// the descending order of the contributions. At most k segments are returned.
+// Putting a musician next to these segments avoids blocking them.
+pub fn important_segments<F: Float>(board: &Board<F>, ms: &[usize], k: usize) -> Vec<(P, P)> {
+    let mut segs = vec![];
+    for m in ms.iter() {
+        let Some((p, _)) = board.musicians()[*m] else {
+            continue;
+        };
+        for (a, attendee) in board.prob.attendees.iter().enumerate() {
+            let c = board.contribution_for(*m, a);
+            if c > 0. {
+                segs.push((c, attendee.position.to_vector(), p));
+            }
+        }
+    }
+    segs.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+    segs.truncate(k);
+    segs.into_iter().map(|(_, p, q)| (p, q)).collect()
+}
+
+// Points m can be placed on whose circles of the musician radius are tangent to two of
+// segs, to one of segs and a musician at one of circles, or to two musicians at circles.
+pub fn tangent_candidates<F: Float>(
+    board: &Board<F>,
+    m: usize,
+    segs: &[(P, P)],
+    circles: &[P],
+) -> Vec<P> {
+    let mut res = vec![];
+
+    // Musicians on a narrow stage have to touch the edges exactly.
+    let r = if is_narrow(board) { R } else { R + EPS };
+
+    // Tangent to two segs
+    for i in 0..segs.len() {
+        for j in 0..i {
+            let (p0, p1) = segs[i];
+            let (q0, q1) = segs[j];
+
+            for c in circles_tangenting_lines(p0, p1, q0, q1, r) {
+                if can_place(board, m, c) {
+                    res.push(c);
+                }
+            }
+        }
+    }
+
+    // Tangent to a seg and a circle
+    for (p0, p1) in segs.iter() {
+        for mc in circles.iter() {
+            for c in circles_tangenting_line_and_circle(*p0, *p1, *mc, R, r) {
+                if can_place(board, m, c) {
+                    res.push(c);
+                }
+            }
+        }
+    }
+
+    // Tangent to two circles
+    for mc1 in circles.iter() {
+        for mc2 in circles.iter() {
+            if mc1 == mc2 {
+                continue;
+            }
+
+            if let Some(c) = tangent_circle(*mc1, *mc2, r) {
+                if can_place(board, m, c) {
+                    res.push(c);
+                }
+            }
+        }
+    }
+
+    res
+}
+
+fn distance_to_stage<F: Float>(board: &Board<F>, p: P) -> f64 {
+    let stage = board.prob.stage;
+    let dx = (stage.min.x - p.x).max(p.x - stage.max.x).max(0.);
+    let dy = (stage.min.y - p.y).max(p.y - stage.max.y).max(0.);
+    dx.hypot(dy)
+}
+
+// (center, radius) of the pillars which circles on the stage can be tangent to.
+fn pillars_near_stage<F: Float>(board: &Board<F>) -> Vec<(P, f64)> {
+    board
+        .prob
+        .pillars
+        .iter()
+        .map(|pillar| (pillar.center.to_vector(), pillar.radius))
+        .filter(|(c, r)| distance_to_stage(board, *c) <= r + R + EPS)
+        .collect()
+}
+
+// Points m can be placed on whose circles of the musician radius are tangent to a
+// pillar and one of segs, a musician at one of circles, or another pillar.
+// Points overlapping pillars are excluded.
+pub fn pillar_tangent_candidates<F: Float>(
+    board: &Board<F>,
+    m: usize,
+    segs: &[(P, P)],
+    circles: &[P],
+) -> Vec<P> {
+    let pillars = pillars_near_stage(board);
+    let r = if is_narrow(board) { R } else { R + EPS };
+
+    let mut res = vec![];
+    for (i, (c, cr)) in pillars.iter().enumerate() {
+        for (p0, p1) in segs.iter() {
+            res.extend(circles_tangenting_line_and_circle(*p0, *p1, *c, *cr, r));
+        }
+        for mc in circles.iter() {
+            res.extend(circles_tangenting_circles(*c, *cr, *mc, R, r));
+        }
+        for (c2, cr2) in pillars[..i].iter() {
+            res.extend(circles_tangenting_circles(*c, *cr, *c2, *cr2, r));
+        }
+    }
+
+    res.retain(|p| {
+        can_place(board, m, *p)
+            && pillars
+                .iter()
+                .all(|(c, cr)| (*p - *c).length() >= cr + R - EPS)
+    });
+    res
+}
+
+// Points on the stage in the middle of the angular gaps between the shadows of the
+// pillars, as seen by the k attendees who would hear m the best (the largest taste
+// divided by the squared distance to the stage). Each point is where the bisector of
+// the gap from the attendee enters the stage.
+pub fn shadow_gap_candidates<F: Float>(board: &Board<F>, m: usize, k: usize) -> Vec<P> {
+    if board.prob.pillars.is_empty() {
+        return vec![];
+    }
+
+    let ins = board.prob.musicians[m];
+    let stage = board.prob.stage;
+    let corners = [
+        P::new(stage.min.x, stage.min.y),
+        P::new(stage.min.x, stage.max.y),
+        P::new(stage.max.x, stage.min.y),
+        P::new(stage.max.x, stage.max.y),
+    ];
+
+    let mut attendees = board
+        .prob
+        .attendees
+        .iter()
+        .filter(|a| a.tastes[ins] > 0.)
+        .map(|a| {
+            let p = a.position.to_vector();
+            (
+                a.tastes[ins] / distance_to_stage(board, p).powi(2).max(100.),
+                p,
+            )
+        })
+        .collect::<Vec<_>>();
+    attendees.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+    attendees.truncate(k);
+
+    let mut res = vec![];
+    for (_, a) in attendees {
+        // Angles relative to the direction to the center of the stage.
+        let base = stage.center().to_vector() - a;
+        let angle = |v: P| base.cross(v).atan2(base.dot(v));
+
+        let (mut lo, mut hi, mut far) = (f64::MAX, f64::MIN, 0f64);
+        for c in corners.iter() {
+            let t = angle(*c - a);
+            (lo, hi, far) = (lo.min(t), hi.max(t), far.max((*c - a).length()));
+        }
+
+        let mut shadows = vec![];
+        for pillar in board.prob.pillars.iter() {
+            let v = pillar.center.to_vector() - a;
+            let d = v.length();
+            if d <= pillar.radius || d - pillar.radius > far {
+                continue;
+            }
+            let (t, w) = (angle(v), (pillar.radius / d).asin());
+            if t + w >= lo && t - w <= hi {
+                shadows.push((t - w, t + w));
+            }
+        }
+        if shadows.is_empty() {
+            continue;
+        }
+        shadows.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let mut gaps = vec![];
+        let mut cur = lo;
+        for (s, e) in shadows {
+            if s > cur {
+                gaps.push((cur, s));
+            }
+            cur = cur.max(e);
+        }
+        if cur < hi {
+            gaps.push((cur, hi));
+        }
+
+        for (g0, g1) in gaps {
+            let t = base.angle_from_x_axis().radians + (g0 + g1) / 2.;
+            let dir = P::new(t.cos(), t.sin());
+            if let Some(p) = ray_entry(stage, a, dir) {
+                if can_place(board, m, p) {
+                    res.push(p);
+                }
+            }
+        }
+    }
+    res
+}
+
+// The first point of the ray from a in the direction dir inside bb.
+fn ray_entry(bb: euclid::default::Box2D<f64>, a: P, dir: P) -> Option<P> {
+    let (mut t0, mut t1) = (0f64, f64::MAX);
+    for (o, d, min, max) in [
+        (a.x, dir.x, bb.min.x, bb.max.x),
+        (a.y, dir.y, bb.min.y, bb.max.y),
+    ] {
+        if d.abs() < EPS {
+            if o < min || o > max {
+                return None;
+            }
+            continue;
+        }
+        let (s0, s1) = ((min - o) / d, (max - o) / d);
+        t0 = t0.max(s0.min(s1));
+        t1 = t1.min(s0.max(s1));
+    }
+    if t0 > t1 {
+        return None;
+    }
+    let p = a + dir * t0;
+    Some(P::new(
+        p.x.clamp(bb.min.x, bb.max.x),
+        p.y.clamp(bb.min.y, bb.max.y),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use euclid::default::Box2D;
+    use lyon_geom::{LineSegment, Point};
+
+    use crate::{board::Board, Attendee, Pillar, Problem};
+
+    use super::{
+        important_segments, pillar_tangent_candidates, shadow_gap_candidates, stage_segments,
+        tangent_candidates,
+    };
+
+    #[test]
+    fn test_tangent_candidates() {
+        let problem = Problem::read_from_file("../problems/42.json").unwrap();
+        let mut board = Board::new(42, problem, "test_solver", false);
+
+        let stage = board.prob.stage;
+        board.try_place(0, stage.min).unwrap();
+        board
+            .try_place(1, Point::new(stage.min.x + 10., stage.min.y))
+            .unwrap();
+
+        let placed = [0, 1]
+            .iter()
+            .map(|m| board.musicians()[*m].unwrap().0)
+            .collect::<Vec<_>>();
+        let mut segs = stage_segments(&board);
+        segs.extend(important_segments(&board, &[0, 1], 10));
+
+        let cs = tangent_candidates(&board, 2, &segs, &placed);
+        assert!(!cs.is_empty());
+        for c in cs.iter() {
+            assert!(board.can_place(2, c.to_point()));
+        }
+
+        // The corner next to the placed musicians, touching both of them.
+        assert!(cs.iter().any(|c| {
+            (*c - placed[0]).length() < 10. + 1e-6 && (*c - placed[1]).length() < 10. + 1e-6
+        }));
+    }
+
+    #[test]
+    fn test_pillar_candidates() {
+        let pillar = Pillar {
+            center: Point::new(200., 100.),
+            radius: 20.,
+        };
+        let problem = Problem {
+            room: Box2D::new(Point::new(0., 0.), Point::new(400., 400.)),
+            stage: Box2D::new(Point::new(100., 100.), Point::new(300., 300.)),
+            musicians: vec![0, 0],
+            attendees: vec![Attendee {
+                position: Point::new(200., 20.),
+                tastes: vec![1000.],
+            }],
+            pillars: vec![pillar.clone()],
+        };
+        let board = Board::new(100, problem, "test_solver", false);
+
+        let segs = stage_segments(&board);
+        let cs = pillar_tangent_candidates(&board, 0, &segs, &[]);
+        assert!(!cs.is_empty());
+        for c in cs.iter() {
+            assert!(board.can_place(0, c.to_point()));
+            // Touching the pillar.
+            let d = (c.to_point() - pillar.center).length();
+            assert!((d - pillar.radius - 5.).abs() < 1e-6, "{}", d);
+        }
+
+        // One on each side of the shadow of the pillar.
+        let cs = shadow_gap_candidates(&board, 0, 1);
+        assert_eq!(cs.len(), 2);
+        for c in cs.iter() {
+            assert!(board.can_place(0, c.to_point()));
+            let ray = LineSegment {
+                from: Point::new(200., 20.),
+                to: c.to_point(),
+            };
+            assert!(ray.distance_to_point(pillar.center) > pillar.radius);
+        }
+    }
+}
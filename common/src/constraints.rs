@@ -0,0 +1,282 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use euclid::default::Point2D;
+use serde::{Deserialize, Serialize};
+
+use crate::{transform::Transform, Problem};
+
+type Point = Point2D<f64>;
+
+// Area where some musicians are allowed, in the coordinates of the problem.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Region {
+    Rect { min: (f64, f64), max: (f64, f64) },
+    // Vertices in either order. Self-intersecting polygons are not supported.
+    Polygon(Vec<(f64, f64)>),
+}
+
+impl Region {
+    // The boundary is included.
+    pub fn contains(&self, p: Point) -> bool {
+        match self {
+            Region::Rect { min, max } => {
+                min.0 <= p.x && p.x <= max.0 && min.1 <= p.y && p.y <= max.1
+            }
+            Region::Polygon(vs) => {
+                let mut inside = false;
+                for i in 0..vs.len() {
+                    let (a, b) = (vs[i], vs[(i + 1) % vs.len()]);
+                    // On the edge.
+                    let cross = (b.0 - a.0) * (p.y - a.1) - (b.1 - a.1) * (p.x - a.0);
+                    if cross == 0.
+                        && a.0.min(b.0) <= p.x
+                        && p.x <= a.0.max(b.0)
+                        && a.1.min(b.1) <= p.y
+                        && p.y <= a.1.max(b.1)
+                    {
+                        return true;
+                    }
+                    if (a.1 > p.y) != (b.1 > p.y)
+                        && p.x < a.0 + (p.y - a.1) / (b.1 - a.1) * (b.0 - a.0)
+                    {
+                        inside = !inside;
+                    }
+                }
+                inside
+            }
+        }
+    }
+
+    pub fn transformed(&self, t: &Transform) -> Self {
+        let f = |(x, y): (f64, f64)| {
+            let p = t.point(Point::new(x, y));
+            (p.x, p.y)
+        };
+        match self {
+            Region::Rect { min, max } => {
+                let (p, q) = (f(*min), f(*max));
+                Region::Rect {
+                    min: (p.0.min(q.0), p.1.min(q.1)),
+                    max: (p.0.max(q.0), p.1.max(q.1)),
+                }
+            }
+            Region::Polygon(vs) => Region::Polygon(vs.iter().map(|v| f(*v)).collect()),
+        }
+    }
+}
+
+// Allows the musicians listed, and the ones playing the instruments listed, only in
+// the region.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegionRule {
+    #[serde(default)]
+    pub musicians: Vec<usize>,
+    #[serde(default)]
+    pub instruments: Vec<usize>,
+    pub region: Region,
+}
+
+// Constraints on the moves of the optimizers, e.g. to keep a hand-crafted part of a
+// layout. They are set to a Board by Board::set_constraints(), and Board::can_place()
+// rejects the moves breaking them.
+//
+// Pinned musicians never move from where they are first placed, usually the initial
+// solution. A musician with region rules must be in one of their regions.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Constraints {
+    #[serde(default)]
+    pub pinned: Vec<usize>,
+    #[serde(default)]
+    pub regions: Vec<RegionRule>,
+}
+
+impl Constraints {
+    pub fn from_json(s: &str) -> Result<Self> {
+        Ok(serde_json::from_str(s)?)
+    }
+
+    pub fn read_from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    // Constraints of a solution file are looked up in foo.constraints.json next to
+    // foo.json.
+    pub fn sidecar_of<P: AsRef<Path>>(solution: P) -> PathBuf {
+        solution.as_ref().with_extension("constraints.json")
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pinned.is_empty() && self.regions.is_empty()
+    }
+
+    pub fn validate(&self, problem: &Problem) -> Result<()> {
+        let n = problem.musicians.len();
+        let instruments = problem.attendees.first().map_or(0, |a| a.tastes.len());
+        for m in self.pinned.iter() {
+            if *m >= n {
+                bail!("pinned musician {} does not exist", m);
+            }
+        }
+        for rule in self.regions.iter() {
+            if let Some(m) = rule.musicians.iter().find(|m| **m >= n) {
+                bail!("musician {} of a region does not exist", m);
+            }
+            if let Some(i) = rule.instruments.iter().find(|i| **i >= instruments) {
+                bail!("instrument {} of a region does not exist", i);
+            }
+            if let Region::Polygon(vs) = &rule.region {
+                if vs.len() < 3 {
+                    bail!("polygon with {} vertices", vs.len());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The regions in the coordinates of transform.problem(), for solvers working on it.
+    pub fn transformed(&self, t: &Transform) -> Self {
+        Self {
+            pinned: self.pinned.clone(),
+            regions: self
+                .regions
+                .iter()
+                .map(|r| RegionRule {
+                    region: r.region.transformed(t),
+                    ..r.clone()
+                })
+                .collect(),
+        }
+    }
+
+    // m -> indices of the rules of m.
+    pub(crate) fn rules_of(&self, musicians: &[usize]) -> Vec<Vec<usize>> {
+        musicians
+            .iter()
+            .enumerate()
+            .map(|(m, ins)| {
+                (0..self.regions.len())
+                    .filter(|i| {
+                        let rule = &self.regions[*i];
+                        rule.musicians.contains(&m) || rule.instruments.contains(ins)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use euclid::default::Point2D;
+    use lyon_geom::Point;
+
+    use crate::{
+        board::Board,
+        finetune::{finetune, FinetuneOptions},
+        testing::{self, attendee},
+        transform::Transform,
+        Problem,
+    };
+
+    use super::{Constraints, Region};
+
+    fn problem() -> Problem {
+        testing::problem(
+            vec![0, 1, 0, 1],
+            vec![
+                attendee(20., 150., vec![1000., -100.]),
+                attendee(280., 150., vec![-100., 1000.]),
+            ],
+        )
+    }
+
+    fn constraints() -> Constraints {
+        Constraints::from_json(
+            r#"{
+                "pinned": [0],
+                "regions": [
+                    { "musicians": [2], "region": { "rect": { "min": [110, 110], "max": [150, 190] } } },
+                    { "instruments": [1], "region": { "polygon": [[150, 110], [190, 110], [190, 190]] } }
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_region() {
+        let tri = Region::Polygon(vec![(0., 0.), (10., 0.), (10., 10.)]);
+        assert!(tri.contains(Point2D::new(8., 2.)));
+        assert!(tri.contains(Point2D::new(5., 5.)));
+        assert!(tri.contains(Point2D::new(10., 3.)));
+        assert!(!tri.contains(Point2D::new(2., 8.)));
+        assert!(!tri.contains(Point2D::new(11., 1.)));
+
+        let t = Transform::mirror_x().then(&Transform::translate(euclid::vec2(5., 0.)));
+        assert!(tri.transformed(&t).contains(Point2D::new(-3., 2.)));
+        assert!(!tri.transformed(&t).contains(Point2D::new(4., 2.)));
+    }
+
+    #[test]
+    fn test_validate() {
+        let p = problem();
+        assert!(constraints().validate(&p).is_ok());
+
+        let mut c = constraints();
+        c.pinned.push(4);
+        assert!(c.validate(&p).is_err());
+
+        let mut c = constraints();
+        c.regions[1].instruments.push(2);
+        assert!(c.validate(&p).is_err());
+    }
+
+    #[test]
+    fn test_board_constraints() {
+        let mut board = Board::new(0, problem(), "test", false);
+        let mut c = constraints();
+        c.pinned.push(4);
+        assert!(board.set_constraints(c).is_err());
+        board.set_constraints(constraints()).unwrap();
+        for (m, (x, y)) in [(130., 150.), (180., 120.), (120., 150.), (180., 160.)]
+            .iter()
+            .enumerate()
+        {
+            board.set_volume(m, 10.);
+            assert!(board.can_place(m, Point::new(*x, *y)));
+            board.try_place(m, Point::new(*x, *y)).unwrap();
+        }
+
+        // Pinned.
+        assert!(board.is_pinned(0));
+        assert!(!board.can_place(0, Point::new(130., 170.)));
+        board.unplace(0);
+        assert!(!board.can_place(0, Point::new(130., 170.)));
+        assert!(board.can_place(0, Point::new(130., 150.)));
+        board.try_place(0, Point::new(130., 150.)).unwrap();
+        // Out of the regions.
+        assert!(!board.can_place(2, Point::new(160., 150.)));
+        assert!(!board.can_place(1, Point::new(160., 180.)));
+        assert!(board.can_place(1, Point::new(185., 130.)));
+        assert!(!board.can_exchange(0, 1));
+        assert!(!board.can_exchange(2, 3));
+
+        let before = board.musicians().to_vec();
+        finetune(&mut board, &FinetuneOptions::default().with_max_passes(20));
+        assert_eq!(board.musicians()[0], before[0]);
+        for m in 0..4 {
+            let p = board.musicians()[m].unwrap().0.to_point();
+            assert!(board.allows(m, p), "{} {:?}", m, p);
+        }
+
+        board.hungarian();
+        assert_eq!(board.musicians()[0], before[0]);
+        board.hungarian_v2(3);
+        for m in 0..4 {
+            let p = board.musicians()[m].unwrap().0.to_point();
+            assert!(board.allows(m, p), "{} {:?}", m, p);
+        }
+    }
+}
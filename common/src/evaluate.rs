@@ -338,7 +338,8 @@ impl EvaluationResult {
     }
 }
 
-// Find rough upper bound.
+// Heuristic solution putting each musician near the best edge point.
+// This is NOT an upper bound (see upper_bound::upper_bound).
 pub fn estimate(problem_id: u32, problem: &Problem, solver: &str) -> (f64, Solution) {
     let p1 = Point2D::new(problem.stage.min.x + 10., problem.stage.min.y + 10.);
     let p2 = Point2D::new(problem.stage.min.x + 10., problem.stage.max.y - 10.);
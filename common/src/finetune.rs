@@ -0,0 +1,255 @@
+use euclid::Vector2D;
+use lyon_geom::Point;
+
+use crate::{board::Board, float::Float};
+
+type P = Vector2D<f64, euclid::UnknownUnit>;
+
+#[derive(Debug, Clone)]
+pub struct FinetuneOptions {
+    initial_step: f64,
+    min_step: f64,
+    max_passes: usize,
+    // Also try the 4 axis directions when the others fail.
+    axis_moves: bool,
+    // Only these musicians are moved if set.
+    musicians: Option<Vec<usize>>,
+}
+
+impl Default for FinetuneOptions {
+    fn default() -> Self {
+        Self {
+            initial_step: 4.0,
+            min_step: 0.01,
+            max_passes: 1000,
+            axis_moves: true,
+            musicians: None,
+        }
+    }
+}
+
+impl FinetuneOptions {
+    pub fn with_initial_step(mut self, step: f64) -> Self {
+        self.initial_step = step;
+        self
+    }
+
+    pub fn with_min_step(mut self, step: f64) -> Self {
+        self.min_step = step;
+        self
+    }
+
+    pub fn with_max_passes(mut self, max_passes: usize) -> Self {
+        self.max_passes = max_passes;
+        self
+    }
+
+    pub fn with_axis_moves(mut self, axis_moves: bool) -> Self {
+        self.axis_moves = axis_moves;
+        self
+    }
+
+    pub fn with_musicians(mut self, musicians: Vec<usize>) -> Self {
+        self.musicians = Some(musicians);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PassReport {
+    pub pass: usize,
+    pub step: f64,
+    pub moved: usize,
+    pub score_before: f64,
+    pub score_after: f64,
+}
+
+impl PassReport {
+    pub fn gain(&self) -> f64 {
+        self.score_after - self.score_before
+    }
+}
+
+// Hill climbing of the placed musicians by small moves.
+// Each musician tries to move by step along the gradient of the score, towards the
+// attendee it earns the most from, and optionally along the axes, sliding along the
+// musicians and the stage edges it touches. The step is halved after a pass without
+// any move, until it gets below min_step. The score never decreases.
+pub fn finetune<F: Float>(board: &mut Board<F>, options: &FinetuneOptions) -> Vec<PassReport> {
+    let mut reports = vec![];
+
+    let mut step = options.initial_step;
+    for pass in 0..options.max_passes {
+        let score_before = board.score();
+
+        // Larger contributions first.
+        let mut ms = (0..board.prob.musicians.len())
+            .filter(|m| board.musicians()[*m].is_some())
+            .filter(|m| match &options.musicians {
+                Some(ms) => ms.contains(m),
+                None => true,
+            })
+            .map(|m| (-board.contribution2(m), m))
+            .collect::<Vec<_>>();
+        ms.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let mut moved = 0;
+        for (_, m) in ms {
+            if try_improve(board, m, step, options.axis_moves) {
+                moved += 1;
+            }
+        }
+
+        reports.push(PassReport {
+            pass,
+            step,
+            moved,
+            score_before,
+            score_after: board.score(),
+        });
+
+        if moved == 0 {
+            step /= 2.;
+            if step < options.min_step {
+                break;
+            }
+        }
+    }
+
+    reports
+}
+
+// Direction towards the attendee m earns the most from.
+fn attraction<F: Float>(board: &Board<F>, m: usize) -> Option<P> {
+    let (p, _) = board.musicians()[m].unwrap();
+    let ins = board.prob.musicians[m];
+
+    let mut best = (0., None);
+    for (a, attendee) in board.prob.attendees.iter().enumerate() {
+        if !board.is_musician_seeing(m, a) {
+            continue;
+        }
+        let v = attendee.position.to_vector() - p;
+        let s = attendee.tastes[ins] / v.square_length();
+        if s > best.0 {
+            best = (s, Some(v));
+        }
+    }
+    best.1
+}
+
+// Removes the components of d going into the touching musicians and stage edges.
+fn slide<F: Float>(board: &Board<F>, m: usize, d: P, step: f64) -> P {
+    let (p, _) = board.musicians()[m].unwrap();
+    let stage = board.prob.stage;
+
+    let mut d = d;
+    for m2 in board.neighbors_within(p.to_point(), 10. + step) {
+        if m2 == m {
+            continue;
+        }
+        let n = (p - board.musicians()[m2].unwrap().0).normalize();
+        let dot = d.dot(n);
+        if dot < 0. {
+            d -= n * dot;
+        }
+    }
+    if (p.x - step <= stage.min.x && d.x < 0.) || (p.x + step >= stage.max.x && d.x > 0.) {
+        d.x = 0.;
+    }
+    if (p.y - step <= stage.min.y && d.y < 0.) || (p.y + step >= stage.max.y && d.y > 0.) {
+        d.y = 0.;
+    }
+    d
+}
+
+fn try_improve<F: Float>(board: &mut Board<F>, m: usize, step: f64, axis_moves: bool) -> bool {
+    let (p, _) = board.musicians()[m].unwrap();
+    let stage = board.prob.stage;
+
+    let mut dirs = vec![board.gradient(m)];
+    dirs.extend(attraction(board, m));
+    if axis_moves {
+        dirs.extend([
+            P::new(1., 0.),
+            P::new(-1., 0.),
+            P::new(0., 1.),
+            P::new(0., -1.),
+        ]);
+    }
+
+    let score = board.score();
+    for d in dirs {
+        for d in [d, slide(board, m, d, step)] {
+            if d.square_length() < 1e-18 {
+                continue;
+            }
+            let q = (p + d.normalize() * step).to_point();
+            let q = Point::new(
+                q.x.clamp(stage.min.x, stage.max.x),
+                q.y.clamp(stage.min.y, stage.max.y),
+            );
+            if q == p.to_point() || !board.can_place(m, q) {
+                continue;
+            }
+
+            board.unplace(m);
+            board.try_place(m, q).unwrap();
+            if board.score() > score {
+                return true;
+            }
+            board.unplace(m);
+            board.try_place(m, p.to_point()).unwrap();
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use lyon_geom::Point;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::{board::Board, Problem};
+
+    use super::{finetune, FinetuneOptions};
+
+    #[test]
+    fn test_finetune() {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let problem = Problem::read_from_file("../problems/85.json").unwrap();
+        let mut board = Board::new(85, problem, "test_solver", false);
+        for i in 0..board.prob.musicians.len() {
+            loop {
+                let x: f64 = rng.gen_range(board.prob.stage.min.x..board.prob.stage.max.x);
+                let y: f64 = rng.gen_range(board.prob.stage.min.y..board.prob.stage.max.y);
+                if board.try_place(i, Point::new(x, y)).is_ok() {
+                    break;
+                }
+            }
+        }
+
+        let initial_score = board.score();
+        let options = FinetuneOptions::default().with_max_passes(5);
+        let reports = finetune(&mut board, &options);
+
+        assert!(!reports.is_empty());
+        for r in reports.iter() {
+            assert!(r.gain() >= 0.);
+        }
+        assert_eq!(reports[0].score_before, initial_score);
+        assert_eq!(reports.last().unwrap().score_after, board.score());
+        assert!(board.score() > initial_score);
+
+        // The others stay still.
+        let before = board.musicians().to_vec();
+        let options = FinetuneOptions::default()
+            .with_max_passes(5)
+            .with_musicians(vec![0, 1]);
+        finetune(&mut board, &options);
+        for (m, p) in before.iter().enumerate().skip(2) {
+            assert_eq!(board.musicians()[m], *p);
+        }
+    }
+}
@@ -1,10 +1,14 @@
-pub trait Float: std::fmt::Debug + Clone + Copy + Eq + Ord + From<f64> + Into<f64> {
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+pub trait Float:
+    std::fmt::Debug + Clone + Copy + Eq + Ord + From<f64> + Into<f64> + Serialize + DeserializeOwned
+{
     fn new(value: f64) -> Self;
     fn get(&self) -> f64;
     const EPS: f64;
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct F64 {
     value: i64,
 }
@@ -46,7 +50,7 @@ impl From<F64> for f64 {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct F32 {
     value: i32,
 }
@@ -87,6 +87,18 @@ pub fn tangent_circle2(c1: P, c2: P, cr: f64, r: f64) -> Option<P> {
     Some(mid + n * d)
 }
 
+// Returns the centers of the circles with radius r tangenting two circles c1 (radius r1)
+// and c2 (radius r2) from outside. The one on the left hand side of the line c1c2 first.
+pub fn circles_tangenting_circles(c1: P, r1: f64, c2: P, r2: f64, r: f64) -> Vec<P> {
+    let (d1, d2) = (r + r1, r + r2);
+    let d = (c2 - c1).length();
+    if d == 0. || d > d1 + d2 || d < (d1 - d2).abs() {
+        return vec![];
+    }
+    let (p, q) = cross_points_cc2(c1, d1 * d1, c2, d2 * d2);
+    vec![q, p]
+}
+
 // Returns all the circles tangenting the given two lines.
 // Returns an empty vector if the two lines are parallel.
 pub fn circles_tangenting_lines(p1: P, p2: P, q1: P, q2: P, r: f64) -> Vec<P> {
@@ -251,4 +263,21 @@ mod tests {
             assert!(dx < 1e-9 || dy < 1e-9);
         }
     }
+
+    #[test]
+    fn test_circles_tangenting_circles() {
+        let c1 = P::new(0., 0.);
+        let c2 = P::new(20., 0.);
+
+        let res = super::circles_tangenting_circles(c1, 5., c2, 3., 10.);
+
+        assert_eq!(res.len(), 2);
+        assert!(res[0].y > 0. && res[1].y < 0.);
+        for c in res {
+            assert!(((c - c1).length() - 15.).abs() < 1e-9);
+            assert!(((c - c2).length() - 13.).abs() < 1e-9);
+        }
+
+        assert!(super::circles_tangenting_circles(c1, 1., c2, 1., 1.).is_empty());
+    }
 }
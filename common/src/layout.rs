@@ -0,0 +1,284 @@
+use std::f64::consts::PI;
+
+use euclid::default::{Box2D, Point2D, Vector2D};
+
+use crate::{spatial_index::SpatialIndex, Pillar, Problem};
+
+type Point = Point2D<f64>;
+type Vector = Vector2D<f64>;
+
+// Minimum distance between musicians.
+const MIN_DIST: f64 = 10.;
+const EPS: f64 = 1e-9;
+
+// Generators of candidate position sets. All of them take the area musicians can be
+// placed on (i.e. the stage shrunk by 10, which is Board::prob.stage) and return points
+// inside it which are at least 10 away from each other.
+
+// Drops the points outside area.
+pub fn clip(ps: Vec<Point>, area: Box2D<f64>) -> Vec<Point> {
+    let area = area.inflate(EPS, EPS);
+    ps.into_iter()
+        .filter(|p| area.min.x <= p.x && p.x <= area.max.x)
+        .filter(|p| area.min.y <= p.y && p.y <= area.max.y)
+        .collect()
+}
+
+// Drops the points closer than 10 to an earlier point, so earlier points take priority.
+pub fn remove_conflicts(ps: Vec<Point>) -> Vec<Point> {
+    if ps.is_empty() {
+        return ps;
+    }
+    let mut index = SpatialIndex::new(Box2D::from_points(ps.iter()), MIN_DIST);
+
+    let mut res = vec![];
+    for p in ps {
+        let v = p.to_vector();
+        if index
+            .near(v, MIN_DIST)
+            .any(|(_, q)| (q - v).square_length() < MIN_DIST * MIN_DIST - EPS)
+        {
+            continue;
+        }
+        index.insert(res.len(), v);
+        res.push(p);
+    }
+    res
+}
+
+// Points origin + i * a + j * b inside area.
+// a and b have to be at least 10 long and not closer than 10 to each other's multiples
+// for the result to be legal, which is the case for the lattices below.
+pub fn lattice(area: Box2D<f64>, a: Vector, b: Vector, origin: Point) -> Vec<Point> {
+    // Solve p = origin + i * a + j * b for the corners to get the ranges of i and j.
+    let det = a.cross(b);
+    assert!(det.abs() > EPS, "degenerate lattice");
+    let coords = |p: Point| {
+        let d = p - origin;
+        (d.cross(b) / det, a.cross(d) / det)
+    };
+
+    let (mut i0, mut i1, mut j0, mut j1) = (f64::MAX, f64::MIN, f64::MAX, f64::MIN);
+    for p in [
+        area.min,
+        area.max,
+        Point::new(area.min.x, area.max.y),
+        Point::new(area.max.x, area.min.y),
+    ] {
+        let (i, j) = coords(p);
+        (i0, i1, j0, j1) = (i0.min(i), i1.max(i), j0.min(j), j1.max(j));
+    }
+
+    let mut res = vec![];
+    for i in (i0 - EPS).ceil() as i64..=(i1 + EPS).floor() as i64 {
+        for j in (j0 - EPS).ceil() as i64..=(j1 + EPS).floor() as i64 {
+            res.push(origin + a * i as f64 + b * j as f64);
+        }
+    }
+    clip(res, area)
+}
+
+// Square grid of the given spacing starting from area.min.
+pub fn square_lattice(area: Box2D<f64>, spacing: f64) -> Vec<Point> {
+    lattice(
+        area,
+        Vector::new(spacing, 0.),
+        Vector::new(0., spacing),
+        area.min,
+    )
+}
+
+// Hexagonal (triangular) lattice whose neighbors are spacing apart, rotated by angle
+// radians and shifted by offset from the center of area.
+pub fn hex_lattice(area: Box2D<f64>, spacing: f64, angle: f64, offset: Vector) -> Vec<Point> {
+    let a = Vector::from_angle_and_length(euclid::Angle::radians(angle), spacing);
+    let b = Vector::from_angle_and_length(euclid::Angle::radians(angle + PI / 3.), spacing);
+    lattice(area, a, b, area.center() + offset)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Bottom,
+    Top,
+    Left,
+    Right,
+}
+
+// Edges of the stage not touching the walls of the room, i.e. the ones with attendees
+// possibly behind.
+pub fn open_edges(prob: &Problem) -> Vec<Edge> {
+    let (stage, room) = (prob.stage, prob.room);
+    let mut res = vec![];
+    if stage.min.y > room.min.y {
+        res.push(Edge::Bottom);
+    }
+    if stage.max.y < room.max.y {
+        res.push(Edge::Top);
+    }
+    if stage.min.x > room.min.x {
+        res.push(Edge::Left);
+    }
+    if stage.max.x < room.max.x {
+        res.push(Edge::Right);
+    }
+    res
+}
+
+// Rows of points along some edges of the area. The r-th row of every edge runs on the
+// border of the area shrunk by r * row_step. The points of a row are evenly distributed
+// from corner to corner at least spacing apart, and the odd rows of a staggered band
+// are shifted by half of that.
+#[derive(Debug, Clone)]
+pub struct EdgeBand {
+    pub rows: usize,
+    pub spacing: f64,
+    pub row_step: f64,
+    pub stagger: bool,
+}
+
+impl EdgeBand {
+    // Rows of touching musicians.
+    pub fn square(rows: usize) -> Self {
+        Self {
+            rows,
+            spacing: MIN_DIST,
+            row_step: MIN_DIST,
+            stagger: false,
+        }
+    }
+
+    // Rows fitting in the gaps of the previous row, as in a hexagonal packing.
+    pub fn hex(rows: usize) -> Self {
+        Self {
+            rows,
+            spacing: MIN_DIST,
+            row_step: MIN_DIST * 3f64.sqrt() / 2.,
+            stagger: true,
+        }
+    }
+
+    // 45 degrees rotated square packing, leaving diagonal gaps between the musicians.
+    pub fn zigzag(rows: usize) -> Self {
+        Self {
+            rows,
+            spacing: MIN_DIST * 2f64.sqrt(),
+            row_step: MIN_DIST / 2f64.sqrt(),
+            stagger: true,
+        }
+    }
+
+    // Points of the outer rows come first.
+    pub fn points(&self, area: Box2D<f64>, edges: &[Edge]) -> Vec<Point> {
+        let mut res = vec![];
+        for r in 0..self.rows {
+            let d = r as f64 * self.row_step;
+            let bb = area.inflate(-d, -d);
+            if bb.is_negative() {
+                break;
+            }
+            let shift = self.stagger && r % 2 == 1;
+
+            for edge in edges.iter() {
+                let (from, to) = match edge {
+                    Edge::Bottom => (bb.min, Point::new(bb.max.x, bb.min.y)),
+                    Edge::Top => (Point::new(bb.min.x, bb.max.y), bb.max),
+                    Edge::Left => (bb.min, Point::new(bb.min.x, bb.max.y)),
+                    Edge::Right => (Point::new(bb.max.x, bb.min.y), bb.max),
+                };
+                res.extend(line_points(from, to, self.spacing, shift));
+            }
+        }
+        remove_conflicts(res)
+    }
+}
+
+// Points from `from` to `to` evenly distributed at least spacing apart, including both
+// ends. If shift, the midpoints of them instead.
+fn line_points(from: Point, to: Point, spacing: f64, shift: bool) -> Vec<Point> {
+    let len = (to - from).length();
+    let n = (len / spacing + EPS).floor() as usize;
+    if n == 0 {
+        return if shift { vec![] } else { vec![from] };
+    }
+    let step = (to - from) / n as f64;
+    if shift {
+        (0..n).map(|i| from + step * (i as f64 + 0.5)).collect()
+    } else {
+        (0..=n).map(|i| from + step * i as f64).collect()
+    }
+}
+
+// Points on the circles of radius (pillar radius + gap) around the pillars, at least
+// spacing apart along each circle. Points closer than that to another pillar are dropped.
+pub fn pillar_arcs(area: Box2D<f64>, pillars: &[Pillar], gap: f64, spacing: f64) -> Vec<Point> {
+    let mut res = vec![];
+    for pillar in pillars.iter() {
+        let r = pillar.radius + gap;
+        let t = 2. * (spacing / (2. * r)).min(1.).asin();
+        let n = ((2. * PI / t + EPS).floor() as usize).max(1);
+        for i in 0..n {
+            let a = 2. * PI * i as f64 / n as f64;
+            res.push(pillar.center + Vector::new(r * a.cos(), r * a.sin()));
+        }
+    }
+    res.retain(|p| {
+        pillars
+            .iter()
+            .all(|pillar| (*p - pillar.center).length() >= pillar.radius + gap - EPS)
+    });
+    remove_conflicts(clip(res, area))
+}
+
+#[cfg(test)]
+mod tests {
+    use euclid::default::{Box2D, Point2D, Vector2D};
+
+    use crate::Problem;
+
+    use super::{hex_lattice, open_edges, pillar_arcs, square_lattice, Edge, EdgeBand};
+
+    fn assert_legal(ps: &[Point2D<f64>], area: Box2D<f64>) {
+        for (i, p) in ps.iter().enumerate() {
+            assert!(area.inflate(1e-6, 1e-6).contains(*p), "{:?}", p);
+            for q in ps[..i].iter() {
+                assert!((*p - *q).length() >= 10. - 1e-6, "{:?} {:?}", p, q);
+            }
+        }
+    }
+
+    #[test]
+    fn test_layout() {
+        let area = Box2D::new(Point2D::new(10., 20.), Point2D::new(105., 83.));
+
+        let ps = square_lattice(area, 10.);
+        assert_eq!(ps.len(), 10 * 7);
+        assert_legal(&ps, area);
+
+        let ps = hex_lattice(area, 10., 0.3, Vector2D::new(1., 2.));
+        assert!(!ps.is_empty());
+        assert_legal(&ps, area);
+
+        let edges = [Edge::Bottom, Edge::Top, Edge::Left, Edge::Right];
+        for band in [EdgeBand::square(3), EdgeBand::hex(3), EdgeBand::zigzag(3)] {
+            let ps = band.points(area, &edges);
+            assert_legal(&ps, area);
+            // All the corners are used.
+            assert!(ps.contains(&area.min));
+            assert!(ps.contains(&area.max));
+        }
+
+        let problem = Problem::read_from_file("../problems/85.json").unwrap();
+        assert!(!open_edges(&problem).is_empty());
+        // The pillars of the problem are off the stage, so use the whole room.
+        let area = problem.room;
+        let ps = pillar_arcs(area, &problem.pillars, 5., 10.);
+        assert!(!ps.is_empty());
+        assert_legal(&ps, area);
+        for p in ps.iter() {
+            assert!(problem
+                .pillars
+                .iter()
+                .all(|pillar| (*p - pillar.center).length() >= pillar.radius + 5. - 1e-6));
+        }
+    }
+}
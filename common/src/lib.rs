@@ -1,12 +1,28 @@
 #[cfg(not(target_arch = "wasm32"))]
 pub mod api;
+pub mod attendee_cluster;
 pub mod board;
 pub mod board_options;
+pub mod candidates;
+pub mod constraints;
 pub mod evaluate;
+pub mod finetune;
 pub mod float;
 pub mod geom;
+pub mod layout;
+pub mod margin;
+pub mod potential;
 pub mod problem;
+pub mod repair;
+pub mod similarity;
+pub mod snapshot;
+pub mod spatial_index;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod transform;
+pub mod upper_bound;
 pub mod vec2;
+pub mod warm_start;
 
 pub use evaluate::*;
 pub use problem::*;
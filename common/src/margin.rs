@@ -0,0 +1,516 @@
+use std::f64::consts::TAU;
+
+use euclid::Vector2D;
+use lyon_geom::LineSegment;
+
+use crate::{board::Board, float::Float};
+
+type P = Vector2D<f64, euclid::UnknownUnit>;
+
+// Layouts optimized right to tangency can be scored differently by another implementation
+// of the rules, as evaluate() and Board already disagree on the epsilons. This moves the
+// musicians slightly away from the borderline cases.
+
+#[derive(Debug, Clone)]
+pub struct MarginOptions {
+    // Cases closer than this to the boundary are borderline.
+    tolerance: f64,
+    // Distance to the boundary after the nudge.
+    margin: f64,
+    // Upper bound of a nudge, which is larger than margin when a musician moves the far
+    // end of a sound line.
+    max_nudge: f64,
+    max_rounds: usize,
+}
+
+impl Default for MarginOptions {
+    fn default() -> Self {
+        Self {
+            tolerance: 1e-5,
+            margin: 1e-4,
+            max_nudge: 0.1,
+            max_rounds: 10,
+        }
+    }
+}
+
+impl MarginOptions {
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn with_margin(mut self, margin: f64) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    pub fn with_max_nudge(mut self, max_nudge: f64) -> Self {
+        self.max_nudge = max_nudge;
+        self
+    }
+
+    pub fn with_max_rounds(mut self, max_rounds: usize) -> Self {
+        self.max_rounds = max_rounds;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Borderline {
+    // Musicians at distance 10.
+    Touching(usize, usize),
+    // A musician at distance 10 from the stage edge.
+    StageEdge(usize),
+    // The sound from the musician to the attendee passes at distance 5 from blocker.
+    MusicianTangent {
+        attendee: usize,
+        musician: usize,
+        blocker: usize,
+    },
+    // The sound from the musician to the attendee is tangent to the pillar.
+    PillarTangent {
+        attendee: usize,
+        musician: usize,
+        pillar: usize,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct MarginReport {
+    pub found: Vec<Borderline>,
+    // Cases left after the last round, including ones made by the nudges.
+    pub remaining: Vec<Borderline>,
+    pub rounds: usize,
+    pub nudged: usize,
+    pub score_before: f64,
+    pub score_after: f64,
+}
+
+impl MarginReport {
+    // Number of the initial cases which are not borderline any more.
+    pub fn resolved(&self) -> usize {
+        self.found
+            .iter()
+            .filter(|c| !self.remaining.contains(c))
+            .count()
+    }
+
+    pub fn cost(&self) -> f64 {
+        self.score_before - self.score_after
+    }
+}
+
+fn positions<F: Float>(board: &Board<F>) -> Vec<Option<P>> {
+    board.musicians().iter().map(|p| p.map(|p| p.0)).collect()
+}
+
+fn segment(from: P, to: P) -> LineSegment<f64> {
+    LineSegment {
+        from: from.to_point(),
+        to: to.to_point(),
+    }
+}
+
+// Exact, unlike angle_from_x_axis() which approximates atan2.
+fn angle(v: P) -> f64 {
+    v.y.atan2(v.x)
+}
+
+pub fn find_borderline<F: Float>(board: &Board<F>, tolerance: f64) -> Vec<Borderline> {
+    let prob = &board.prob;
+    let ps = positions(board);
+    let placed = (0..ps.len())
+        .filter(|m| ps[*m].is_some())
+        .collect::<Vec<_>>();
+
+    let mut res = vec![];
+
+    // Touching musicians.
+    let mut by_x = placed.clone();
+    by_x.sort_by(|a, b| ps[*a].unwrap().x.partial_cmp(&ps[*b].unwrap().x).unwrap());
+    for i in 0..by_x.len() {
+        for j in i + 1..by_x.len() {
+            let (a, b) = (ps[by_x[i]].unwrap(), ps[by_x[j]].unwrap());
+            if b.x - a.x >= 10. + tolerance {
+                break;
+            }
+            if (b - a).length() < 10. + tolerance {
+                res.push(Borderline::Touching(
+                    by_x[i].min(by_x[j]),
+                    by_x[i].max(by_x[j]),
+                ));
+            }
+        }
+    }
+
+    // Stage edges. prob.stage is where the centers can be.
+    let stage = prob.stage;
+    for &m in placed.iter() {
+        let p = ps[m].unwrap();
+        if p.x - stage.min.x < tolerance
+            || stage.max.x - p.x < tolerance
+            || p.y - stage.min.y < tolerance
+            || stage.max.y - p.y < tolerance
+        {
+            res.push(Borderline::StageEdge(m));
+        }
+    }
+
+    for (a, attendee) in prob.attendees.iter().enumerate() {
+        let q = attendee.position.to_vector();
+
+        // The musicians by the angle from the attendee, repeated 3 times to look around
+        // without wrapping.
+        let mut by_angle = placed
+            .iter()
+            .map(|m| (angle(ps[*m].unwrap() - q), *m))
+            .collect::<Vec<_>>();
+        by_angle.sort_by(|x, y| x.partial_cmp(y).unwrap());
+        let n = by_angle.len();
+        let by_angle = [-TAU, 0., TAU]
+            .iter()
+            .flat_map(|d| by_angle.iter().map(move |(t, m)| (t + d, *m)))
+            .collect::<Vec<_>>();
+
+        // A blocker at distance d from the attendee is within the angle w from the sound.
+        let d_min = placed
+            .iter()
+            .map(|m| (ps[*m].unwrap() - q).length())
+            .fold(f64::INFINITY, f64::min);
+        let w = ((5. + tolerance) / d_min).min(1.).asin();
+
+        // The pillars by the angle from the attendee in the same way. The sound can be
+        // tangent to a pillar only within the angle w_pillar from its center, except for
+        // the pillars around the attendee, which are always checked.
+        let mut around = vec![];
+        let mut pillars_by_angle = vec![];
+        let mut w_pillar = 0f64;
+        for (pi, pillar) in prob.pillars.iter().enumerate() {
+            let v = pillar.center.to_vector() - q;
+            let r = pillar.radius + tolerance;
+            if v.length() <= r {
+                around.push(pi);
+                continue;
+            }
+            w_pillar = w_pillar.max((r / v.length()).asin());
+            pillars_by_angle.push((angle(v), pi));
+        }
+        pillars_by_angle.sort_by(|x, y| x.partial_cmp(y).unwrap());
+        let pillars_by_angle = [-TAU, 0., TAU]
+            .iter()
+            .flat_map(|d| pillars_by_angle.iter().map(move |(t, pi)| (t + d, *pi)))
+            .collect::<Vec<_>>();
+
+        for i in n..2 * n {
+            let (t, m) = by_angle[i];
+            let seg = segment(q, ps[m].unwrap());
+            let mut check = |k: usize| {
+                let b = by_angle[k].1;
+                let d = seg.distance_to_point(ps[b].unwrap().to_point());
+                if (d - 5.).abs() < tolerance {
+                    res.push(Borderline::MusicianTangent {
+                        attendee: a,
+                        musician: m,
+                        blocker: b,
+                    });
+                }
+            };
+            let mut k = i + 1;
+            while k < i + n && by_angle[k].0 - t <= w {
+                check(k);
+                k += 1;
+            }
+            let mut k = i - 1;
+            while k > i - n && t - by_angle[k].0 <= w {
+                check(k);
+                k -= 1;
+            }
+
+            let len = seg.length();
+            let k = pillars_by_angle.partition_point(|(s, _)| *s < t - w_pillar);
+            let near = pillars_by_angle[k..]
+                .iter()
+                .take_while(|(s, _)| *s <= t + w_pillar)
+                .map(|(_, pi)| *pi);
+            for pi in around.iter().copied().chain(near) {
+                let pillar = &prob.pillars[pi];
+                if (pillar.center.to_vector() - q).length() > len + pillar.radius + tolerance {
+                    continue;
+                }
+                let d = seg.distance_to_point(pillar.center);
+                if (d - pillar.radius).abs() < tolerance {
+                    res.push(Borderline::PillarTangent {
+                        attendee: a,
+                        musician: m,
+                        pillar: pi,
+                    });
+                }
+            }
+        }
+    }
+
+    res
+}
+
+// The musician to move for the case, and the positions to try.
+fn nudges<F: Float>(
+    board: &Board<F>,
+    case: &Borderline,
+    options: &MarginOptions,
+) -> Vec<(usize, P)> {
+    let prob = &board.prob;
+    let ps = positions(board);
+    let margin = options.margin;
+
+    match *case {
+        Borderline::Touching(m1, m2) => {
+            let (p1, p2) = (ps[m1].unwrap(), ps[m2].unwrap());
+            let v = p2 - p1;
+            let len = v.length();
+            if len == 0. {
+                return vec![];
+            }
+            // Twice the margin, so that they can still move away from the stage edges.
+            let d = v / len * (10. + 2. * margin - len);
+            vec![(m2, p2 + d), (m1, p1 - d)]
+        }
+        Borderline::StageEdge(m) => {
+            let p = ps[m].unwrap();
+            let stage = prob.stage.inflate(-margin, -margin);
+            if stage.is_negative() {
+                return vec![];
+            }
+            vec![(m, p.clamp(stage.min.to_vector(), stage.max.to_vector()))]
+        }
+        Borderline::MusicianTangent {
+            attendee,
+            musician,
+            blocker,
+        } => {
+            // Moves the blocker perpendicularly to the sound, to block it or not.
+            let q = prob.attendees[attendee].position.to_vector();
+            let (p, b) = (ps[musician].unwrap(), ps[blocker].unwrap());
+            let seg = segment(q, p);
+            let d = seg.distance_to_point(b.to_point());
+            let foot = seg.closest_point(b.to_point()).to_vector();
+            if d == 0. {
+                return vec![];
+            }
+            let n = (b - foot) / d;
+            [5. + margin, 5. - margin]
+                .iter()
+                .map(|t| (blocker, b + n * (t - d)))
+                .collect()
+        }
+        Borderline::PillarTangent {
+            attendee,
+            musician,
+            pillar,
+        } => {
+            // Moves the musician perpendicularly to the sound, which moves the sound at
+            // the pillar by the ratio of the distances from the attendee.
+            let q = prob.attendees[attendee].position.to_vector();
+            let p = ps[musician].unwrap();
+            let pillar = &prob.pillars[pillar];
+            let seg = segment(q, p);
+            let d = seg.distance_to_point(pillar.center);
+            let foot = seg.closest_point(pillar.center).to_vector();
+            let ratio = (foot - q).length() / seg.length();
+            if d == 0. || ratio == 0. {
+                return vec![];
+            }
+            let n = (foot - pillar.center.to_vector()) / d;
+            [pillar.radius + margin, pillar.radius - margin]
+                .iter()
+                .map(|t| (musician, p + n * (t - d) / ratio))
+                .collect()
+        }
+    }
+}
+
+// Nudges the musicians away from the borderline cases, choosing the nudge with the best
+// score when there are several. Each musician moves at most once per round, and the cases
+// are found again after each round, as the nudges can make new ones.
+pub fn apply_margin<F: Float>(board: &mut Board<F>, options: &MarginOptions) -> MarginReport {
+    let score_before = board.score();
+    let found = find_borderline(board, options.tolerance);
+
+    let mut cases = found.clone();
+    let mut rounds = 0;
+    let mut nudged = 0;
+    while !cases.is_empty() && rounds < options.max_rounds {
+        rounds += 1;
+
+        let mut moved = vec![false; board.prob.musicians.len()];
+        for case in cases.iter() {
+            let mut best: Option<(f64, usize, P)> = None;
+            for (m, p) in nudges(board, case, options) {
+                let old = board.musicians()[m].unwrap().0;
+                if moved[m]
+                    || (p - old).length() > options.max_nudge
+                    || !board.can_place(m, p.to_point())
+                {
+                    continue;
+                }
+                board.unplace(m);
+                board.try_place(m, p.to_point()).unwrap();
+                let score = board.score();
+                board.unplace(m);
+                board.try_place(m, old.to_point()).unwrap();
+                if best.is_none_or(|(s, _, _)| score > s) {
+                    best = Some((score, m, p));
+                }
+            }
+            if let Some((_, m, p)) = best {
+                board.unplace(m);
+                board.try_place(m, p.to_point()).unwrap();
+                moved[m] = true;
+                nudged += 1;
+            }
+        }
+
+        cases = find_borderline(board, options.tolerance);
+    }
+
+    MarginReport {
+        found,
+        remaining: cases,
+        rounds,
+        nudged,
+        score_before,
+        score_after: board.score(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use euclid::default::{Box2D, Point2D};
+    use lyon_geom::Point;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::{board::Board, Attendee, Pillar, Problem};
+
+    use super::{apply_margin, find_borderline, segment, Borderline, MarginOptions};
+
+    fn board(ps: &[(f64, f64)]) -> Board {
+        let problem = Problem {
+            room: Box2D::new(Point2D::new(0., 0.), Point2D::new(300., 300.)),
+            stage: Box2D::new(Point2D::new(100., 100.), Point2D::new(200., 200.)),
+            musicians: vec![0; ps.len()],
+            attendees: vec![Attendee {
+                position: Point2D::new(50., 150.),
+                tastes: vec![1000.],
+            }],
+            pillars: vec![Pillar {
+                center: Point2D::new(80., 150.),
+                radius: 3.,
+            }],
+        };
+        let mut board = Board::new(56, problem, "test", false);
+        for (m, (x, y)) in ps.iter().enumerate() {
+            board.set_volume(m, 10.);
+            board.try_place(m, Point::new(*x, *y)).unwrap();
+        }
+        board
+    }
+
+    #[test]
+    fn test_find_borderline() {
+        // 0 and 1 are touching, 0 is on the edge, 2 is tangent to the sound of 3, and
+        // the sound of 4 is tangent to the pillar.
+        let board = board(&[
+            (110., 120.),
+            (120., 120.),
+            (150., 145.),
+            (180., 150.),
+            (150., 150. + 10. / 0.99f64.sqrt()),
+        ]);
+        let cases = find_borderline(&board, 1e-5);
+        assert!(cases.contains(&Borderline::Touching(0, 1)));
+        assert!(cases.contains(&Borderline::StageEdge(0)));
+        assert!(cases.contains(&Borderline::MusicianTangent {
+            attendee: 0,
+            musician: 3,
+            blocker: 2
+        }));
+        assert!(cases.iter().any(|c| matches!(
+            c,
+            Borderline::PillarTangent {
+                musician: 4,
+                pillar: 0,
+                ..
+            }
+        )));
+        assert!(!cases.contains(&Borderline::StageEdge(3)));
+    }
+
+    #[test]
+    fn test_find_borderline_pillars() {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let problem = Problem::read_from_file("../problems/85.json").unwrap();
+        let mut board = Board::new(85, problem, "test", false);
+        for i in 0..board.prob.musicians.len() {
+            loop {
+                let x: f64 = rng.gen_range(board.prob.stage.min.x..board.prob.stage.max.x);
+                let y: f64 = rng.gen_range(board.prob.stage.min.y..board.prob.stage.max.y);
+                if board.try_place(i, Point::new(x, y)).is_ok() {
+                    break;
+                }
+            }
+        }
+
+        // A large tolerance to have many cases, compared with all the pillars checked.
+        let tolerance = 1.;
+        let mut expected = vec![];
+        for (a, attendee) in board.prob.attendees.iter().enumerate() {
+            for (m, p) in board.musicians().iter().enumerate() {
+                let seg = segment(attendee.position.to_vector(), p.unwrap().0);
+                for (pi, pillar) in board.prob.pillars.iter().enumerate() {
+                    if (seg.distance_to_point(pillar.center) - pillar.radius).abs() < tolerance {
+                        expected.push((a, m, pi));
+                    }
+                }
+            }
+        }
+        let mut actual = find_borderline(&board, tolerance)
+            .into_iter()
+            .filter_map(|c| match c {
+                Borderline::PillarTangent {
+                    attendee,
+                    musician,
+                    pillar,
+                } => Some((attendee, musician, pillar)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        actual.sort();
+        assert!(!expected.is_empty());
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_apply_margin() {
+        let mut board = board(&[
+            (110., 120.),
+            (120., 120.),
+            (150., 145.),
+            (180., 150.),
+            (150., 150. + 10. / 0.99f64.sqrt()),
+        ]);
+        let before = board.musicians().to_vec();
+
+        let options = MarginOptions::default();
+        let report = apply_margin(&mut board, &options);
+        assert!(report.remaining.is_empty(), "{:?}", report.remaining);
+        assert_eq!(report.resolved(), report.found.len());
+        assert!(find_borderline(&board, 1e-5).is_empty());
+
+        // Nobody moves much.
+        for (p, q) in before.iter().zip(board.musicians().iter()) {
+            assert!((p.unwrap().0 - q.unwrap().0).length() < 0.01);
+        }
+        assert_eq!(report.score_after, board.score());
+    }
+}
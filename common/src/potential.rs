@@ -0,0 +1,313 @@
+use euclid::default::{Box2D, Point2D};
+use lyon_geom::LineSegment;
+
+use crate::Problem;
+
+type Point = Point2D<f64>;
+
+// Grid of sample points covering bb every step from bb.min. The last ones are on
+// bb.max, so the last cells may be narrower.
+#[derive(Clone, Debug)]
+struct Grid {
+    bb: Box2D<f64>,
+    step: f64,
+    nx: usize,
+    ny: usize,
+    // ins -> x * ny + y -> value
+    values: Vec<Vec<f64>>,
+}
+
+impl Grid {
+    fn new(prob: &Problem, bb: Box2D<f64>, step: f64) -> Self {
+        let nx = (bb.width() / step).ceil() as usize + 1;
+        let ny = (bb.height() / step).ceil() as usize + 1;
+        let num_instruments = prob.attendees[0].tastes.len();
+
+        let mut grid = Self {
+            bb,
+            step,
+            nx,
+            ny,
+            values: vec![],
+        };
+        let mut values = vec![vec![0.; nx * ny]; num_instruments];
+        for x in 0..nx {
+            for y in 0..ny {
+                let p = grid.point_of(x, y);
+                for a in prob.attendees.iter() {
+                    // The pillars block the sound wherever the musicians are.
+                    let seg = LineSegment {
+                        from: p,
+                        to: a.position,
+                    };
+                    if prob.pillars.iter().any(|pillar| {
+                        seg.square_distance_to_point(pillar.center) < pillar.radius.powi(2)
+                    }) {
+                        continue;
+                    }
+                    let d2 = (a.position - p).square_length();
+                    for (ins, t) in a.tastes.iter().enumerate() {
+                        values[ins][x * ny + y] += (1_000_000. * t / d2).ceil();
+                    }
+                }
+            }
+        }
+
+        grid.values = values;
+        grid
+    }
+
+    // i-th sample coordinate of n between min and max.
+    fn coord(&self, min: f64, max: f64, n: usize, i: usize) -> f64 {
+        if i + 1 == n {
+            max
+        } else {
+            min + i as f64 * self.step
+        }
+    }
+
+    fn point_of(&self, x: usize, y: usize) -> Point {
+        Point::new(
+            self.coord(self.bb.min.x, self.bb.max.x, self.nx, x),
+            self.coord(self.bb.min.y, self.bb.max.y, self.ny, y),
+        )
+    }
+
+    fn contains(&self, p: Point) -> bool {
+        self.bb.min.x <= p.x && p.x <= self.bb.max.x && self.bb.min.y <= p.y && p.y <= self.bb.max.y
+    }
+
+    fn value(&self, ins: usize, p: Point) -> f64 {
+        // (cell index, fraction) of a coordinate
+        let locate = |v: f64, min: f64, max: f64, n: usize| {
+            if n == 1 {
+                return (0, 0.);
+            }
+            let v = v.clamp(min, max);
+            let i = (((v - min) / self.step).floor() as usize).min(n - 2);
+            let (c0, c1) = (self.coord(min, max, n, i), self.coord(min, max, n, i + 1));
+            if c1 <= c0 {
+                return (i, 0.);
+            }
+            (i, ((v - c0) / (c1 - c0)).clamp(0., 1.))
+        };
+        let (x, fx) = locate(p.x, self.bb.min.x, self.bb.max.x, self.nx);
+        let (y, fy) = locate(p.y, self.bb.min.y, self.bb.max.y, self.ny);
+
+        let at = |dx: usize, dy: usize| {
+            let x = (x + dx).min(self.nx - 1);
+            let y = (y + dy).min(self.ny - 1);
+            self.values[ins][x * self.ny + y]
+        };
+        (at(0, 0) * (1. - fx) + at(1, 0) * fx) * (1. - fy)
+            + (at(0, 1) * (1. - fx) + at(1, 1) * fx) * fy
+    }
+
+    // Sample points not less than any of their 8 neighbors.
+    fn local_maxima(&self, ins: usize) -> Vec<(f64, Point)> {
+        let vs = &self.values[ins];
+        let mut res = vec![];
+        for x in 0..self.nx {
+            for y in 0..self.ny {
+                let v = vs[x * self.ny + y];
+                let mut is_max = true;
+                for x2 in x.saturating_sub(1)..(x + 2).min(self.nx) {
+                    for y2 in y.saturating_sub(1)..(y + 2).min(self.ny) {
+                        if vs[x2 * self.ny + y2] > v {
+                            is_max = false;
+                        }
+                    }
+                }
+                if is_max {
+                    res.push((v, self.point_of(x, y)));
+                }
+            }
+        }
+        res
+    }
+}
+
+// Per-instrument field of "the impact a musician at p would get, ignoring blocking by
+// the other musicians" over the area musicians can be placed on, i.e.
+// Σ_a ceil(1_000_000 * taste / d^2) over the attendees not hidden by the pillars.
+// Values between the sample points are bilinearly interpolated.
+// Building it takes O(#samples * |attendees| * (#instruments + #pillars)).
+//
+// Only greedy-solver uses it for now. common::estimate scores the exact impacts of a
+// few candidate points, dp-solver's all table is exact on the integer points of a
+// line, and psh-solver's assign_musicians counts the blocking by the other musicians,
+// so they keep their own loops.
+#[derive(Clone, Debug)]
+pub struct PotentialMap {
+    area: Box2D<f64>,
+    coarse: Grid,
+    // Finer grids along the edges of area, where the optimal positions usually are.
+    edges: Vec<Grid>,
+}
+
+impl PotentialMap {
+    pub fn new(prob: &Problem, step: f64) -> Self {
+        let area = prob.stage.inflate(-10., -10.);
+        Self {
+            area,
+            coarse: Grid::new(prob, area, step),
+            edges: vec![],
+        }
+    }
+
+    // Adds grids of the given step on the bands of the given width along the 4 edges.
+    pub fn with_edge_refinement(mut self, prob: &Problem, step: f64, width: f64) -> Self {
+        let Box2D { min, max } = self.area;
+        let w = width.min(self.area.width()).min(self.area.height());
+        for bb in [
+            Box2D::new(min, Point::new(max.x, min.y + w)),
+            Box2D::new(Point::new(min.x, max.y - w), max),
+            Box2D::new(min, Point::new(min.x + w, max.y)),
+            Box2D::new(Point::new(max.x - w, min.y), max),
+        ] {
+            self.edges.push(Grid::new(prob, bb, step));
+        }
+        self
+    }
+
+    pub fn area(&self) -> Box2D<f64> {
+        self.area
+    }
+
+    // Points outside the area are clamped into it.
+    pub fn value(&self, ins: usize, p: Point) -> f64 {
+        let p = p.clamp(self.area.min, self.area.max);
+        for grid in self.edges.iter() {
+            if grid.contains(p) {
+                return grid.value(ins, p);
+            }
+        }
+        self.coarse.value(ins, p)
+    }
+
+    // Returns up to k local maxima of the field of ins in the descending order of values.
+    // Maxima of the coarse grid inside the refined bands are replaced by the finer ones.
+    // Points on the inner borders of the bands may be reported as maxima of the bands.
+    pub fn top_k(&self, ins: usize, k: usize) -> Vec<(f64, Point)> {
+        let mut res = self
+            .coarse
+            .local_maxima(ins)
+            .into_iter()
+            .filter(|(_, p)| !self.edges.iter().any(|grid| grid.contains(*p)))
+            .collect::<Vec<_>>();
+        // value() looks up the first band containing the point.
+        for (i, grid) in self.edges.iter().enumerate() {
+            res.extend(
+                grid.local_maxima(ins)
+                    .into_iter()
+                    .filter(|(_, p)| !self.edges[..i].iter().any(|grid| grid.contains(*p))),
+            );
+        }
+        res.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+        res.truncate(k);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use euclid::default::Point2D;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::{
+        testing::{self, attendee},
+        Pillar, Problem,
+    };
+
+    use super::PotentialMap;
+
+    #[test]
+    fn test_potential_map() {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let problem = Problem::read_from_file("../problems/42.json").unwrap();
+        let map = PotentialMap::new(&problem, 10.).with_edge_refinement(&problem, 2., 20.);
+
+        let exact = |ins: usize, p: Point2D<f64>| {
+            problem
+                .attendees
+                .iter()
+                .map(|a| (1_000_000. * a.tastes[ins] / (a.position - p).square_length()).ceil())
+                .sum::<f64>()
+        };
+        let abs_sum = |ins: usize, p: Point2D<f64>| {
+            problem
+                .attendees
+                .iter()
+                .map(|a| (1_000_000. * a.tastes[ins] / (a.position - p).square_length()).abs())
+                .sum::<f64>()
+        };
+
+        // Exact on the sample points.
+        let area = map.area();
+        for ins in 0..problem.attendees[0].tastes.len() {
+            for p in [area.min, area.max, Point2D::new(area.min.x, area.max.y)] {
+                assert!((map.value(ins, p) - exact(ins, p)).abs() < 1e-6);
+            }
+        }
+
+        // Close elsewhere. Positive and negative tastes cancel out, so the error is
+        // relative to the sum of the absolute impacts.
+        for _ in 0..100 {
+            let p = Point2D::new(
+                rng.gen_range(area.min.x..area.max.x),
+                rng.gen_range(area.min.y..area.max.y),
+            );
+            let (v, e) = (map.value(0, p), exact(0, p));
+            assert!((v - e).abs() <= abs_sum(0, p) * 0.05, "{} {}", v, e);
+        }
+
+        let top = map.top_k(0, 5);
+        assert!(!top.is_empty() && top.len() <= 5);
+        assert!(top.windows(2).all(|w| w[0].0 >= w[1].0));
+        for (v, p) in top.iter() {
+            assert!(area.min.x <= p.x && p.x <= area.max.x);
+            assert!(area.min.y <= p.y && p.y <= area.max.y);
+            assert!((map.value(0, *p) - v).abs() < 1e-6);
+        }
+
+        // The samples are every step from the minimum, as the greedy-solver expects.
+        let edges = PotentialMap::new(&problem, 1000.).with_edge_refinement(&problem, 2., 0.);
+        let on_lattice = |v: f64, min: f64, max: f64| v == max || ((v - min) / 2.).fract() == 0.;
+        for (_, p) in edges.top_k(0, usize::MAX) {
+            assert!(on_lattice(p.x, area.min.x, area.max.x), "{:?}", p);
+            assert!(on_lattice(p.y, area.min.y, area.max.y), "{:?}", p);
+        }
+    }
+
+    #[test]
+    fn test_pillars() {
+        let problem = Problem {
+            pillars: vec![Pillar {
+                center: Point2D::new(150., 250.),
+                radius: 5.,
+            }],
+            ..testing::problem(
+                vec![0],
+                vec![
+                    attendee(150., 290., vec![1000.]),
+                    attendee(290., 150., vec![1000.]),
+                ],
+            )
+        };
+        let map = PotentialMap::new(&problem, 10.);
+
+        // Only the attendee on the right is heard from the middle of the stage.
+        let p = Point2D::new(150., 150.);
+        assert_eq!(
+            map.value(0, p),
+            (1_000_000. * 1000. / 140_f64.powi(2)).ceil()
+        );
+        // Both are heard from the corner.
+        let q = Point2D::new(190., 190.);
+        assert_eq!(
+            map.value(0, q),
+            2. * (1_000_000. * 1000. / (100_f64.powi(2) + 40_f64.powi(2))).ceil()
+        );
+    }
+}
@@ -5,7 +5,9 @@ use anyhow::Result;
 use euclid::default::{Box2D, Point2D};
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
+use crate::transform::Transform;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Problem {
     pub room: Box2D<f64>,
     pub stage: Box2D<f64>,
@@ -14,33 +16,17 @@ pub struct Problem {
     pub pillars: Vec<Pillar>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Attendee {
     pub position: Point2D<f64>,
     pub tastes: Vec<f64>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Pillar {
     pub center: Point2D<f64>,
     pub radius: f64,
 }
-impl Pillar {
-    fn flipped(&self) -> Pillar {
-        Pillar {
-            center: Point2D::new(self.center.y, self.center.x),
-            radius: self.radius,
-        }
-    }
-
-    fn multiplied(&self, d: f64) -> Pillar {
-        Pillar {
-            center: self.center * d,
-            radius: self.radius * d,
-        }
-    }
-}
-
 #[derive(Clone, Debug)]
 pub struct Solution {
     pub problem_id: u32,
@@ -118,47 +104,14 @@ impl Problem {
     }
 
     pub fn flipped(&self) -> Problem {
-        Problem {
-            room: box_flipped(self.room),
-            stage: box_flipped(self.stage),
-            musicians: self.musicians.clone(),
-            attendees: self
-                .attendees
-                .iter()
-                .map(|a| Attendee {
-                    position: Point2D::new(a.position.y, a.position.x),
-                    tastes: a.tastes.clone(),
-                })
-                .collect::<Vec<_>>(),
-            pillars: self.pillars.iter().map(|p| p.flipped()).collect(),
-        }
+        Transform::swap_xy().problem(self)
     }
 
     pub fn multiplied(&self, d: f64) -> Problem {
-        Problem {
-            room: self.room * d,
-            stage: self.stage * d,
-            musicians: self.musicians.clone(),
-            attendees: self
-                .attendees
-                .iter()
-                .map(|a| Attendee {
-                    position: Point2D::new(a.position.x * d, a.position.y * d),
-                    tastes: a.tastes.clone(),
-                })
-                .collect(),
-            pillars: self.pillars.iter().map(|p| p.multiplied(d)).collect(),
-        }
+        Transform::scale(d).problem(self)
     }
 }
 
-fn box_flipped(b: Box2D<f64>) -> Box2D<f64> {
-    Box2D::new(
-        Point2D::new(b.min.y, b.min.x),
-        Point2D::new(b.max.y, b.max.x),
-    )
-}
-
 impl Solution {
     pub fn read_from_file<P: AsRef<Path>>(path: P) -> Result<Solution> {
         let content = std::fs::read_to_string(path)?;
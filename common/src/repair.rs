@@ -0,0 +1,341 @@
+use anyhow::{bail, Result};
+use euclid::default::{Box2D, Point2D, Vector2D};
+
+use crate::{evaluate, spatial_index::SpatialIndex, Placement, Problem, Solution};
+
+type Point = Point2D<f64>;
+type Vector = Vector2D<f64>;
+
+// Overlapping musicians are pushed a bit farther apart than needed, so that they do not
+// touch again because of rounding.
+const MARGIN: f64 = 1e-6;
+const MAX_ITERATIONS: usize = 1000;
+// Resolution of the search of free positions.
+const SEARCH_STEP: f64 = 0.5;
+
+#[derive(Debug, Clone)]
+pub struct RepairReport {
+    // Musicians moved into the stage or away from the others.
+    pub moved: usize,
+    // Musicians without a placement, or still overlapping after the displacements, which
+    // are put on the nearest free positions.
+    pub relocated: usize,
+    pub max_displacement: f64,
+    // None when the solution does not have a placement and a volume per musician.
+    pub score_before: Option<f64>,
+    pub score_after: f64,
+}
+
+impl RepairReport {
+    // Score lost by the repair.
+    pub fn cost(&self) -> Option<f64> {
+        self.score_before.map(|s| s - self.score_after)
+    }
+}
+
+// The area where the centers of the musicians can be, same as Board::try_place.
+fn stage_area(problem: &Problem) -> Box2D<f64> {
+    problem.stage.inflate(-10., -10.)
+}
+
+fn on_stage(stage: &Box2D<f64>, p: Point) -> bool {
+    stage.min.x <= p.x && p.x < stage.max.x + 1e-9 && stage.min.y <= p.y && p.y < stage.max.y + 1e-9
+}
+
+fn is_free(index: &SpatialIndex, p: Point) -> bool {
+    !index
+        .near(p.to_vector(), 10.)
+        .any(|(_, q)| (q - p.to_vector()).square_length() < 100.)
+}
+
+// Returns whether Board::try_place accepts all the placements.
+pub fn is_legal(problem: &Problem, solution: &Solution) -> bool {
+    let n = problem.musicians.len();
+    if solution.placements.len() != n || solution.volumes.len() != n {
+        return false;
+    }
+    let stage = stage_area(problem);
+    let mut index = SpatialIndex::new(problem.stage, 10.);
+    for (m, p) in solution.placements.iter().enumerate() {
+        if !on_stage(&stage, p.position) || !is_free(&index, p.position) {
+            return false;
+        }
+        index.insert(m, p.position.to_vector());
+    }
+    true
+}
+
+// Pushes apart the pairs of musicians closer than 10, and keeps them on the stage. The
+// pinned musicians do not move. Returns whether any musician moved.
+fn separate(ps: &mut [Point], ms: &mut [usize], pinned: &[bool], stage: &Box2D<f64>) -> bool {
+    let d = 10. + MARGIN;
+    ms.sort_by(|a, b| ps[*a].x.partial_cmp(&ps[*b].x).unwrap());
+
+    let mut moved = false;
+    for i in 0..ms.len() {
+        for j in i + 1..ms.len() {
+            let (a, b) = (ms[i], ms[j]);
+            if ps[b].x - ps[a].x >= 10. {
+                break;
+            }
+            let v = ps[b] - ps[a];
+            if v.square_length() >= 100. {
+                continue;
+            }
+            let len = v.length();
+            // Musicians at the same position are separated in some arbitrary direction.
+            let dir = if len > 0. {
+                v / len
+            } else {
+                let angle = (a * ms.len() + b) as f64;
+                Vector::new(angle.cos(), angle.sin())
+            };
+            let push = dir * (d - len);
+            match (pinned[a], pinned[b]) {
+                (true, true) => continue,
+                (true, false) => ps[b] = (ps[b] + push).clamp(stage.min, stage.max),
+                (false, true) => ps[a] = (ps[a] - push).clamp(stage.min, stage.max),
+                (false, false) => {
+                    ps[a] = (ps[a] - push / 2.).clamp(stage.min, stage.max);
+                    ps[b] = (ps[b] + push / 2.).clamp(stage.min, stage.max);
+                }
+            }
+            moved = true;
+        }
+    }
+    moved
+}
+
+// The free position nearest to p, searched on circles of growing radii.
+fn nearest_free(index: &SpatialIndex, stage: &Box2D<f64>, p: Point) -> Option<Point> {
+    let max_r = (stage.max - stage.min).length() + SEARCH_STEP;
+    let mut r = 0.;
+    while r <= max_r {
+        let k = ((std::f64::consts::TAU * r / SEARCH_STEP).ceil() as usize).max(1);
+        for i in 0..k {
+            let angle = std::f64::consts::TAU * i as f64 / k as f64;
+            let q = p + Vector::new(angle.cos(), angle.sin()) * r;
+            if on_stage(stage, q) && is_free(index, q) {
+                return Some(q);
+            }
+        }
+        r += SEARCH_STEP;
+    }
+    None
+}
+
+// Makes the solution legal by moving the musicians as little as possible: they are
+// projected into the stage, the overlapping ones are pushed apart, and the ones which
+// still overlap or are missing are put on the nearest free positions. Missing volumes
+// are 1, as in RawSolution.
+pub fn repair(problem: &Problem, solution: &Solution) -> Result<(Solution, RepairReport)> {
+    repair_with_pinned(problem, solution, &[])
+}
+
+// Same as repair(), but the pinned musicians keep their positions, e.g. the ones pinned
+// by Constraints. Fails if they are not legal by themselves.
+pub fn repair_with_pinned(
+    problem: &Problem,
+    solution: &Solution,
+    pinned: &[usize],
+) -> Result<(Solution, RepairReport)> {
+    let n = problem.musicians.len();
+    let stage = stage_area(problem);
+    if stage.is_negative() {
+        bail!("stage is too small for a musician");
+    }
+
+    let mut is_pinned = vec![false; n];
+    for &m in pinned.iter() {
+        let Some(p) = solution.placements.get(m) else {
+            bail!("pinned musician {} has no placement", m);
+        };
+        if !on_stage(&stage, p.position) {
+            bail!("pinned musician {} is off the stage", m);
+        }
+        is_pinned[m] = true;
+    }
+
+    let score_before = (solution.placements.len() == n && solution.volumes.len() == n)
+        .then(|| evaluate(problem, solution));
+
+    let orig = (0..n)
+        .map(|m| solution.placements.get(m).map(|p| p.position))
+        .collect::<Vec<_>>();
+    let mut ps = (0..n)
+        .map(|m| match orig[m] {
+            Some(p) if is_pinned[m] => p,
+            Some(p) => p.clamp(stage.min, stage.max),
+            None => stage.center(),
+        })
+        .collect::<Vec<_>>();
+
+    let mut placed = (0..n).filter(|m| orig[*m].is_some()).collect::<Vec<_>>();
+    for _ in 0..MAX_ITERATIONS {
+        if !separate(&mut ps, &mut placed, &is_pinned, &stage) {
+            break;
+        }
+    }
+
+    // The pinned musicians, then the ones moved the least keep their positions first.
+    let displacement = |m: usize, ps: &[Point]| orig[m].map_or(0., |p| (ps[m] - p).length());
+    placed.sort_by(|a, b| {
+        (!is_pinned[*a], displacement(*a, &ps))
+            .partial_cmp(&(!is_pinned[*b], displacement(*b, &ps)))
+            .unwrap()
+    });
+    let mut index = SpatialIndex::new(problem.stage, 10.);
+    let mut leftovers = (0..n).filter(|m| orig[*m].is_none()).collect::<Vec<_>>();
+    for &m in placed.iter() {
+        if on_stage(&stage, ps[m]) && is_free(&index, ps[m]) {
+            index.insert(m, ps[m].to_vector());
+        } else if is_pinned[m] {
+            bail!("pinned musician {} overlaps another pinned one", m);
+        } else {
+            leftovers.push(m);
+        }
+    }
+    for &m in leftovers.iter() {
+        let Some(p) = nearest_free(&index, &stage, ps[m]) else {
+            bail!("no room for musician {}", m);
+        };
+        ps[m] = p;
+        index.insert(m, p.to_vector());
+    }
+
+    let repaired = Solution {
+        problem_id: solution.problem_id,
+        solver: solution.solver.clone(),
+        placements: ps.iter().map(|p| Placement { position: *p }).collect(),
+        volumes: (0..n)
+            .map(|m| solution.volumes.get(m).copied().unwrap_or(1.))
+            .collect(),
+    };
+
+    let report = RepairReport {
+        moved: placed
+            .iter()
+            .filter(|m| !leftovers.contains(m) && displacement(**m, &ps) > 0.)
+            .count(),
+        relocated: leftovers.len(),
+        max_displacement: placed
+            .iter()
+            .map(|m| displacement(*m, &ps))
+            .fold(0., f64::max),
+        score_before,
+        score_after: evaluate(problem, &repaired),
+    };
+    Ok((repaired, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use euclid::default::{Box2D, Point2D};
+
+    use crate::{
+        board::Board,
+        testing::{self, attendee},
+        Placement, Problem, Solution,
+    };
+
+    use super::{is_legal, repair, repair_with_pinned};
+
+    fn problem(musicians: usize) -> Problem {
+        Problem {
+            room: Box2D::new(Point2D::new(0., 0.), Point2D::new(200., 200.)),
+            stage: Box2D::new(Point2D::new(50., 50.), Point2D::new(110., 100.)),
+            ..testing::problem(
+                (0..musicians).map(|m| m % 2).collect(),
+                vec![
+                    attendee(10., 20., vec![1000., -200.]),
+                    attendee(180., 150., vec![-300., 800.]),
+                ],
+            )
+        }
+    }
+
+    fn solution(ps: &[(f64, f64)]) -> Solution {
+        Solution {
+            problem_id: 0,
+            solver: "test".to_owned(),
+            placements: ps
+                .iter()
+                .map(|(x, y)| Placement {
+                    position: Point2D::new(*x, *y),
+                })
+                .collect(),
+            volumes: vec![10.; ps.len()],
+        }
+    }
+
+    fn assert_placeable(problem: &Problem, solution: &Solution) {
+        assert!(is_legal(problem, solution));
+        let mut board = Board::new(0, problem.clone(), "test", false);
+        for (m, p) in solution.placements.iter().enumerate() {
+            board.try_place(m, p.position).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_legal_solution_is_unchanged() {
+        let p = problem(3);
+        let s = solution(&[(60., 60.), (70., 60.), (100., 90.)]);
+        assert!(is_legal(&p, &s));
+
+        let (r, report) = repair(&p, &s).unwrap();
+        for (a, b) in r.placements.iter().zip(s.placements.iter()) {
+            assert_eq!(a.position, b.position);
+        }
+        assert_eq!(report.moved, 0);
+        assert_eq!(report.relocated, 0);
+        assert_eq!(report.cost(), Some(0.));
+    }
+
+    #[test]
+    fn test_repair_moves_minimally() {
+        let p = problem(4);
+        // Off stage, overlapping, and at the same position.
+        let s = solution(&[(40., 60.), (65., 60.), (80., 80.), (80., 80.)]);
+        assert!(!is_legal(&p, &s));
+
+        let (r, report) = repair(&p, &s).unwrap();
+        assert_placeable(&p, &r);
+        assert_eq!(r.placements[0].position, Point2D::new(60., 60.));
+        assert!(report.max_displacement < 21.);
+        assert_eq!(
+            report.score_before.unwrap() - report.score_after,
+            report.cost().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_repair_keeps_pinned() {
+        let p = problem(3);
+        let s = solution(&[(60., 60.), (65., 60.), (66., 62.)]);
+
+        let (r, report) = repair_with_pinned(&p, &s, &[1]).unwrap();
+        assert_placeable(&p, &r);
+        assert_eq!(r.placements[1].position, Point2D::new(65., 60.));
+        assert_eq!(report.moved + report.relocated, 2);
+
+        // Overlapping pinned musicians can not be repaired.
+        assert!(repair_with_pinned(&p, &s, &[0, 1]).is_err());
+    }
+
+    #[test]
+    fn test_repair_crowded_and_missing() {
+        let p = problem(12);
+        // All at one point, and 2 musicians without placements.
+        let mut s = solution(&[(80., 75.); 10]);
+        s.volumes.pop();
+
+        let (r, report) = repair(&p, &s).unwrap();
+        assert_placeable(&p, &r);
+        assert_eq!(r.volumes.len(), 12);
+        assert!(report.relocated >= 2);
+        assert!(report.score_before.is_none());
+
+        // At most about 20 musicians fit on the stage.
+        assert!(repair(&problem(30), &s).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::Problem;
+
+// Attendee positions are counted on GRID x GRID cells of the room.
+const GRID: usize = 8;
+
+// Summary of a problem to find the problems sharing its layouts, e.g. to start from their
+// solutions (see warm_start::transfer).
+#[derive(Debug, Clone)]
+pub struct Features {
+    pub problem_id: u32,
+    room: (f64, f64),
+    stage: (f64, f64),
+    // Position of the stage in the room, relative to the room size.
+    stage_offset: (f64, f64),
+    // Share of the attendees in each cell.
+    attendees: Vec<f64>,
+    // Share of the musicians of each instrument, largest first.
+    instruments: Vec<f64>,
+    musicians: usize,
+    pillars: usize,
+}
+
+impl Features {
+    pub fn new(problem_id: u32, problem: &Problem) -> Self {
+        let (room, stage) = (problem.room, problem.stage);
+
+        let mut attendees = vec![0.; GRID * GRID];
+        for a in problem.attendees.iter() {
+            let cell = |x: f64, min: f64, len: f64| {
+                (((x - min) / len * GRID as f64) as usize).min(GRID - 1)
+            };
+            let i = cell(a.position.x, room.min.x, room.width());
+            let j = cell(a.position.y, room.min.y, room.height());
+            attendees[i * GRID + j] += 1. / problem.attendees.len() as f64;
+        }
+
+        let mut counts = vec![0; problem.musicians.iter().max().map_or(0, |m| m + 1)];
+        for ins in problem.musicians.iter() {
+            counts[*ins] += 1;
+        }
+        counts.sort_by(|a, b| b.cmp(a));
+
+        Self {
+            problem_id,
+            room: (room.width(), room.height()),
+            stage: (stage.width(), stage.height()),
+            stage_offset: (
+                (stage.min.x - room.min.x) / room.width(),
+                (stage.min.y - room.min.y) / room.height(),
+            ),
+            attendees,
+            instruments: counts
+                .iter()
+                .map(|c| *c as f64 / problem.musicians.len() as f64)
+                .collect(),
+            musicians: problem.musicians.len(),
+            pillars: problem.pillars.len(),
+        }
+    }
+
+    // 0 for the same layouts. Each of the room, the stage, the attendees, the musicians
+    // and the pillars adds up to about 1 when they are totally different.
+    pub fn distance(&self, other: &Features) -> f64 {
+        let log_ratio = |a: f64, b: f64| (a / b).ln().abs();
+        // Half the L1 distance of the distributions, in [0, 1].
+        let l1 = |a: &[f64], b: &[f64]| {
+            let n = a.len().max(b.len());
+            (0..n)
+                .map(|i| (a.get(i).unwrap_or(&0.) - b.get(i).unwrap_or(&0.)).abs())
+                .sum::<f64>()
+                / 2.
+        };
+
+        let room = log_ratio(self.room.0, other.room.0) + log_ratio(self.room.1, other.room.1);
+        let stage = log_ratio(self.stage.0, other.stage.0)
+            + log_ratio(self.stage.1, other.stage.1)
+            + (self.stage_offset.0 - other.stage_offset.0).abs()
+            + (self.stage_offset.1 - other.stage_offset.1).abs();
+        let attendees = l1(&self.attendees, &other.attendees);
+        let musicians = l1(&self.instruments, &other.instruments)
+            + log_ratio(self.musicians as f64, other.musicians as f64);
+        let pillars = self.pillars.abs_diff(other.pillars) as f64
+            / (self.pillars + other.pillars).max(1) as f64;
+
+        room + stage + attendees + musicians + pillars
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SimilarityIndex {
+    entries: Vec<Features>,
+}
+
+impl SimilarityIndex {
+    // Reads all the {problem_id}.json in the directory.
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let mut index = Self::default();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|e| e != "json") {
+                continue;
+            }
+            let Some(Ok(problem_id)) = path.file_stem().and_then(|s| s.to_str()).map(str::parse)
+            else {
+                continue;
+            };
+            index.add(problem_id, &Problem::read_from_file(&path)?);
+        }
+        index.entries.sort_by_key(|f| f.problem_id);
+        Ok(index)
+    }
+
+    pub fn add(&mut self, problem_id: u32, problem: &Problem) {
+        self.entries.push(Features::new(problem_id, problem));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // The k problems most similar to the problem, other than itself, with the distances.
+    pub fn nearest(&self, problem_id: u32, problem: &Problem, k: usize) -> Vec<(u32, f64)> {
+        let features = Features::new(problem_id, problem);
+        let mut res = self
+            .entries
+            .iter()
+            .filter(|f| f.problem_id != problem_id)
+            .map(|f| (f.problem_id, features.distance(f)))
+            .collect::<Vec<_>>();
+        res.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        res.truncate(k);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use euclid::default::{Box2D, Point2D};
+
+    use crate::{
+        testing::{self, attendee},
+        Problem,
+    };
+
+    use super::{Features, SimilarityIndex};
+
+    fn problem(stage_x: f64, musicians: Vec<usize>, taste: f64) -> Problem {
+        Problem {
+            room: Box2D::new(Point2D::new(0., 0.), Point2D::new(400., 300.)),
+            stage: Box2D::new(
+                Point2D::new(stage_x, 100.),
+                Point2D::new(stage_x + 100., 200.),
+            ),
+            ..testing::problem(
+                musicians,
+                (0..20)
+                    .map(|i| attendee(10. + i as f64 * 19., 20., vec![taste, -taste]))
+                    .collect(),
+            )
+        }
+    }
+
+    #[test]
+    fn test_distance() {
+        let p = Features::new(1, &problem(100., vec![0, 0, 1], 10.));
+        // Only the tastes differ.
+        let q = Features::new(2, &problem(100., vec![0, 0, 1], -50.));
+        // The instruments are renamed.
+        let r = Features::new(3, &problem(100., vec![1, 0, 0], 10.));
+        let s = Features::new(4, &problem(250., vec![0, 0, 1], 10.));
+        let t = Features::new(5, &problem(100., vec![0, 1, 2, 3, 4, 5], 10.));
+
+        assert_eq!(p.distance(&q), 0.);
+        assert_eq!(p.distance(&r), 0.);
+        assert!(p.distance(&s) > 0.);
+        assert!(p.distance(&t) > p.distance(&s));
+        assert_eq!(p.distance(&t), t.distance(&p));
+    }
+
+    #[test]
+    fn test_nearest() {
+        let mut index = SimilarityIndex::default();
+        index.add(1, &problem(100., vec![0, 0, 1], 10.));
+        index.add(2, &problem(250., vec![0, 0, 1], 10.));
+        index.add(3, &problem(120., vec![0, 0, 1], 10.));
+
+        let res = index.nearest(1, &problem(100., vec![0, 0, 1], 10.), 5);
+        assert_eq!(res.iter().map(|r| r.0).collect::<Vec<_>>(), vec![3, 2]);
+    }
+}
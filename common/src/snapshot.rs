@@ -0,0 +1,144 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+
+use crate::{board::Board, float::Float, Problem};
+
+const MAGIC: &[u8; 4] = b"BRDS";
+
+// Bump this whenever the fields of Board (or anything it contains) change,
+// so that stale snapshots are rejected instead of misread.
+pub const SNAPSHOT_VERSION: u32 = 5;
+
+// FNV-1a hash of the problem as the board keeps it, which the snapshot records instead of
+// the problem itself.
+fn fingerprint(problem: &Problem) -> Result<u64> {
+    let mut res = 0xcbf29ce484222325u64;
+    for b in bincode::serialize(problem)? {
+        res = (res ^ b as u64).wrapping_mul(0x100000001b3);
+    }
+    Ok(res)
+}
+
+// Binary snapshots of a Board including all the incremental caches, but not the problem,
+// which is given again on restoring and checked against the fingerprint.
+// Restoring a snapshot is much cheaper than replaying try_place for every musician.
+impl<F: Float> Board<F> {
+    pub fn to_snapshot(&self) -> Result<Vec<u8>> {
+        let mut buf = MAGIC.to_vec();
+        buf.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+        buf.extend_from_slice(&fingerprint(&self.prob)?.to_le_bytes());
+        bincode::serialize_into(&mut buf, self)?;
+        Ok(buf)
+    }
+
+    pub fn from_snapshot(bytes: &[u8], problem: Problem) -> Result<Self> {
+        if bytes.len() < 16 || &bytes[0..4] != MAGIC {
+            bail!("not a board snapshot");
+        }
+        let version = u32::from_le_bytes(bytes[4..8].try_into()?);
+        if version != SNAPSHOT_VERSION {
+            bail!(
+                "unsupported snapshot version {} (expected {})",
+                version,
+                SNAPSHOT_VERSION
+            );
+        }
+        let hash = u64::from_le_bytes(bytes[8..16].try_into()?);
+
+        let mut board: Self = bincode::deserialize(&bytes[16..])?;
+        board.attach_problem(problem);
+        if fingerprint(&board.prob)? != hash {
+            bail!("snapshot of another problem");
+        }
+        Ok(board)
+    }
+
+    pub fn write_snapshot<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        std::fs::write(path, self.to_snapshot()?)?;
+        Ok(())
+    }
+
+    pub fn read_snapshot<P: AsRef<Path>>(path: P, problem: Problem) -> Result<Self> {
+        Self::from_snapshot(&std::fs::read(path)?, problem)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lyon_geom::Point;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::{board::Board, board_options::BoardOptions, float::F64, Problem};
+
+    use super::SNAPSHOT_VERSION;
+
+    #[test]
+    fn test_snapshot() {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let problem = Problem::read_from_file("../problems/64.json").unwrap();
+        let mut board = Board::new(64, problem.clone(), "test_solver", false);
+
+        for i in 0..board.prob.musicians.len() {
+            loop {
+                let x: f64 = rng.gen_range(board.prob.stage.min.x..board.prob.stage.max.x);
+                let y: f64 = rng.gen_range(board.prob.stage.min.y..board.prob.stage.max.y);
+                if board.try_place(i, Point::new(x, y)).is_ok() {
+                    break;
+                }
+            }
+        }
+
+        let bytes = board.to_snapshot().unwrap();
+        let mut restored = Board::<F64>::from_snapshot(&bytes, problem.clone()).unwrap();
+
+        assert_eq!(restored.score(), board.score());
+
+        // The caches must keep working after restoring.
+        for i in (0..board.prob.musicians.len()).step_by(3) {
+            board.unplace(i);
+            restored.unplace(i);
+            assert_eq!(restored.score(), board.score());
+        }
+
+        let mut other = problem.clone();
+        other.attendees[0].tastes[0] += 1.;
+        assert!(Board::<F64>::from_snapshot(&bytes, other).is_err());
+
+        let mut bytes = bytes;
+        bytes[4..8].copy_from_slice(&(SNAPSHOT_VERSION + 1).to_le_bytes());
+        assert!(Board::<F64>::from_snapshot(&bytes, problem).is_err());
+    }
+
+    #[test]
+    fn test_snapshot_with_clustering() {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let problem = Problem::read_from_file("../problems/42.json").unwrap();
+        let options = BoardOptions::default().with_attendee_clustering(100., 0.1);
+        let mut board = Board::<F64>::new_with_options(
+            42,
+            problem.clone(),
+            "test_solver",
+            false,
+            vec![],
+            vec![],
+            options,
+        );
+        for i in 0..board.prob.musicians.len() {
+            loop {
+                let x: f64 = rng.gen_range(board.prob.stage.min.x..board.prob.stage.max.x);
+                let y: f64 = rng.gen_range(board.prob.stage.min.y..board.prob.stage.max.y);
+                if board.try_place(i, Point::new(x, y)).is_ok() {
+                    break;
+                }
+            }
+        }
+
+        let bytes = board.to_snapshot().unwrap();
+        let restored = Board::<F64>::from_snapshot(&bytes, problem).unwrap();
+        assert_eq!(restored.score(), board.score());
+        assert_eq!(restored.to_exact().score(), board.to_exact().score());
+    }
+}
@@ -0,0 +1,127 @@
+use euclid::Vector2D;
+use lyon_geom::Box2D;
+use serde::{Deserialize, Serialize};
+
+type P = Vector2D<f64, euclid::UnknownUnit>;
+
+// Upper bound of the number of cells. The cell size is doubled until the grid fits.
+const MAX_CELLS: usize = 1 << 18;
+
+// Uniform grid bucketing ids by their positions.
+// Points outside the bounding box are clamped into the border cells, so the
+// queries are correct for any point (they are just slower far outside).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SpatialIndex {
+    min: P,
+    cell: f64,
+    nx: usize,
+    ny: usize,
+    // x * ny + y -> (id, position)
+    cells: Vec<Vec<(usize, P)>>,
+}
+
+impl SpatialIndex {
+    pub fn new(bb: Box2D<f64>, cell: f64) -> Self {
+        let mut cell = cell;
+        let (nx, ny) = loop {
+            let nx = (bb.width().max(0.) / cell).floor() as usize + 1;
+            let ny = (bb.height().max(0.) / cell).floor() as usize + 1;
+            if nx * ny <= MAX_CELLS {
+                break (nx, ny);
+            }
+            cell *= 2.;
+        };
+
+        Self {
+            min: bb.min.to_vector(),
+            cell,
+            nx,
+            ny,
+            cells: vec![vec![]; nx * ny],
+        }
+    }
+
+    fn cell_of(&self, p: P) -> (usize, usize) {
+        let x = ((p.x - self.min.x) / self.cell).floor().max(0.) as usize;
+        let y = ((p.y - self.min.y) / self.cell).floor().max(0.) as usize;
+        (x.min(self.nx - 1), y.min(self.ny - 1))
+    }
+
+    pub fn insert(&mut self, id: usize, p: P) {
+        let (x, y) = self.cell_of(p);
+        self.cells[x * self.ny + y].push((id, p));
+    }
+
+    // Removes id which was inserted at p.
+    pub fn remove(&mut self, id: usize, p: P) {
+        let (x, y) = self.cell_of(p);
+        let cell = &mut self.cells[x * self.ny + y];
+        if let Some(k) = cell.iter().position(|(i, _)| *i == id) {
+            cell.swap_remove(k);
+        }
+    }
+
+    // Returns the entries in the cells overlapping the square [p - r, p + r].
+    // This is a superset of the entries within distance r from p.
+    pub fn near(&self, p: P, r: f64) -> impl Iterator<Item = (usize, P)> + '_ {
+        let (x0, y0) = self.cell_of(p - P::new(r, r));
+        let (x1, y1) = self.cell_of(p + P::new(r, r));
+
+        (x0..=x1).flat_map(move |x| {
+            (y0..=y1).flat_map(move |y| self.cells[x * self.ny + y].iter().copied())
+        })
+    }
+
+    // Returns the ids whose positions are within distance r from p (inclusive).
+    pub fn within(&self, p: P, r: f64) -> Vec<usize> {
+        self.near(p, r)
+            .filter(|(_, q)| (*q - p).square_length() <= r * r)
+            .map(|(id, _)| id)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lyon_geom::{Box2D, Point};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::{SpatialIndex, P};
+
+    #[test]
+    fn test_within() {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let bb = Box2D::new(Point::new(10., 20.), Point::new(300., 200.));
+        let mut index = SpatialIndex::new(bb, 10.);
+
+        // Some points are outside the bounding box.
+        let mut ps = vec![];
+        for i in 0..500 {
+            let p = P::new(rng.gen_range(-50.0..350.0), rng.gen_range(-50.0..250.0));
+            index.insert(i, p);
+            ps.push(Some(p));
+        }
+        for i in (0..500).step_by(3) {
+            index.remove(i, ps[i].unwrap());
+            ps[i] = None;
+        }
+
+        for _ in 0..100 {
+            let p = P::new(rng.gen_range(-50.0..350.0), rng.gen_range(-50.0..250.0));
+            let r = rng.gen_range(0.0..30.0);
+
+            let mut expected = ps
+                .iter()
+                .enumerate()
+                .filter_map(|(i, q)| q.filter(|q| (*q - p).square_length() <= r * r).map(|_| i))
+                .collect::<Vec<_>>();
+            let mut actual = index.within(p, r);
+
+            expected.sort();
+            actual.sort();
+
+            assert_eq!(expected, actual);
+        }
+    }
+}
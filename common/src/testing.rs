@@ -0,0 +1,24 @@
+// Fixtures for the tests, shared with the other crates through the "testing" feature.
+
+use euclid::default::{Box2D, Point2D};
+
+use crate::{Attendee, Problem};
+
+// A 300x300 room with the stage on 100..200 and no pillars. The tests needing another
+// geometry override the fields.
+pub fn problem(musicians: Vec<usize>, attendees: Vec<Attendee>) -> Problem {
+    Problem {
+        room: Box2D::new(Point2D::new(0., 0.), Point2D::new(300., 300.)),
+        stage: Box2D::new(Point2D::new(100., 100.), Point2D::new(200., 200.)),
+        musicians,
+        attendees,
+        pillars: vec![],
+    }
+}
+
+pub fn attendee(x: f64, y: f64, tastes: Vec<f64>) -> Attendee {
+    Attendee {
+        position: Point2D::new(x, y),
+        tastes,
+    }
+}
@@ -0,0 +1,354 @@
+use anyhow::Result;
+use euclid::default::{Box2D, Point2D, Vector2D};
+
+use crate::{board::Board, Attendee, Pillar, Placement, Problem, Solution};
+
+type Point = Point2D<f64>;
+type Vector = Vector2D<f64>;
+
+// Similarity transform p -> scale * m * p + offset, where m is a rotation by a multiple
+// of 90 degrees, possibly mirrored. The coordinates are only permuted and negated, so the
+// transform and its inverse are exact as long as scale is a power of 2 and the offsets
+// are representable, e.g. integers.
+//
+// Solvers which only handle one orientation of the stage can solve
+// transform.problem(&problem) and map the solution back by transform.solution_back().
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    m: [[f64; 2]; 2],
+    scale: f64,
+    offset: Vector,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Self {
+            m: [[1., 0.], [0., 1.]],
+            scale: 1.,
+            offset: Vector::zero(),
+        }
+    }
+
+    pub fn translate(v: Vector) -> Self {
+        Self {
+            offset: v,
+            ..Self::identity()
+        }
+    }
+
+    pub fn scale(d: f64) -> Self {
+        assert!(d > 0.);
+        Self {
+            scale: d,
+            ..Self::identity()
+        }
+    }
+
+    // Counterclockwise rotation by quarter_turns * 90 degrees around the origin.
+    pub fn rotate(quarter_turns: i32) -> Self {
+        let m = match quarter_turns.rem_euclid(4) {
+            0 => [[1., 0.], [0., 1.]],
+            1 => [[0., -1.], [1., 0.]],
+            2 => [[-1., 0.], [0., -1.]],
+            _ => [[0., 1.], [-1., 0.]],
+        };
+        Self {
+            m,
+            ..Self::identity()
+        }
+    }
+
+    // (x, y) -> (-x, y)
+    pub fn mirror_x() -> Self {
+        Self {
+            m: [[-1., 0.], [0., 1.]],
+            ..Self::identity()
+        }
+    }
+
+    // (x, y) -> (x, -y)
+    pub fn mirror_y() -> Self {
+        Self {
+            m: [[1., 0.], [0., -1.]],
+            ..Self::identity()
+        }
+    }
+
+    // (x, y) -> (y, x), same as Problem::flipped().
+    pub fn swap_xy() -> Self {
+        Self {
+            m: [[0., 1.], [1., 0.]],
+            ..Self::identity()
+        }
+    }
+
+    // The 8 orientations of a square, i.e. the rotations with and without mirroring.
+    pub fn orientations() -> Vec<Self> {
+        (0..8)
+            .map(|i| {
+                let t = Self::rotate(i % 4);
+                if i < 4 {
+                    t
+                } else {
+                    Self::mirror_x().then(&t)
+                }
+            })
+            .collect()
+    }
+
+    // Mirrors the problem so that the stage is closest to the bottom left corner of the
+    // room, and moves stage.min to the origin.
+    pub fn stage_at_origin(problem: &Problem) -> Self {
+        let (stage, room) = (problem.stage, problem.room);
+        let mut t = Self::identity();
+        if room.max.x - stage.max.x < stage.min.x - room.min.x {
+            t = t.then(&Self::mirror_x());
+        }
+        if room.max.y - stage.max.y < stage.min.y - room.min.y {
+            t = t.then(&Self::mirror_y());
+        }
+        let min = t.rect(stage).min;
+        t.then(&Self::translate(-min.to_vector()))
+    }
+
+    // self followed by next.
+    pub fn then(&self, next: &Self) -> Self {
+        let (a, b) = (&next.m, &self.m);
+        let mut m = [[0.; 2]; 2];
+        for i in 0..2 {
+            for j in 0..2 {
+                m[i][j] = a[i][0] * b[0][j] + a[i][1] * b[1][j];
+            }
+        }
+        Self {
+            m,
+            scale: self.scale * next.scale,
+            offset: next.vector(self.offset) + next.offset,
+        }
+    }
+
+    pub fn inverse(&self) -> Self {
+        // m is orthogonal, so its inverse is its transpose.
+        let m = [[self.m[0][0], self.m[1][0]], [self.m[0][1], self.m[1][1]]];
+        let linear = Self {
+            m,
+            scale: 1. / self.scale,
+            offset: Vector::zero(),
+        };
+        Self {
+            offset: -linear.vector(self.offset),
+            ..linear
+        }
+    }
+
+    // Applies the linear part only.
+    pub fn vector(&self, v: Vector) -> Vector {
+        let m = &self.m;
+        Vector::new(
+            self.scale * (m[0][0] * v.x + m[0][1] * v.y),
+            self.scale * (m[1][0] * v.x + m[1][1] * v.y),
+        )
+    }
+
+    pub fn point(&self, p: Point) -> Point {
+        (self.vector(p.to_vector()) + self.offset).to_point()
+    }
+
+    pub fn rect(&self, b: Box2D<f64>) -> Box2D<f64> {
+        Box2D::from_points([self.point(b.min), self.point(b.max)])
+    }
+
+    pub fn problem(&self, problem: &Problem) -> Problem {
+        Problem {
+            room: self.rect(problem.room),
+            stage: self.rect(problem.stage),
+            musicians: problem.musicians.clone(),
+            attendees: problem
+                .attendees
+                .iter()
+                .map(|a| Attendee {
+                    position: self.point(a.position),
+                    tastes: a.tastes.clone(),
+                })
+                .collect(),
+            pillars: problem
+                .pillars
+                .iter()
+                .map(|p| Pillar {
+                    center: self.point(p.center),
+                    radius: p.radius * self.scale,
+                })
+                .collect(),
+        }
+    }
+
+    // Maps a solution of the original problem to the transformed one.
+    pub fn solution(&self, solution: &Solution) -> Solution {
+        Solution {
+            placements: solution
+                .placements
+                .iter()
+                .map(|p| Placement {
+                    position: self.point(p.position),
+                })
+                .collect(),
+            ..solution.clone()
+        }
+    }
+
+    // Maps a solution of the transformed problem back to the original one.
+    pub fn solution_back(&self, solution: &Solution) -> Solution {
+        self.inverse().solution(solution)
+    }
+
+    // Rebuilds a board of the transformed problem on the original problem, with the same
+    // placements, volumes and constraints.
+    pub fn board_back(&self, board: &Board, problem: &Problem) -> Result<Board> {
+        let back = self.inverse();
+        let mut res = Board::new(board.problem_id, problem.clone(), &board.solver, false);
+        res.set_constraints(board.constraints().transformed(&back))?;
+        for (m, p) in board.musicians().iter().enumerate() {
+            res.set_volume(m, board.volume(m));
+            if let Some((p, _)) = p {
+                res.try_place(m, back.point(p.to_point()))?;
+            }
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use euclid::default::{Box2D, Point2D, Vector2D};
+
+    use crate::{
+        board::Board,
+        evaluate,
+        testing::{self, attendee},
+        Pillar, Placement, Problem, Solution,
+    };
+
+    use super::Transform;
+
+    fn problem() -> Problem {
+        Problem {
+            room: Box2D::new(Point2D::new(0., 0.), Point2D::new(300., 200.)),
+            stage: Box2D::new(Point2D::new(150., 20.), Point2D::new(250., 80.)),
+            pillars: vec![Pillar {
+                center: Point2D::new(200., 130.),
+                radius: 7.,
+            }],
+            ..testing::problem(
+                vec![0, 1, 0],
+                vec![
+                    attendee(100., 150., vec![1000., -500.]),
+                    attendee(280., 190., vec![-200., 1500.]),
+                    attendee(50., 10., vec![700., 300.]),
+                ],
+            )
+        }
+    }
+
+    fn solution() -> Solution {
+        Solution {
+            problem_id: 0,
+            solver: "test".to_owned(),
+            placements: [(170., 40.), (200.5, 61.25), (230., 35.)]
+                .iter()
+                .map(|(x, y)| Placement {
+                    position: Point2D::new(*x, *y),
+                })
+                .collect(),
+            volumes: vec![10., 10., 10.],
+        }
+    }
+
+    fn transforms() -> Vec<Transform> {
+        let mut res = vec![];
+        for t in Transform::orientations() {
+            res.push(t);
+            res.push(
+                Transform::translate(Vector2D::new(-150., -20.))
+                    .then(&t)
+                    .then(&Transform::scale(2.)),
+            );
+        }
+        res
+    }
+
+    #[test]
+    fn test_inverse_is_exact() {
+        let s = solution();
+        for t in transforms() {
+            let back = t.solution_back(&t.solution(&s));
+            for (p, q) in s.placements.iter().zip(back.placements.iter()) {
+                assert_eq!(p.position, q.position, "{:?}", t);
+            }
+            assert_eq!(t.then(&t.inverse()), Transform::identity());
+        }
+    }
+
+    #[test]
+    fn test_score_is_invariant() {
+        let (p, s) = (problem(), solution());
+        let score = evaluate(&p, &s);
+        for t in Transform::orientations() {
+            assert_eq!(evaluate(&t.problem(&p), &t.solution(&s)), score, "{:?}", t);
+        }
+    }
+
+    #[test]
+    fn test_orientations() {
+        let p = Point2D::new(3., 1.);
+        let mut images = Transform::orientations()
+            .iter()
+            .map(|t| (t.point(p).x as i32, t.point(p).y as i32))
+            .collect::<Vec<_>>();
+        images.sort();
+        images.dedup();
+        assert_eq!(images.len(), 8);
+
+        assert_eq!(Transform::rotate(1).point(p), Point2D::new(-1., 3.));
+        assert_eq!(
+            Transform::swap_xy().problem(&problem()).stage,
+            problem().flipped().stage
+        );
+    }
+
+    #[test]
+    fn test_board_back() {
+        let (p, s) = (problem(), solution());
+        let t = Transform::stage_at_origin(&p);
+        let q = t.problem(&p);
+
+        let mut board = Board::new(0, q.clone(), "test", false);
+        for (m, pl) in t.solution(&s).placements.iter().enumerate() {
+            board.set_volume(m, 10.);
+            board.try_place(m, pl.position).unwrap();
+        }
+
+        let back = t.board_back(&board, &p).unwrap();
+        assert_eq!(back.score(), board.score());
+        assert_eq!(
+            back.solution().unwrap().placements[1].position,
+            s.placements[1].position
+        );
+    }
+
+    #[test]
+    fn test_stage_at_origin() {
+        let p = problem();
+        let t = Transform::stage_at_origin(&p);
+        let q = t.problem(&p);
+        assert_eq!(q.stage.min, Point2D::new(0., 0.));
+        assert_eq!(q.stage.size(), p.stage.size());
+        // The stage is closer to the right wall, so it is mirrored.
+        assert_eq!(q.room.min, Point2D::new(-50., -20.));
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::BinaryHeap;
+
+use euclid::default::{Box2D, Point2D};
+
+use crate::{
+    float::{Float, F64},
+    Attendee, Problem,
+};
+
+// Stop refining when the bound is within this ratio of an achievable value.
+const TOLERANCE: f64 = 1e-4;
+const MAX_ITER: usize = 100_000;
+
+fn distance_to_box_sq(bb: &Box2D<f64>, p: Point2D<f64>) -> f64 {
+    let dx = (bb.min.x - p.x).max(p.x - bb.max.x).max(0.);
+    let dy = (bb.min.y - p.y).max(p.y - bb.max.y).max(0.);
+    dx * dx + dy * dy
+}
+
+// Score of instrument ins at the point in bb closest to each attendee, counting only
+// positive tastes. scale is volume * q, which is rounded up per attendee as evaluate()
+// does. An upper bound of the unblocked score anywhere in bb.
+fn impact_bound(attendees: &[Attendee], ins: usize, bb: &Box2D<f64>, scale: f64) -> f64 {
+    let mut res = 0.;
+    for a in attendees.iter() {
+        if a.tastes[ins] > 0. {
+            // Attendees are at least 10 away from the musicians.
+            let d2 = distance_to_box_sq(bb, a.position).max(100.);
+            res += (scale * (1_000_000. * a.tastes[ins] / d2).ceil()).ceil();
+        }
+    }
+    res
+}
+
+// Upper bound of the score of a single musician with instrument ins anywhere in area,
+// by best-first branch and bound over quadtree cells.
+fn max_impact(attendees: &[Attendee], ins: usize, area: Box2D<f64>, scale: f64) -> f64 {
+    let mut cells = vec![area];
+    let mut que = BinaryHeap::new();
+    que.push((F64::new(impact_bound(attendees, ins, &area, scale)), 0));
+
+    let mut best = 0.;
+    for _ in 0..MAX_ITER {
+        let (bound, i) = *que.peek().unwrap();
+        let bound = bound.get();
+        if bound <= best * (1. + TOLERANCE) {
+            break;
+        }
+        que.pop();
+
+        let bb = cells[i];
+        let c = bb.center();
+        best = f64::max(best, impact_bound(attendees, ins, &Box2D::new(c, c), scale));
+
+        if bb.width().max(bb.height()) < 0.1 {
+            // The bound of a tiny cell is as good as it gets.
+            que.push((F64::new(bound), i));
+            break;
+        }
+
+        let children = if bb.width() >= bb.height() {
+            [
+                Box2D::new(bb.min, Point2D::new(c.x, bb.max.y)),
+                Box2D::new(Point2D::new(c.x, bb.min.y), bb.max),
+            ]
+        } else {
+            [
+                Box2D::new(bb.min, Point2D::new(bb.max.x, c.y)),
+                Box2D::new(Point2D::new(bb.min.x, c.y), bb.max),
+            ]
+        };
+        for child in children {
+            que.push((
+                F64::new(impact_bound(attendees, ins, &child, scale)),
+                cells.len(),
+            ));
+            cells.push(child);
+        }
+    }
+
+    // The maximum of the bounds of the remaining cells covers the whole area.
+    que.peek().unwrap().0.get()
+}
+
+// Upper bound of the closeness factor q of a musician among count musicians with the
+// same instrument. The disks of radius 5 around the musicians are disjoint, so at most
+// ((d + 5) / 5)^2 musicians (including itself) are within distance d, i.e. the j-th
+// nearest one is at least max(10, 5 * sqrt(j + 1) - 5) away.
+fn max_q(count: usize) -> f64 {
+    let mut res = 1.;
+    for j in 1..count {
+        res += 1. / f64::max(10., 5. * ((j + 1) as f64).sqrt() - 5.);
+    }
+    res
+}
+
+// Upper bound of the score of any solution, relaxing the problem so that every musician
+// stands at the best point for the instrument, nobody is blocked, negative tastes are
+// ignored, the volume is 10 and (for v2) q takes its maximum over any packing.
+pub fn upper_bound(problem_id: u32, problem: &Problem) -> f64 {
+    if problem.attendees.is_empty() {
+        return 0.;
+    }
+    let area = problem.stage.inflate(-10., -10.);
+    let num_instruments = problem.attendees[0].tastes.len();
+
+    let mut counts = vec![0; num_instruments];
+    for ins in problem.musicians.iter() {
+        counts[*ins] += 1;
+    }
+
+    let mut res = 0.;
+    for (ins, count) in counts.iter().enumerate() {
+        if *count == 0 {
+            continue;
+        }
+        let q = if problem_id <= 55 { 1. } else { max_q(*count) };
+        res += *count as f64 * max_impact(&problem.attendees, ins, area, 10. * q);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use euclid::default::{Box2D, Point2D};
+    use lyon_geom::Point;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::{
+        board::Board,
+        evaluate,
+        testing::{self, attendee},
+        Placement, Problem, Solution,
+    };
+
+    use super::{impact_bound, upper_bound};
+
+    #[test]
+    fn test_impact_bound() {
+        // Two musicians 12 apart, so that q = 1 + 1/12 is not an integer and the score
+        // is rounded up per attendee. Nobody blocks the others. v2 by the problem id.
+        let problem = testing::problem(
+            vec![0, 0],
+            (0..7)
+                .map(|i| attendee(20. + 37. * i as f64, 30., vec![123.4 + 57. * i as f64]))
+                .collect(),
+        );
+        let ps = [Point2D::new(140., 150.), Point2D::new(152., 150.)];
+        let solution = Solution {
+            problem_id: 56,
+            solver: "test".to_owned(),
+            placements: ps.iter().map(|p| Placement { position: *p }).collect(),
+            volumes: vec![10., 10.],
+        };
+
+        let q = 1. + 1. / 12.;
+        let bound = ps
+            .iter()
+            .map(|p| impact_bound(&problem.attendees, 0, &Box2D::new(*p, *p), 10. * q))
+            .sum::<f64>();
+        assert_eq!(bound, evaluate(&problem, &solution));
+    }
+
+    #[test]
+    fn test_upper_bound() {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        for problem_id in [42, 85] {
+            let problem =
+                Problem::read_from_file(format!("../problems/{}.json", problem_id)).unwrap();
+            let bound = upper_bound(problem_id, &problem);
+
+            let mut board = Board::new(problem_id, problem, "test_solver", false);
+            for i in 0..board.prob.musicians.len() {
+                loop {
+                    let x: f64 = rng.gen_range(board.prob.stage.min.x..board.prob.stage.max.x);
+                    let y: f64 = rng.gen_range(board.prob.stage.min.y..board.prob.stage.max.y);
+                    if board.try_place(i, Point::new(x, y)).is_ok() {
+                        break;
+                    }
+                }
+                board.set_volume(i, 10.);
+            }
+            board.hungarian_v2(3);
+
+            assert!(board.score_ignore_negative() <= bound);
+        }
+    }
+}
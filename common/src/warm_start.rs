@@ -0,0 +1,147 @@
+use anyhow::Result;
+use euclid::default::{Box2D, Point2D};
+
+use crate::{
+    board::Board,
+    repair::{repair, RepairReport},
+    Placement, Problem, Solution,
+};
+
+// Maps x in [from.min, from.max] to [to.min, to.max] on each axis.
+fn stretch(p: Point2D<f64>, from: Box2D<f64>, to: Box2D<f64>) -> Point2D<f64> {
+    let axis = |x: f64, from: (f64, f64), to: (f64, f64)| {
+        if from.1 > from.0 {
+            to.0 + (x - from.0) / (from.1 - from.0) * (to.1 - to.0)
+        } else {
+            (to.0 + to.1) / 2.
+        }
+    };
+    Point2D::new(
+        axis(p.x, (from.min.x, from.max.x), (to.min.x, to.max.x)),
+        axis(p.y, (from.min.y, from.max.y), (to.min.y, to.max.y)),
+    )
+}
+
+// Starts a solution of problem from a solution of another problem with similar layouts
+// (see similarity::SimilarityIndex). The positions are stretched from the stage of the
+// source to the stage of the problem, made legal by repair(), which also places the
+// extra musicians, and the instruments are assigned by the hungarian algorithm. The
+// volumes of the result are 10.
+pub fn transfer(
+    source: &Problem,
+    solution: &Solution,
+    problem_id: u32,
+    problem: &Problem,
+) -> Result<(Board, RepairReport)> {
+    let from = source.stage.inflate(-10., -10.);
+    let to = problem.stage.inflate(-10., -10.);
+    let mapped = Solution {
+        problem_id,
+        solver: solution.solver.clone(),
+        placements: solution
+            .placements
+            .iter()
+            .take(problem.musicians.len())
+            .map(|p| Placement {
+                position: stretch(p.position, from, to),
+            })
+            .collect(),
+        volumes: vec![10.; problem.musicians.len()],
+    };
+    let (mapped, report) = repair(problem, &mapped)?;
+
+    let mut board = Board::new(problem_id, problem.clone(), &solution.solver, false);
+    for (m, p) in mapped.placements.iter().enumerate() {
+        board.set_volume(m, 10.);
+        board.try_place(m, p.position)?;
+    }
+    if problem.is_v2() {
+        board.hungarian_v2(3);
+    } else {
+        board.hungarian();
+    }
+
+    Ok((board, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use euclid::default::{Box2D, Point2D};
+
+    use crate::{
+        board::Board,
+        layout::square_lattice,
+        testing::{self, attendee},
+        Placement, Problem, Solution,
+    };
+
+    use super::transfer;
+
+    fn problem(stage: Box2D<f64>, musicians: Vec<usize>) -> Problem {
+        Problem {
+            room: Box2D::new(Point2D::new(0., 0.), Point2D::new(400., 300.)),
+            stage,
+            ..testing::problem(
+                musicians,
+                (0..20)
+                    .map(|i| {
+                        let i = i as f64;
+                        attendee(10. + i * 19., 20., vec![100. * i - 900., 500. - 50. * i])
+                    })
+                    .collect(),
+            )
+        }
+    }
+
+    fn solution(problem: &Problem) -> Solution {
+        let ps = square_lattice(problem.stage.inflate(-10., -10.), 15.);
+        Solution {
+            problem_id: 0,
+            solver: "test".to_owned(),
+            placements: ps[..problem.musicians.len()]
+                .iter()
+                .map(|p| Placement { position: *p })
+                .collect(),
+            volumes: vec![10.; problem.musicians.len()],
+        }
+    }
+
+    #[test]
+    fn test_transfer_to_itself() {
+        let stage = Box2D::new(Point2D::new(100., 100.), Point2D::new(200., 200.));
+        let p = problem(stage, vec![0, 1, 0, 1, 1]);
+        let s = solution(&p);
+
+        let mut board = Board::new(1, p.clone(), "test", false);
+        for (m, pl) in s.placements.iter().enumerate() {
+            board.set_volume(m, 10.);
+            board.try_place(m, pl.position).unwrap();
+        }
+
+        let (t, report) = transfer(&p, &s, 1, &p).unwrap();
+        assert_eq!(report.moved + report.relocated, 0);
+        // The positions are the same, with the instruments reassigned.
+        assert!(t.score_ignore_negative() >= board.score_ignore_negative());
+    }
+
+    #[test]
+    fn test_transfer_to_smaller_stage() {
+        let p = problem(
+            Box2D::new(Point2D::new(100., 100.), Point2D::new(200., 200.)),
+            vec![0, 1, 0, 1, 1, 0, 0, 1],
+        );
+        let q = problem(
+            Box2D::new(Point2D::new(250., 150.), Point2D::new(310., 200.)),
+            vec![1, 1, 0, 0, 1, 0, 1, 0, 1],
+        );
+        let s = solution(&p);
+
+        let (t, report) = transfer(&p, &s, 2, &q).unwrap();
+        assert!(t.solution().is_ok());
+        assert_eq!(t.problem_id, 2);
+        // The stage is too narrow for the spacing of the source, and there is one more
+        // musician.
+        assert!(report.moved > 0);
+        assert!(report.relocated >= 1);
+    }
+}
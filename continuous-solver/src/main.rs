@@ -0,0 +1,70 @@
+mod solver;
+
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use common::{evaluate, Problem, RawSolution, Solution};
+use log::info;
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::solver::{random_solution, Params, Solver};
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[arg(short, long)]
+    problem_id: u32,
+    #[arg(long)]
+    initial_solution: Option<PathBuf>,
+    #[arg(long, default_value_t = 20)]
+    rounds: usize,
+    #[arg(long, default_value_t = 200)]
+    iters: usize,
+    #[arg(long, default_value_t = 1.0)]
+    lr: f64,
+    #[arg(long, default_value_t = 1e-6)]
+    initial_penalty: f64,
+    #[arg(long, default_value_t = 2.0)]
+    penalty_growth: f64,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+}
+
+fn main() -> Result<()> {
+    env_logger::init();
+    let args = Args::parse();
+    let f = PathBuf::from(format!("../problems/{}.json", args.problem_id));
+    if !f.is_file() {
+        return Err(anyhow!("File not found: {}", f.display()));
+    }
+    let problem: Problem = Problem::read_from_file(f)?;
+
+    let initial_solution = if let Some(path) = args.initial_solution {
+        let s = std::fs::read_to_string(path)?;
+        Solution::from(RawSolution::from_json(&s)?)
+    } else {
+        let mut rng = StdRng::seed_from_u64(args.seed);
+        random_solution(args.problem_id, &problem, &mut rng)?
+    };
+
+    let params = Params {
+        rounds: args.rounds,
+        iters: args.iters,
+        lr: args.lr,
+        initial_penalty: args.initial_penalty,
+        penalty_growth: args.penalty_growth,
+    };
+    let mut solver = Solver::new(args.problem_id, problem.clone(), &initial_solution, params);
+    let sol = solver.solve()?;
+
+    let score = evaluate(&problem, &sol);
+    info!("score = {}", score);
+
+    if !std::path::Path::new("results").is_dir() {
+        std::fs::create_dir_all("results")?;
+    }
+    let output = PathBuf::from(format!("results/{}-{}.json", args.problem_id, score));
+    Solution::write_to_file(output, sol)?;
+
+    Ok(())
+}
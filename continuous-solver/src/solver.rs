@@ -0,0 +1,500 @@
+use std::f64::consts::PI;
+
+use anyhow::{bail, Context, Result};
+use common::{
+    board::Board,
+    evaluate,
+    finetune::{finetune, FinetuneOptions},
+    repair::repair,
+    spatial_index::SpatialIndex,
+    Placement, Problem, Solution,
+};
+use euclid::default::{Box2D, Vector2D};
+use log::info;
+use lyon_geom::Point;
+use rand::{seq::SliceRandom, Rng};
+
+pub const SOLVER_NAME: &str = "continuous-solver";
+
+type P = Vector2D<f64>;
+
+#[derive(Debug, Clone)]
+pub struct Params {
+    pub rounds: usize,
+    pub iters: usize,
+    pub lr: f64,
+    // Weight of the squared overlaps and out-of-stage distances in the first round.
+    pub initial_penalty: f64,
+    // The penalty is multiplied by this every round.
+    pub penalty_growth: f64,
+}
+
+// Maximizes a smooth surrogate of the score over all the musician positions at once
+// with Adam, allowing overlaps and leaving the stage at a penalty which grows every
+// round. The blocking is modeled by the visibilities of the visibility mode of Board,
+// which are recomputed from a legalized configuration at the beginning of each round.
+pub struct Solver {
+    problem_id: u32,
+    prob: Problem,
+    params: Params,
+
+    // Area musicians can be placed on.
+    stage: Box2D<f64>,
+
+    // m -> position (may be illegal)
+    xs: Vec<P>,
+
+    // m -> [(a, visibility * 1_000_000 * taste)]
+    weights: Vec<Vec<(usize, f64)>>,
+    // m -> 1 if m contributes positively, 0 otherwise (the volume will be 0).
+    volumes: Vec<f64>,
+}
+
+pub fn random_solution(problem_id: u32, prob: &Problem, rng: &mut impl Rng) -> Result<Solution> {
+    let stage = prob.stage.inflate(-10., -10.);
+
+    let mut ps = vec![];
+    let mut x = stage.min.x;
+    while x <= stage.max.x {
+        let mut y = stage.min.y;
+        while y <= stage.max.y {
+            ps.push(Point::new(x, y));
+            y += 10.;
+        }
+        x += 10.;
+    }
+    if ps.len() < prob.musicians.len() {
+        bail!(
+            "only {} lattice points for {} musicians",
+            ps.len(),
+            prob.musicians.len()
+        );
+    }
+    ps.shuffle(rng);
+
+    Ok(Solution {
+        problem_id,
+        solver: SOLVER_NAME.to_owned(),
+        placements: ps[..prob.musicians.len()]
+            .iter()
+            .map(|p| Placement { position: *p })
+            .collect(),
+        volumes: vec![1.; prob.musicians.len()],
+    })
+}
+
+impl Solver {
+    pub fn new(problem_id: u32, prob: Problem, initial: &Solution, params: Params) -> Self {
+        let n = prob.musicians.len();
+        Self {
+            problem_id,
+            stage: prob.stage.inflate(-10., -10.),
+            prob,
+            params,
+            xs: initial
+                .placements
+                .iter()
+                .map(|p| p.position.to_vector())
+                .collect(),
+            weights: vec![vec![]; n],
+            volumes: vec![1.; n],
+        }
+    }
+
+    pub fn solve(&mut self) -> Result<Solution> {
+        let initial_board = match self.snap(false) {
+            Some(board) => board,
+            None => {
+                // The initial solution is too crowded to snap.
+                let solution = Solution {
+                    problem_id: self.problem_id,
+                    solver: SOLVER_NAME.to_owned(),
+                    placements: self
+                        .xs
+                        .iter()
+                        .map(|x| Placement {
+                            position: x.to_point(),
+                        })
+                        .collect(),
+                    volumes: vec![1.; self.xs.len()],
+                };
+                let (repaired, report) = repair(&self.prob, &solution)?;
+                info!(
+                    "repaired the initial solution: {} moved, {} relocated",
+                    report.moved, report.relocated
+                );
+                self.xs = repaired
+                    .placements
+                    .iter()
+                    .map(|p| p.position.to_vector())
+                    .collect();
+                self.snap(false)
+                    .context("failed to snap the repaired solution")?
+            }
+        };
+        let initial_solution = initial_board.solution_with_optimized_volume().unwrap();
+        let initial_score = evaluate(&self.prob, &initial_solution);
+        info!("initial score: {}", initial_score);
+
+        self.refresh();
+        let (_, s0, _) = self.objective(&self.xs, 0., 1.);
+        let s0 = s0.abs().max(1.);
+
+        for round in 0..self.params.rounds {
+            self.refresh();
+
+            let mu = self.params.initial_penalty * self.params.penalty_growth.powi(round as i32);
+            let lr = self.params.lr * (1. - round as f64 / self.params.rounds as f64).max(0.05);
+
+            self.adam(mu, s0, lr);
+
+            let (obj, score, _) = self.objective(&self.xs, mu, s0);
+            info!(
+                "round {:>3}: penalty = {:.3e}, surrogate = {:.0}, objective = {:.6}",
+                round, mu, score, obj
+            );
+        }
+
+        let Some(mut board) = self.snap(false) else {
+            info!("failed to snap");
+            return Ok(initial_solution);
+        };
+        info!("snapped score: {}", board.score());
+
+        let reports = finetune(&mut board, &FinetuneOptions::default().with_max_passes(50));
+        if let Some(r) = reports.last() {
+            info!("finetuned score: {}", r.score_after);
+        }
+
+        let mut solution = board.solution_with_optimized_volume().unwrap();
+        solution.solver = SOLVER_NAME.to_owned();
+        let score = evaluate(&self.prob, &solution);
+        info!("final score: {} (initial: {})", score, initial_score);
+
+        if score < initial_score {
+            return Ok(initial_solution);
+        }
+        Ok(solution)
+    }
+
+    fn adam(&mut self, mu: f64, s0: f64, lr: f64) {
+        const BETA1: f64 = 0.9;
+        const BETA2: f64 = 0.999;
+        const EPS: f64 = 1e-12;
+
+        let n = self.xs.len();
+        let mut m1 = vec![P::zero(); n];
+        let mut m2 = vec![P::zero(); n];
+
+        for t in 1..=self.params.iters {
+            let (_, _, grad) = self.objective(&self.xs, mu, s0);
+
+            let c1 = 1. - BETA1.powi(t as i32);
+            let c2 = 1. - BETA2.powi(t as i32);
+            for i in 0..n {
+                let g = grad[i];
+                m1[i] = m1[i] * BETA1 + g * (1. - BETA1);
+                m2[i] = m2[i] * BETA2 + P::new(g.x * g.x, g.y * g.y) * (1. - BETA2);
+
+                let (a, b) = (m1[i] / c1, m2[i] / c2);
+                // Ascent, as the objective is maximized.
+                self.xs[i] += P::new(a.x / (b.x.sqrt() + EPS), a.y / (b.y.sqrt() + EPS)) * lr;
+            }
+        }
+    }
+
+    // Recomputes the weights and the volumes from the legalized configuration.
+    fn refresh(&mut self) {
+        let Some(board) = self.snap(true) else {
+            return;
+        };
+
+        for m in 0..self.xs.len() {
+            let ins = self.prob.musicians[m];
+            self.weights[m].clear();
+            for (a, attendee) in self.prob.attendees.iter().enumerate() {
+                let w = board.seeing_weight(m, a);
+                if w > 0. {
+                    self.weights[m].push((a, w * 1_000_000. * attendee.tastes[ins]));
+                }
+            }
+            self.volumes[m] = if board.contribution2(m) > 0. { 1. } else { 0. };
+        }
+    }
+
+    // Returns (objective, surrogate score, gradient of objective).
+    // objective = surrogate score / s0 - mu * (Σ overlap^2 + Σ out-of-stage distance^2)
+    fn objective(&self, xs: &[P], mu: f64, s0: f64) -> (f64, f64, Vec<P>) {
+        let n = xs.len();
+
+        let mut impacts = vec![0.; n];
+        let mut d_impacts = vec![P::zero(); n];
+        for m in 0..n {
+            for (a, w) in self.weights[m].iter() {
+                let d = self.prob.attendees[*a].position.to_vector() - xs[m];
+                // Attendees are at least 10 away from the stage.
+                let d2 = d.square_length().max(100.);
+                impacts[m] += w / d2;
+                d_impacts[m] += d * (2. * w / (d2 * d2));
+            }
+        }
+
+        let mut qs = vec![1.; n];
+        let mut grad = vec![P::zero(); n];
+        if self.prob.is_v2() {
+            for m in 0..n {
+                for j in m + 1..n {
+                    if self.prob.musicians[m] != self.prob.musicians[j] {
+                        continue;
+                    }
+                    let d = xs[m] - xs[j];
+                    let l = d.length().max(10.);
+                    qs[m] += 1. / l;
+                    qs[j] += 1. / l;
+                    if l > 10. {
+                        // d/dxs[m] 1/|xs[m] - xs[j]| = -d / l^3
+                        let w = self.volumes[m] * impacts[m] + self.volumes[j] * impacts[j];
+                        grad[m] -= d * (w / l.powi(3));
+                        grad[j] += d * (w / l.powi(3));
+                    }
+                }
+            }
+        }
+
+        let mut score = 0.;
+        for m in 0..n {
+            score += self.volumes[m] * qs[m] * impacts[m];
+            grad[m] += d_impacts[m] * (self.volumes[m] * qs[m]);
+        }
+
+        let mut obj = score / s0;
+        for g in grad.iter_mut() {
+            *g /= s0;
+        }
+
+        let mut index = SpatialIndex::new(self.stage, 10.);
+        for (m, x) in xs.iter().enumerate() {
+            index.insert(m, *x);
+        }
+        for m in 0..n {
+            for j in index.within(xs[m], 10.) {
+                if j <= m {
+                    continue;
+                }
+                let d = xs[m] - xs[j];
+                let l = d.length();
+                let dir = if l > 0. { d / l } else { P::new(1., 0.) };
+                let pen = 10. - l;
+                obj -= mu * pen * pen;
+                grad[m] += dir * (2. * mu * pen);
+                grad[j] -= dir * (2. * mu * pen);
+            }
+
+            let (x, g) = (xs[m], &mut grad[m]);
+            for (v, min, max, g) in [
+                (x.x, self.stage.min.x, self.stage.max.x, &mut g.x),
+                (x.y, self.stage.min.y, self.stage.max.y, &mut g.y),
+            ] {
+                if v < min {
+                    obj -= mu * (min - v) * (min - v);
+                    *g += 2. * mu * (min - v);
+                } else if v > max {
+                    obj -= mu * (v - max) * (v - max);
+                    *g -= 2. * mu * (v - max);
+                }
+            }
+        }
+
+        (obj, score, grad)
+    }
+
+    // Legal configuration close to xs. The musicians which can stay stay, and the others
+    // go to the nearest free points. Returns None if the stage is too crowded to find one.
+    fn snap(&self, use_visibility: bool) -> Option<Board> {
+        let mut board = Board::new(
+            self.problem_id,
+            self.prob.clone(),
+            SOLVER_NAME,
+            use_visibility,
+        );
+
+        let clamp = |p: P| {
+            Point::new(
+                p.x.clamp(self.stage.min.x, self.stage.max.x),
+                p.y.clamp(self.stage.min.y, self.stage.max.y),
+            )
+        };
+
+        let mut rest = vec![];
+        for (m, x) in self.xs.iter().enumerate() {
+            if board.try_place(m, clamp(*x)).is_err() {
+                rest.push(m);
+            }
+        }
+        for m in rest {
+            let p = self.nearest_free_point(&board, m, clamp(self.xs[m]))?;
+            board.try_place(m, p).unwrap();
+        }
+
+        Some(board)
+    }
+
+    fn nearest_free_point(&self, board: &Board, m: usize, p: Point<f64>) -> Option<Point<f64>> {
+        const STEP: f64 = 0.5;
+
+        let mut r = STEP;
+        while r <= 20. {
+            let k = ((2. * PI * r / STEP) as usize).max(8);
+            for i in 0..k {
+                let t = 2. * PI * i as f64 / k as f64;
+                let q = p + P::new(r * t.cos(), r * t.sin());
+                if board.can_place(m, q) {
+                    return Some(q);
+                }
+            }
+            r += STEP;
+        }
+
+        let mut ps = vec![];
+        let mut x = self.stage.min.x;
+        while x <= self.stage.max.x {
+            let mut y = self.stage.min.y;
+            while y <= self.stage.max.y {
+                ps.push(Point::new(x, y));
+                y += 5.;
+            }
+            x += 5.;
+        }
+        ps.sort_by(|a, b| {
+            (*a - p)
+                .square_length()
+                .partial_cmp(&(*b - p).square_length())
+                .unwrap()
+        });
+        ps.into_iter().find(|q| board.can_place(m, *q))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{repair::is_legal, testing, Pillar, Placement, Solution};
+    use euclid::default::Point2D;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::{random_solution, Params, Solver, P};
+
+    fn solver(ps: &[(f64, f64)]) -> Solver {
+        let problem = common::Problem {
+            // v2, but the pillar blocks nothing.
+            pillars: vec![Pillar {
+                center: Point2D::new(290., 290.),
+                radius: 5.,
+            }],
+            ..testing::problem(
+                (0..ps.len()).map(|m| m % 2).collect(),
+                vec![
+                    testing::attendee(20., 150., vec![1000., -300.]),
+                    testing::attendee(150., 280., vec![200., 800.]),
+                    testing::attendee(280., 60., vec![-500., 600.]),
+                ],
+            )
+        };
+        let initial = Solution {
+            problem_id: 0,
+            solver: "test".to_owned(),
+            placements: ps
+                .iter()
+                .map(|(x, y)| Placement {
+                    position: Point2D::new(*x, *y),
+                })
+                .collect(),
+            volumes: vec![1.; ps.len()],
+        };
+        let params = Params {
+            rounds: 1,
+            iters: 1,
+            lr: 1.,
+            initial_penalty: 1e-6,
+            penalty_growth: 2.,
+        };
+        Solver::new(0, problem, &initial, params)
+    }
+
+    #[test]
+    fn test_gradient() {
+        // 0 and 1 overlap, 0 and 2 are close with the same instrument, and 3 is off the
+        // stage.
+        let mut solver = solver(&[(130., 150.), (135., 153.), (160., 170.), (195., 120.)]);
+        solver.refresh();
+        assert!(solver.weights.iter().all(|w| !w.is_empty()));
+
+        let (mu, s0) = (10., 1.);
+        let (_, _, grad) = solver.objective(&solver.xs, mu, s0);
+        let h = 1e-4;
+        for m in 0..solver.xs.len() {
+            for d in [P::new(h, 0.), P::new(0., h)] {
+                let mut xs = solver.xs.clone();
+                xs[m] += d;
+                let (f1, _, _) = solver.objective(&xs, mu, s0);
+                xs[m] -= d * 2.;
+                let (f2, _, _) = solver.objective(&xs, mu, s0);
+
+                let numeric = (f1 - f2) / (2. * h);
+                let analytic = grad[m].dot(d) / h;
+                assert!(
+                    (numeric - analytic).abs() <= 1e-4 * analytic.abs().max(1.),
+                    "musician {}: {} vs {}",
+                    m,
+                    numeric,
+                    analytic
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_penalties() {
+        // Without the weights only the penalties remain: 0 and 1 overlap by 4, and 3 is
+        // 3 off the stage, whose area is 110..190.
+        let solver = solver(&[(130., 150.), (136., 150.), (160., 170.), (193., 120.)]);
+        let mu = 0.5;
+        let (obj, score, grad) = solver.objective(&solver.xs, mu, 1.);
+        assert_eq!(score, 0.);
+        assert!((obj + mu * (4. * 4. + 3. * 3.)).abs() < 1e-9);
+        assert_eq!(grad[0], P::new(-8. * mu, 0.));
+        assert_eq!(grad[1], P::new(8. * mu, 0.));
+        assert_eq!(grad[2], P::zero());
+        assert_eq!(grad[3], P::new(-6. * mu, 0.));
+    }
+
+    #[test]
+    fn test_random_solution() {
+        let mut rng = StdRng::seed_from_u64(42);
+        // 9 x 9 lattice points on the stage.
+        let problem = solver(&[(150., 150.); 81]).prob;
+        assert!(random_solution(0, &problem, &mut rng).is_ok());
+        let problem = solver(&[(150., 150.); 82]).prob;
+        assert!(random_solution(0, &problem, &mut rng).is_err());
+    }
+
+    #[test]
+    fn test_snap() {
+        let mut rng = StdRng::seed_from_u64(42);
+        for i in 0..20 {
+            let ps = (0..12)
+                .map(|_| {
+                    if i % 2 == 0 {
+                        // Anywhere in the room.
+                        (rng.gen_range(0.0..300.0), rng.gen_range(0.0..300.0))
+                    } else {
+                        // Crowded around a point.
+                        (rng.gen_range(149.0..151.0), rng.gen_range(149.0..151.0))
+                    }
+                })
+                .collect::<Vec<_>>();
+            let solver = solver(&ps);
+            let board = solver.snap(false).unwrap();
+            assert!(is_legal(&solver.prob, &board.solution().unwrap()));
+        }
+    }
+}
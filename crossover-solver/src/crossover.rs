@@ -0,0 +1,237 @@
+use anyhow::{bail, Result};
+use common::{
+    board::Board,
+    layout::{remove_conflicts, square_lattice},
+    Problem, Solution,
+};
+use euclid::default::{Box2D, Point2D, Vector2D};
+use rand::Rng;
+
+pub const SOLVER_NAME: &str = "crossover-solver";
+
+type Point = Point2D<f64>;
+type Vector = Vector2D<f64>;
+
+// The part of the stage whose musicians are taken from the first parent. The rest comes
+// from the second one.
+#[derive(Debug, Clone, Copy)]
+pub enum Cut {
+    // The side of the line through origin which normal points to.
+    HalfPlane { origin: Point, normal: Vector },
+    Region(Box2D<f64>),
+}
+
+impl Cut {
+    // A line through a random point of the stage in a random direction, or a random
+    // rectangle in the stage, with the same probability.
+    pub fn random(stage: Box2D<f64>, rng: &mut impl Rng) -> Self {
+        let mut point = || {
+            Point::new(
+                rng.gen_range(stage.min.x..=stage.max.x),
+                rng.gen_range(stage.min.y..=stage.max.y),
+            )
+        };
+        let (p, q) = (point(), point());
+        if rng.gen_bool(0.5) {
+            let angle = rng.gen_range(0.0..std::f64::consts::TAU);
+            Cut::HalfPlane {
+                origin: p,
+                normal: Vector::new(angle.cos(), angle.sin()),
+            }
+        } else {
+            Cut::Region(Box2D::from_points([p, q]))
+        }
+    }
+
+    pub fn contains(&self, p: Point) -> bool {
+        match self {
+            Cut::HalfPlane { origin, normal } => (p - *origin).dot(*normal) >= 0.,
+            Cut::Region(r) => r.contains(p),
+        }
+    }
+
+    // Distance from p to the border of the cut.
+    pub fn distance(&self, p: Point) -> f64 {
+        match self {
+            Cut::HalfPlane { origin, normal } => (p - *origin).dot(*normal).abs() / normal.length(),
+            Cut::Region(r) => {
+                let dx = (r.min.x - p.x).max(p.x - r.max.x);
+                let dy = (r.min.y - p.y).max(p.y - r.max.y);
+                if dx <= 0. && dy <= 0. {
+                    -dx.max(dy)
+                } else {
+                    dx.max(0.).hypot(dy.max(0.))
+                }
+            }
+        }
+    }
+}
+
+// Combines the positions of a inside the cut with the ones of b outside of it.
+// Where the two sides conflict, the positions farther from the cut are kept. Missing
+// musicians are placed greedily on the other positions of the parents, and the
+// instruments are assigned to the positions by the hungarian algorithm.
+// The volumes of the child are 10.
+pub fn crossover(
+    problem_id: u32,
+    problem: &Problem,
+    a: &Solution,
+    b: &Solution,
+    cut: &Cut,
+) -> Result<Board> {
+    let n = problem.musicians.len();
+
+    let mut ps = a
+        .placements
+        .iter()
+        .filter(|p| cut.contains(p.position))
+        .chain(b.placements.iter().filter(|p| !cut.contains(p.position)))
+        .map(|p| p.position)
+        .collect::<Vec<_>>();
+    ps.sort_by(|p, q| cut.distance(*q).partial_cmp(&cut.distance(*p)).unwrap());
+    let mut ps = remove_conflicts(ps);
+    ps.truncate(n);
+
+    let mut board = Board::new(problem_id, problem.clone(), SOLVER_NAME, false);
+    for m in 0..n {
+        board.set_volume(m, 10.);
+    }
+    for (m, p) in ps.iter().enumerate() {
+        board.try_place(m, *p)?;
+    }
+
+    // Greedy repair.
+    let spare = a
+        .placements
+        .iter()
+        .chain(b.placements.iter())
+        .map(|p| p.position)
+        .collect::<Vec<_>>();
+    for m in ps.len()..n {
+        let mut best = None;
+        for p in spare.iter() {
+            if let Ok(gain) = board.score_increase_if_put_musician_on(m, *p) {
+                if best.is_none_or(|(g, _)| gain > g) {
+                    best = Some((gain, *p));
+                }
+            }
+        }
+        let p = match best {
+            Some((_, p)) => p,
+            None => {
+                let lattice = square_lattice(board.prob.stage, 10.);
+                let Some(p) = lattice.into_iter().find(|p| board.can_place(m, *p)) else {
+                    bail!("no room for musician {}", m);
+                };
+                p
+            }
+        };
+        board.try_place(m, p)?;
+    }
+
+    if problem.is_v2() {
+        board.hungarian_v2(3);
+    } else {
+        board.hungarian();
+    }
+
+    Ok(board)
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{
+        board::Board,
+        layout::square_lattice,
+        testing::{self, attendee},
+        Placement, Problem, Solution,
+    };
+    use euclid::default::{Point2D, Vector2D};
+    use rand::{rngs::SmallRng, seq::SliceRandom, Rng, SeedableRng};
+
+    use super::{crossover, Cut};
+
+    fn problem(rng: &mut SmallRng) -> Problem {
+        testing::problem(
+            (0..12).map(|i| i % 3).collect(),
+            (0..30)
+                .map(|i| {
+                    let t = i as f64 / 30. * std::f64::consts::TAU;
+                    let tastes = (0..3).map(|_| rng.gen_range(-1000.0..1000.0)).collect();
+                    attendee(150. + 120. * t.cos(), 150. + 120. * t.sin(), tastes)
+                })
+                .collect(),
+        )
+    }
+
+    fn random_solution(problem: &Problem, rng: &mut SmallRng) -> Solution {
+        let stage = problem.stage.inflate(-10., -10.);
+        let mut ps = square_lattice(stage, 10.);
+        ps.shuffle(rng);
+        Solution {
+            problem_id: 0,
+            solver: "test".to_owned(),
+            placements: ps[..problem.musicians.len()]
+                .iter()
+                .map(|p| Placement { position: *p })
+                .collect(),
+            volumes: vec![10.; problem.musicians.len()],
+        }
+    }
+
+    fn score(problem: &Problem, s: &Solution) -> f64 {
+        let mut board = Board::new(0, problem.clone(), "test", false);
+        for (m, p) in s.placements.iter().enumerate() {
+            board.set_volume(m, 10.);
+            board.try_place(m, p.position).unwrap();
+        }
+        board.score_ignore_negative()
+    }
+
+    #[test]
+    fn test_crossover_is_legal() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let problem = problem(&mut rng);
+        for _ in 0..20 {
+            let a = random_solution(&problem, &mut rng);
+            let b = random_solution(&problem, &mut rng);
+            let cut = Cut::random(problem.stage, &mut rng);
+
+            let child = crossover(0, &problem, &a, &b, &cut).unwrap();
+            assert!(child.musicians().iter().all(|p| p.is_some()));
+            assert!(child.solution().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_crossover_with_whole_stage() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let problem = problem(&mut rng);
+        let a = random_solution(&problem, &mut rng);
+        let b = random_solution(&problem, &mut rng);
+
+        let cut = Cut::HalfPlane {
+            origin: Point2D::new(0., 0.),
+            normal: Vector2D::new(1., 1.),
+        };
+        let child = crossover(0, &problem, &a, &b, &cut).unwrap();
+
+        // All the positions come from a, with the instruments optimally reassigned.
+        let mut ps = child
+            .solution()
+            .unwrap()
+            .placements
+            .iter()
+            .map(|p| (p.position.x, p.position.y))
+            .collect::<Vec<_>>();
+        let mut qs = a
+            .placements
+            .iter()
+            .map(|p| (p.position.x, p.position.y))
+            .collect::<Vec<_>>();
+        ps.sort_by(|p, q| p.partial_cmp(q).unwrap());
+        qs.sort_by(|p, q| p.partial_cmp(q).unwrap());
+        assert_eq!(ps, qs);
+        assert!(child.score_ignore_negative() >= score(&problem, &a));
+    }
+}
@@ -0,0 +1,193 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Result};
+use clap::Parser;
+use common::{
+    board::Board,
+    evaluate,
+    finetune::{finetune, FinetuneOptions},
+    Problem, RawSolution, Solution,
+};
+use crossover_solver::crossover::{crossover, Cut, SOLVER_NAME};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+// Memetic search over saved solutions: children are made by crossover of two members of
+// the population, finetuned along the cut, and replace the worst member when better.
+#[derive(Parser, Debug)]
+struct Args {
+    #[arg(short, long)]
+    problem_id: u32,
+    /// directory of the solutions to start from
+    #[arg(long, default_value = "results")]
+    solutions_dir: PathBuf,
+    /// time limit in seconds
+    #[arg(long, default_value_t = 60.0)]
+    time_limit: f64,
+    /// number of solutions kept
+    #[arg(long, default_value_t = 16)]
+    population: usize,
+    /// max passes of the finetuning of each child, 0 to disable
+    #[arg(long, default_value_t = 2)]
+    finetune_passes: usize,
+    /// musicians closer than this to the cut are finetuned
+    #[arg(long, default_value_t = 20.0)]
+    finetune_radius: f64,
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+// Board of the solution with all the volumes at 10, so that score_ignore_negative() is
+// the score with the optimized volumes.
+fn board_of(problem_id: u32, problem: &Problem, solution: &Solution) -> Result<Board> {
+    if solution.placements.len() != problem.musicians.len() {
+        bail!("wrong number of musicians");
+    }
+    let mut board = Board::new(problem_id, problem.clone(), SOLVER_NAME, false);
+    for (m, p) in solution.placements.iter().enumerate() {
+        board.set_volume(m, 10.);
+        board.try_place(m, p.position)?;
+    }
+    Ok(board)
+}
+
+fn load_population(args: &Args, problem: &Problem) -> Result<Vec<(f64, Solution)>> {
+    let mut res = vec![];
+    for entry in std::fs::read_dir(&args.solutions_dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|e| e != "json") {
+            continue;
+        }
+        let Ok(raw) = RawSolution::from_json(&std::fs::read_to_string(&path)?) else {
+            continue;
+        };
+        if raw.problem_id != args.problem_id {
+            continue;
+        }
+        match board_of(args.problem_id, problem, &Solution::from(raw)) {
+            Ok(board) => res.push((board.score_ignore_negative(), board.solution()?)),
+            Err(e) => eprintln!("skipping {}: {}", path.display(), e),
+        }
+    }
+
+    res.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+    res.dedup_by(|a, b| a.0 == b.0);
+    res.truncate(args.population);
+    Ok(res)
+}
+
+// Index of the best of two random members.
+fn tournament(population: &[(f64, Solution)], rng: &mut impl Rng) -> usize {
+    let i = rng.gen_range(0..population.len());
+    let j = rng.gen_range(0..population.len());
+    if population[i].0 >= population[j].0 {
+        i
+    } else {
+        j
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let f = PathBuf::from(format!("../problems/{}.json", args.problem_id));
+    if !f.is_file() {
+        return Err(anyhow!("File not found: {}", f.display()));
+    }
+    let problem: Problem = Problem::read_from_file(f)?;
+
+    let mut population = load_population(&args, &problem)?;
+    if population.len() < 2 {
+        bail!(
+            "{} distinct solutions of problem {} found in {}, at least 2 are needed",
+            population.len(),
+            args.problem_id,
+            args.solutions_dir.display()
+        );
+    }
+    let initial_score = population[0].0;
+    eprintln!(
+        "population: {} solutions, best {}, worst {}",
+        population.len(),
+        initial_score,
+        population.last().unwrap().0
+    );
+
+    let mut rng = SmallRng::seed_from_u64(args.seed.unwrap_or_else(|| rand::thread_rng().gen()));
+    let start = std::time::Instant::now();
+    let mut best = initial_score;
+    let (mut children, mut accepted) = (0, 0);
+    while start.elapsed().as_secs_f64() < args.time_limit {
+        let i = tournament(&population, &mut rng);
+        let j = tournament(&population, &mut rng);
+        if i == j {
+            continue;
+        }
+        let cut = Cut::random(problem.stage, &mut rng);
+        children += 1;
+
+        let Ok(mut board) = crossover(
+            args.problem_id,
+            &problem,
+            &population[i].1,
+            &population[j].1,
+            &cut,
+        ) else {
+            continue;
+        };
+        if args.finetune_passes > 0 {
+            let near_cut = board
+                .musicians()
+                .iter()
+                .enumerate()
+                .filter(|(_, p)| {
+                    p.is_some_and(|(p, _)| cut.distance(p.to_point()) < args.finetune_radius)
+                })
+                .map(|(m, _)| m)
+                .collect::<Vec<_>>();
+            finetune(
+                &mut board,
+                &FinetuneOptions::default()
+                    .with_max_passes(args.finetune_passes)
+                    .with_musicians(near_cut),
+            );
+        }
+
+        let score = board.score_ignore_negative();
+        let worst = population.len() - 1;
+        if score <= population[worst].0 || population.iter().any(|(s, _)| *s == score) {
+            continue;
+        }
+        population[worst] = (score, board.solution()?);
+        population.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+        accepted += 1;
+
+        if score > best {
+            best = score;
+            eprintln!(
+                "{:.1}s: new best {} ({}/{} children accepted)",
+                start.elapsed().as_secs_f64(),
+                best,
+                accepted,
+                children
+            );
+        }
+    }
+    eprintln!("{} children, {} accepted", children, accepted);
+
+    let board = board_of(args.problem_id, &problem, &population[0].1)?;
+    let sol = board.solution_with_optimized_volume()?;
+    let score = evaluate(&problem, &sol);
+    eprintln!(
+        "final score: {} (diff = {}/{:.5}%)",
+        score,
+        score - initial_score,
+        (score - initial_score) / initial_score.abs() * 100.,
+    );
+
+    if !std::path::Path::new("results").is_dir() {
+        std::fs::create_dir_all("results")?;
+    }
+    let output = PathBuf::from(format!("results/{}-{}.json", args.problem_id, score));
+    Solution::write_to_file(output, sol)?;
+
+    Ok(())
+}
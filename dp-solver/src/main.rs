@@ -10,6 +10,7 @@ fn main(
     problem_id: u32,
     #[opt(short, long, default_value = "")] out: String,
     #[opt(short, long)] submit_must: bool,
+    #[opt(short, long)] closeness: bool,
 ) -> Result<()> {
     let cl = Client::new();
     let userboard = cl.get_userboard()?;
@@ -20,7 +21,7 @@ fn main(
 
     let problem = cl.get_problem(problem_id)?;
 
-    let (_score, board) = solve(problem_id, problem.clone());
+    let (_score, board) = solve(problem_id, problem.clone(), closeness);
 
     let solution: Solution = board.solution_with_optimized_volume().unwrap();
 
@@ -1,55 +1,154 @@
-use common::{board::Board, geom::tangent_circle2, Attendee, Problem};
+use common::{
+    board::Board,
+    geom::tangent_circle2,
+    layout::{open_edges, Edge},
+    transform::Transform,
+    Attendee, Pillar, Problem,
+};
+use euclid::default::Box2D;
 use lyon_geom::{LineSegment, Vector};
 
 const SOLVER_NAME: &str = "dp-solver";
 
 const D: usize = 2;
 
-pub fn solve(problem_id: u32, problem: Problem) -> (f64, Board) {
-    if problem.stage.min.x > 0. || problem.stage.min.y > 0. {
-        panic!("stage.min should be (0, 0)");
-    }
+// Larger conflict groups, e.g. on narrow stages where the rows of the opposite edges
+// overlap, are resolved greedily instead of enumerating the subsets.
+const MAX_EXACT_CONFLICTS: usize = 12;
 
+// closeness adds the approximated closeness bonus on v2 problems.
+pub fn solve(problem_id: u32, problem: Problem, closeness: bool) -> (f64, Board) {
+    let closeness = closeness && problem.is_v2();
     let mul_problem = problem.multiplied(D as f64);
 
-    let mul_problem_flipped = mul_problem.flipped();
+    // Solve the edges independently, each in the frame where it is the right edge.
+    let mut ps = vec![];
+    for edge in open_edges(&problem) {
+        let frame = frame(edge, mul_problem.stage);
+        let back = frame.inverse();
+        let qs = Dp::new(frame.problem(&mul_problem))
+            .with_closeness(closeness)
+            .solve();
+        ps.extend(
+            qs.into_iter()
+                .map(|q| back.point(q.to_point()).to_vector() / D as f64),
+        );
+    }
 
-    let p1 = Dp::new(mul_problem).solve();
-    let mut p2 = Dp::new(mul_problem_flipped).solve();
+    // Points of the adjacent edges may conflict around the corners. Each group of
+    // conflicting points is resolved by removing a minimal set of them, choosing the
+    // sets of the groups one by one with the others fixed.
+    let groups = conflict_groups(&ps)
+        .into_iter()
+        .map(|g| minimal_removals(&ps, &g))
+        .collect::<Vec<_>>();
+
+    let evaluate = |choice: &[usize]| {
+        let removed = groups
+            .iter()
+            .zip(choice.iter())
+            .flat_map(|(g, c)| g[*c].iter().copied())
+            .collect::<Vec<_>>();
+        let outer = ps
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !removed.contains(i))
+            .map(|(_, p)| p.to_point())
+            .collect::<Vec<_>>();
+
+        let mut hs = hungarian_solver::solver::Solver::new(
+            problem_id,
+            problem.clone(),
+            hungarian_solver::solver::Algorithm::Normal,
+        );
+        hs.solve_with_positions(&outer)
+    };
+
+    let mut choice = vec![0; groups.len()];
+    let mut best = evaluate(&choice);
+    for g in 0..groups.len() {
+        for c in 1..groups[g].len() {
+            let prev = choice[g];
+            choice[g] = c;
+            let (score, board) = evaluate(&choice);
+            if score > best.0 {
+                best = (score, board);
+            } else {
+                choice[g] = prev;
+            }
+        }
+    }
 
-    p2.iter_mut().for_each(|p| *p = P::new(p.y, p.x));
+    let (score, mut board) = best;
 
-    let mut ps = p1;
-    ps.append(&mut p2);
+    board.solver = SOLVER_NAME.to_string();
 
-    ps.iter_mut().for_each(|p| {
-        p.x /= D as f64;
-        p.y /= D as f64;
-    });
+    (score, board)
+}
 
-    let mut conflicts = vec![];
+// Maps the problem so that the edge of the stage becomes the right edge of a stage
+// whose min is (0, 0), which is what Dp works on.
+fn frame(edge: Edge, stage: Box2D<f64>) -> Transform {
+    let (w, h) = (stage.width(), stage.height());
+    let t = Transform::translate(-stage.min.to_vector());
+    match edge {
+        Edge::Right => t,
+        Edge::Left => t
+            .then(&Transform::mirror_x())
+            .then(&Transform::translate(Vector::new(w, 0.))),
+        Edge::Top => t.then(&Transform::swap_xy()),
+        Edge::Bottom => t
+            .then(&Transform::swap_xy())
+            .then(&Transform::mirror_x())
+            .then(&Transform::translate(Vector::new(h, 0.))),
+    }
+}
 
-    for i in 0..ps.len() {
-        for j in 0..ps.len() {
-            if i == j {
-                continue;
-            }
-            if (ps[i] - ps[j]).square_length() < 100. {
-                conflicts.push(i);
-                break;
+fn conflicts(p: P, q: P) -> bool {
+    (p - q).square_length() < 100.
+}
+
+// Connected components of the points conflicting with another point.
+fn conflict_groups(ps: &[P]) -> Vec<Vec<usize>> {
+    let mut group = vec![usize::MAX; ps.len()];
+    let mut groups = vec![];
+    for s in 0..ps.len() {
+        if group[s] != usize::MAX || !(0..ps.len()).any(|j| j != s && conflicts(ps[s], ps[j])) {
+            continue;
+        }
+        let mut g = vec![s];
+        group[s] = groups.len();
+        let mut k = 0;
+        while k < g.len() {
+            let i = g[k];
+            for j in 0..ps.len() {
+                if group[j] == usize::MAX && conflicts(ps[i], ps[j]) {
+                    group[j] = groups.len();
+                    g.push(j);
+                }
             }
+            k += 1;
         }
+        groups.push(g);
+    }
+    groups
+}
+
+// Minimal subsets of the group whose removal resolves all the conflicts in it.
+fn minimal_removals(ps: &[P], group: &[usize]) -> Vec<Vec<usize>> {
+    let n = group.len();
+    if n > MAX_EXACT_CONFLICTS {
+        return vec![greedy_removal(ps, group)];
     }
 
     let mut masks = vec![];
-    for mask in 0..1 << conflicts.len() {
+    for mask in 0..1usize << n {
         let mut ok = true;
-        for i in 0..conflicts.len() {
+        for i in 0..n {
             for j in 0..i {
-                if mask & 1 << i == 0 && mask & 1 << j == 0 {
-                    if (ps[conflicts[i]] - ps[conflicts[j]]).square_length() < 100. {
-                        ok = false;
-                    }
+                if mask & 1 << i == 0 && mask & 1 << j == 0 && conflicts(ps[group[i]], ps[group[j]])
+                {
+                    ok = false;
                 }
             }
         }
@@ -58,55 +157,43 @@ pub fn solve(problem_id: u32, problem: Problem) -> (f64, Board) {
         }
     }
 
-    let mut minimal_masks = vec![];
-    for m1 in masks.iter() {
-        let mut is_min = true;
-        for m2 in masks.iter() {
-            if m1 != m2 && m1 & m2 == *m2 {
-                is_min = false;
-                break;
-            }
-        }
-        if is_min {
-            minimal_masks.push(m1);
-        }
-    }
-
-    let mut best: Option<(f64, Board)> = None;
-
-    for mask in minimal_masks {
-        let mut outer = vec![];
-
-        for (i, p) in ps.iter().enumerate() {
-            let mut masked = false;
-            for j in 0..conflicts.len() {
-                if mask & 1 << j != 0 && i == conflicts[j] {
-                    masked = true;
-                }
-            }
-            if !masked {
-                outer.push(*p);
-            }
-        }
-
-        let mut hs = hungarian_solver::solver::Solver::new(
-            problem_id,
-            problem.clone(),
-            hungarian_solver::solver::Algorithm::Normal,
-        );
-        let (score, board) =
-            hs.solve_with_positions(&outer.into_iter().map(|p| p.to_point()).collect());
+    masks
+        .iter()
+        .filter(|m1| !masks.iter().any(|m2| m1 != &m2 && *m1 & m2 == *m2))
+        .map(|mask| {
+            (0..n)
+                .filter(|i| mask & 1 << i != 0)
+                .map(|i| group[i])
+                .collect()
+        })
+        .collect()
+}
 
-        if best.is_none() || score > best.as_ref().unwrap().0 {
-            best = Some((score, board));
+// Removes the point with the most conflicts until none remain.
+fn greedy_removal(ps: &[P], group: &[usize]) -> Vec<usize> {
+    let mut rest = group.to_vec();
+    let mut removed = vec![];
+    loop {
+        let count = |i: usize| {
+            rest.iter()
+                .filter(|j| **j != i && conflicts(ps[i], ps[**j]))
+                .count()
+        };
+        let Some((k, c)) = rest
+            .iter()
+            .enumerate()
+            .map(|(k, i)| (k, count(*i)))
+            .max_by_key(|(_, c)| *c)
+        else {
+            break;
+        };
+        if c == 0 {
+            break;
         }
+        removed.push(rest.swap_remove(k));
     }
-
-    let (score, mut board) = best.unwrap();
-
-    board.solver = SOLVER_NAME.to_string();
-
-    (score, board)
+    removed.sort();
+    removed
 }
 
 pub struct Dp {
@@ -122,6 +209,11 @@ pub struct Dp {
 
     // num attendees (a)
     attendees: Vec<Attendee>,
+    pillars: Vec<Pillar>,
+    // y -> a -> whether the attendee is not hidden by a pillar from y.
+    unhidden: Vec<Vec<bool>>,
+    // Whether to add the closeness bonus of v2 for the adjacent same instruments.
+    closeness: bool,
 
     // y -> i -> d -> y's blocked impact by y + 10 + d.
     blk_pos: Vec<Vec<Vec<i64>>>,
@@ -152,7 +244,8 @@ impl Dp {
             .clone()
             .into_iter()
             .filter(|a| a.position.x >= w)
-            .collect();
+            .collect::<Vec<_>>();
+        let unhidden = vec![vec![true; attendees.len()]; h + 1];
 
         let blk_pos = vec![vec![vec![0; d]; k]; h + 1];
         let blk_neg = vec![vec![vec![0; d]; k]; h + 1];
@@ -168,6 +261,9 @@ impl Dp {
             d,
             r: d as f64 / 2.,
             attendees,
+            pillars: problem.pillars,
+            unhidden,
+            closeness: false,
             blk_pos,
             blk_neg,
             add,
@@ -176,13 +272,27 @@ impl Dp {
         }
     }
 
+    pub fn with_closeness(mut self, closeness: bool) -> Self {
+        self.closeness = closeness;
+        self
+    }
+
     pub fn init(&mut self) {
+        // unhidden
+        for y in 0..=self.h {
+            let p = self.point(y);
+            for (a, attendee) in self.attendees.iter().enumerate() {
+                self.unhidden[y][a] = !self.hidden_by_pillars(attendee, p);
+            }
+        }
         // all
         for y in 0..=self.h {
             let p = self.point(y);
             for i in 0..self.k {
-                for a in self.attendees.iter() {
-                    self.all[y][i] += impact(a, i, p);
+                for (a, attendee) in self.attendees.iter().enumerate() {
+                    if self.unhidden[y][a] {
+                        self.all[y][i] += impact(attendee, i, p);
+                    }
                 }
             }
         }
@@ -198,12 +308,12 @@ impl Dp {
 
                     let q = self.point(y + self.d + d);
 
-                    for a in self.attendees.iter() {
-                        if y > 0 && !is_visible(a, p, q) {
-                            self.blk_pos[y][i][d] += impact(a, i, p);
+                    for (a, attendee) in self.attendees.iter().enumerate() {
+                        if y > 0 && self.unhidden[y][a] && !is_visible(attendee, p, q) {
+                            self.blk_pos[y][i][d] += impact(attendee, i, p);
                         }
-                        if !is_visible(a, q, p) {
-                            self.blk_neg[y + self.d + d][i][d] += impact(a, i, q);
+                        if self.unhidden[y + self.d + d][a] && !is_visible(attendee, q, p) {
+                            self.blk_neg[y + self.d + d][i][d] += impact(attendee, i, q);
                         }
                     }
                 }
@@ -222,7 +332,7 @@ impl Dp {
 
                     let tc = tangent_circle2(p, q, self.r, r).unwrap();
 
-                    if tc.y < self.d as f64 {
+                    if !self.on_stage(tc) {
                         continue;
                     }
 
@@ -237,7 +347,10 @@ impl Dp {
                             continue;
                         }
 
-                        if is_visible(a, tc, p) && is_visible(a, tc, q) {
+                        if is_visible(a, tc, p)
+                            && is_visible(a, tc, q)
+                            && !self.hidden_by_pillars(a, tc)
+                        {
                             v += impact(a, ins, tc);
                         }
                     }
@@ -253,6 +366,37 @@ impl Dp {
         P::new(self.w, y as f64)
     }
 
+    fn hidden_by_pillars(&self, a: &Attendee, p: P) -> bool {
+        let seg = LineSegment {
+            from: p.to_point(),
+            to: a.position,
+        };
+        self.pillars
+            .iter()
+            .any(|pillar| seg.square_distance_to_point(pillar.center) < pillar.radius.powi(2))
+    }
+
+    // Score of putting instrument i on y next to instrument j on y0 = y - 10 - d.
+    fn transition(&self, y0: usize, j: usize, y: usize, i: usize, d: usize) -> i64 {
+        let prev = self.all[y0][j] - self.blk_pos[y0][j][d];
+        let cur = self.all[y][i] - self.blk_neg[y][i][d];
+
+        // Approximation of the closeness factor of v2, which multiplies the impacts of
+        // both by 1 + 1 / distance. Farther pairs are ignored.
+        let bonus = if self.closeness && y0 > 0 && i == j {
+            (prev.max(0) + cur.max(0)) * D as i64 / (self.d + d) as i64
+        } else {
+            0
+        };
+
+        self.dp[y0][j] - self.blk_pos[y0][j][d] + cur + self.add[y0][d] + bonus
+    }
+
+    // Whether a musician can be put on p, which is not beyond the edge.
+    fn on_stage(&self, p: P) -> bool {
+        p.x >= self.d as f64 && p.y >= self.d as f64
+    }
+
     // Returns the points to put the musicians.
     pub fn solve(&mut self) -> Vec<P> {
         println!("Initializing...");
@@ -270,9 +414,7 @@ impl Dp {
                     }
                     let y0 = y - d - self.d;
                     for j in 0..self.k {
-                        let v = self.dp[y0][j] - self.blk_pos[y0][j][d] + self.all[y][i]
-                            - self.blk_neg[y][i][d]
-                            + self.add[y0][d];
+                        let v = self.transition(y0, j, y, i, d);
 
                         self.dp[y][i] = self.dp[y][i].max(v);
                     }
@@ -306,16 +448,14 @@ impl Dp {
                 }
                 let y0 = y - d - self.d;
                 for j in 0..self.k {
-                    let v = self.dp[y0][j] - self.blk_pos[y0][j][d] + self.all[y][i]
-                        - self.blk_neg[y][i][d]
-                        + self.add[y0][d];
+                    let v = self.transition(y0, j, y, i, d);
 
                     if self.dp[y][i] == v {
                         let r = self.r + 1e-6;
 
                         let tc = tangent_circle2(self.point(y0), self.point(y), self.r, r).unwrap();
 
-                        if tc.y >= self.d as f64 {
+                        if self.on_stage(tc) {
                             inner.push(tc);
                         }
 
@@ -353,3 +493,33 @@ fn impact(a: &Attendee, ins: usize, p: P) -> i64 {
 
     impact.ceil() as i64
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{conflicts, minimal_removals, P};
+
+    #[test]
+    fn test_minimal_removals() {
+        let resolves = |ps: &[P], removed: &[usize]| {
+            (0..ps.len()).all(|i| {
+                (0..i).all(|j| {
+                    removed.contains(&i) || removed.contains(&j) || !conflicts(ps[i], ps[j])
+                })
+            })
+        };
+
+        // Two overlapping rows.
+        for n in [4, 40] {
+            let ps = (0..n)
+                .map(|i| P::new((i / 2) as f64 * 10., (i % 2) as f64 * 6.))
+                .collect::<Vec<_>>();
+            let group = (0..n).collect::<Vec<_>>();
+            let res = minimal_removals(&ps, &group);
+            assert!(!res.is_empty());
+            for removed in res.iter() {
+                assert!(resolves(&ps, removed));
+                assert!(removed.len() <= n / 2);
+            }
+        }
+    }
+}
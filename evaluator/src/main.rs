@@ -2,7 +2,13 @@ use std::path::PathBuf;
 
 use anyhow::{bail, Result};
 use clap::Parser;
-use common::{board::Board, evaluate, Problem, RawSolution, Solution};
+use common::{
+    board::Board,
+    evaluate,
+    margin::{apply_margin, MarginOptions},
+    repair::repair,
+    Problem, RawSolution, Solution,
+};
 
 #[derive(Parser, Debug)]
 struct Args {
@@ -10,6 +16,13 @@ struct Args {
     problem_id: u32,
     #[arg(short, long)]
     solution: PathBuf,
+    /// Makes the solution legal by moving the musicians minimally, and writes it here.
+    #[arg(long)]
+    repair: Option<PathBuf>,
+    /// Moves the musicians away from the borderline cases of the rules, and writes the
+    /// solution here.
+    #[arg(long)]
+    margin: Option<PathBuf>,
 }
 
 fn main() -> Result<()> {
@@ -26,11 +39,24 @@ fn main() -> Result<()> {
     if !args.solution.is_file() {
         bail!("File not found:{}", args.solution.display());
     }
-    let solution = Solution::read_from_file(args.solution)?;
+    let mut solution = Solution::read_from_file(args.solution)?;
+
+    if let Some(output) = &args.repair {
+        let (repaired, report) = repair(&problem, &solution)?;
+        println!(
+            "repair: {} moved, {} relocated, max displacement = {}",
+            report.moved, report.relocated, report.max_displacement
+        );
+        if let Some(cost) = report.cost() {
+            println!("repair cost = {}", cost);
+        }
+        Solution::write_to_file(output, repaired.clone())?;
+        solution = repaired;
+    }
 
     println!("score = {}", evaluate(&problem, &solution));
     // Evaluate by board
-    let mut board = Board::new(args.problem_id, problem, "N/A", false);
+    let mut board = Board::new(args.problem_id, problem.clone(), "N/A", false);
     for (i, placement) in solution.placements.iter().enumerate() {
         board.try_place(i, placement.position)?;
     }
@@ -39,5 +65,23 @@ fn main() -> Result<()> {
     }
     println!("score by board = {}", board.score());
 
+    if let Some(output) = &args.margin {
+        let report = apply_margin(&mut board, &MarginOptions::default());
+        println!(
+            "margin: {} borderline cases, {} resolved, {} left, {} musicians nudged",
+            report.found.len(),
+            report.resolved(),
+            report.remaining.len(),
+            report.nudged
+        );
+        println!("margin cost by board = {}", report.cost());
+        let mut margined = solution.clone();
+        for (p, q) in margined.placements.iter_mut().zip(board.musicians()) {
+            p.position = q.unwrap().0.to_point();
+        }
+        println!("score = {}", evaluate(&problem, &margined));
+        Solution::write_to_file(output, margined)?;
+    }
+
     Ok(())
 }
@@ -0,0 +1,44 @@
+use common::{Attendee, Problem};
+use euclid::default::{Box2D, Point2D};
+use rand::{rngs::SmallRng, seq::SliceRandom, Rng, SeedableRng};
+
+// A random v1 problem small enough to be solved exactly: a few musicians of two
+// instruments on a 40x40 stage, and a few attendees around it.
+pub fn tiny_instance(seed: u64) -> Problem {
+    let mut rng = SmallRng::seed_from_u64(seed);
+
+    let room = Box2D::new(Point2D::new(0., 0.), Point2D::new(200., 200.));
+    let min = Point2D::new(
+        rng.gen_range(20..=140) as f64,
+        rng.gen_range(20..=140) as f64,
+    );
+    let stage = Box2D::new(min, min + euclid::vec2(40., 40.));
+
+    // Both instruments appear, as some solvers assume.
+    let mut musicians = vec![0, 1];
+    for _ in 0..rng.gen_range(1..=2) {
+        musicians.push(rng.gen_range(0..2));
+    }
+    musicians.shuffle(&mut rng);
+
+    let num_attendees = rng.gen_range(6..=10);
+    let mut attendees = vec![];
+    while attendees.len() < num_attendees {
+        let position = Point2D::new(rng.gen_range(0.0..200.0), rng.gen_range(0.0..200.0));
+        if stage.inflate(10., 10.).contains(position) {
+            continue;
+        }
+        attendees.push(Attendee {
+            position,
+            tastes: (0..2).map(|_| rng.gen_range(-1000.0..1000.0)).collect(),
+        });
+    }
+
+    Problem {
+        room,
+        stage,
+        musicians,
+        attendees,
+        pillars: vec![],
+    }
+}
@@ -0,0 +1,2 @@
+pub mod instances;
+pub mod solver;
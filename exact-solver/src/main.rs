@@ -0,0 +1,144 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use anyhow::Result;
+use clap::Parser;
+use common::{board::Board, evaluate, layout::square_lattice, Problem, Solution};
+use exact_solver::{instances::tiny_instance, solver::ExactSolver};
+use hungarian_solver::solver::Algorithm;
+
+// Solves random tiny instances exactly, and reports the optimality gaps of the
+// heuristic solvers. The exact solutions are only optimal on the grid, so the gaps can
+// be negative when a solver finds positions off the grid.
+#[derive(Parser, Debug)]
+struct Args {
+    #[arg(long, default_value_t = 10)]
+    instances: u64,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    // Spacing of the grid on the stage.
+    #[arg(long, default_value_t = 2.)]
+    step: f64,
+    // Time limit of the annealing in seconds.
+    #[arg(long, default_value_t = 1.)]
+    time_limit: f64,
+}
+
+const SOLVERS: [&str; 4] = ["greedy", "dp", "hungarian", "annealing"];
+
+fn solve(name: &str, problem: &Problem, time_limit: f64, seed: u64) -> Result<Solution> {
+    let problem = problem.clone();
+    let board = match name {
+        "greedy" => greedy_solver::solver::Solver::new(0, problem).solve().1,
+        "dp" => dp_solver::solver::solve(0, problem, false).1,
+        "hungarian" => {
+            hungarian_solver::solver::Solver::new(0, problem, Algorithm::Normal)
+                .solve(false)
+                .1
+        }
+        "annealing" => {
+            // The random initial placement can get stuck on tiny stages, so it starts from
+            // the musicians packed on a square grid.
+            let mut board = Board::new(0, problem.clone(), "initial", false);
+            let grid = square_lattice(board.prob.stage, 10.);
+            for m in 0..problem.musicians.len() {
+                let Some(p) = grid.get(m) else {
+                    anyhow::bail!("musicians do not fit on the stage")
+                };
+                board.try_place(m, *p)?;
+                board.set_volume(m, 1.);
+            }
+
+            let solver = tanakh_solver::solver::Solver2 {
+                problem_id: 0,
+                problem: problem.clone(),
+                start_temp: None,
+                better_initial: false,
+                initial_solution: Some(board.solution()?),
+                taste: None,
+                use_contribution: false,
+                param: String::new(),
+                use_visibility: false,
+                grid_levels: tanakh_solver::solver::DEFAULT_GRID_LEVELS,
+                constraints: Default::default(),
+            };
+            let res = saru::annealing(
+                &solver,
+                &saru::AnnealingOptions {
+                    time_limit,
+                    limit_temp: 1.0,
+                    restart: 0,
+                    silent: true,
+                    header: String::new(),
+                },
+                seed,
+                1,
+            );
+            let Some(mut solution) = res.solution else {
+                anyhow::bail!("Valid solution not found")
+            };
+            tanakh_solver::solver::post_process(0, &problem, &Default::default(), &mut solution)?;
+            return Ok(solution);
+        }
+        _ => unreachable!(),
+    };
+    board.solution_with_optimized_volume()
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let mut gaps = vec![vec![]; SOLVERS.len()];
+    for i in 0..args.instances {
+        let seed = args.seed + i;
+        let problem = tiny_instance(seed);
+
+        let Some(exact) = ExactSolver::new(0, problem.clone(), args.step).solve() else {
+            eprintln!("instance {}: musicians do not fit on the grid", seed);
+            continue;
+        };
+        eprintln!(
+            "instance {}: {} musicians, exact {} ({} nodes)",
+            seed,
+            problem.musicians.len(),
+            exact.score,
+            exact.nodes
+        );
+
+        for (s, name) in SOLVERS.iter().enumerate() {
+            let res = catch_unwind(AssertUnwindSafe(|| {
+                solve(name, &problem, args.time_limit, seed)
+            }));
+            let score = match res {
+                Ok(Ok(solution)) => evaluate(&problem, &solution),
+                Ok(Err(e)) => {
+                    eprintln!("  {:<10} failed: {}", name, e);
+                    continue;
+                }
+                Err(_) => {
+                    eprintln!("  {:<10} panicked", name);
+                    continue;
+                }
+            };
+            let gap = if exact.score > 0. {
+                (exact.score - score) / exact.score * 100.
+            } else {
+                0.
+            };
+            eprintln!("  {:<10} {} (gap {:.2}%)", name, score, gap);
+            gaps[s].push(gap);
+        }
+    }
+
+    println!("solver      mean gap  max gap  solved");
+    for (name, gaps) in SOLVERS.iter().zip(gaps.iter()) {
+        if gaps.is_empty() {
+            println!("{:<10}         -        -  0", name);
+            continue;
+        }
+        let mean = gaps.iter().sum::<f64>() / gaps.len() as f64;
+        let max = gaps.iter().fold(f64::MIN, |a, b| a.max(*b));
+        println!("{:<10} {:>8.2}% {:>7.2}%  {}", name, mean, max, gaps.len());
+    }
+
+    Ok(())
+}
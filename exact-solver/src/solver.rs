@@ -0,0 +1,271 @@
+use common::{board::Board, layout::square_lattice, Problem, Solution};
+use euclid::default::Point2D;
+
+pub const SOLVER_NAME: &str = "exact-solver";
+
+type Point = Point2D<f64>;
+
+#[derive(Debug, Clone)]
+pub struct ExactResult {
+    // Score with the optimized volumes, which is what evaluate() returns.
+    pub score: f64,
+    pub solution: Solution,
+    // Number of the search nodes visited.
+    pub nodes: usize,
+    // False if the search was cut by the node limit.
+    pub optimal: bool,
+}
+
+// Branch-and-bound over the placements of all the musicians on the points of a square
+// grid on the stage. Only for v1 problems, where placing a musician only blocks the others.
+// Blocking can still increase the score by hiding negative impacts, so the bound is
+// taken on a copy of the problem without the negative tastes: the current score of the
+// copy plus the best scores of the remaining musicians placed alone on it.
+pub struct ExactSolver {
+    problem: Problem,
+    board: Board,
+    // Board of the problem with the negative tastes replaced by 0.
+    pos_board: Board,
+    points: Vec<Point>,
+    max_nodes: Option<usize>,
+
+    // Musicians in the order they are placed, larger bounds first. Musicians of the same
+    // instrument are next to each other, and take the points in the increasing order of
+    // the indices in cands to break the symmetry.
+    order: Vec<usize>,
+    // instrument -> indices of points, better ones first
+    cands: Vec<Vec<usize>>,
+    // i -> sum of the bounds of order[i..]
+    rest_bound: Vec<f64>,
+
+    nodes: usize,
+    best: Option<(f64, Vec<Point>)>,
+    cur: Vec<usize>,
+}
+
+impl ExactSolver {
+    // Points are spaced step apart.
+    pub fn new(problem_id: u32, problem: Problem, step: f64) -> Self {
+        assert!(
+            !problem.is_v2(),
+            "the bound does not hold with the closeness"
+        );
+
+        let mut pos_problem = problem.clone();
+        for a in pos_problem.attendees.iter_mut() {
+            for t in a.tastes.iter_mut() {
+                *t = t.max(0.);
+            }
+        }
+
+        let mut board = Board::new(problem_id, problem.clone(), SOLVER_NAME, false);
+        let mut pos_board = Board::new(problem_id, pos_problem, SOLVER_NAME, false);
+        // With the max volumes, score_ignore_negative() equals the score of the optimized
+        // volumes.
+        for m in 0..board.prob.musicians.len() {
+            board.set_volume(m, 10.);
+            pos_board.set_volume(m, 10.);
+        }
+        let points = square_lattice(board.prob.stage, step);
+
+        Self {
+            problem,
+            board,
+            pos_board,
+            points,
+            max_nodes: None,
+            order: vec![],
+            cands: vec![],
+            rest_bound: vec![],
+            nodes: 0,
+            best: None,
+            cur: vec![],
+        }
+    }
+
+    pub fn with_max_nodes(mut self, max_nodes: usize) -> Self {
+        self.max_nodes = Some(max_nodes);
+        self
+    }
+
+    fn place(&mut self, m: usize, p: Point) {
+        self.board.try_place(m, p).unwrap();
+        self.pos_board.try_place(m, p).unwrap();
+    }
+
+    fn unplace(&mut self, m: usize) {
+        self.board.unplace(m);
+        self.pos_board.unplace(m);
+    }
+
+    // Scores of the instruments alone on each point.
+    fn single_scores(board: &mut Board, points: &[Point]) -> Vec<Vec<f64>> {
+        let k = board.prob.attendees[0].tastes.len();
+        let mut res = vec![vec![0.; points.len()]; k];
+        for (ins, scores) in res.iter_mut().enumerate() {
+            let Some(m) = board.prob.musicians.iter().position(|i| *i == ins) else {
+                continue;
+            };
+            for (p, point) in points.iter().enumerate() {
+                board.try_place(m, *point).unwrap();
+                scores[p] = board.score_ignore_negative();
+                board.unplace(m);
+            }
+        }
+        res
+    }
+
+    // Returns None if the musicians do not fit on the grid.
+    pub fn solve(&mut self) -> Option<ExactResult> {
+        let singles = Self::single_scores(&mut self.board, &self.points);
+        let pos_singles = Self::single_scores(&mut self.pos_board, &self.points);
+
+        self.cands = singles
+            .iter()
+            .map(|s| {
+                let mut ps = (0..self.points.len()).collect::<Vec<_>>();
+                ps.sort_by(|a, b| s[*b].partial_cmp(&s[*a]).unwrap());
+                ps
+            })
+            .collect();
+
+        let bound = |ins: usize| pos_singles[ins].iter().fold(0f64, |a, b| a.max(*b));
+        let musicians = &self.board.prob.musicians;
+        self.order = (0..musicians.len()).collect();
+        self.order.sort_by(|a, b| {
+            let (ia, ib) = (musicians[*a], musicians[*b]);
+            bound(ib)
+                .partial_cmp(&bound(ia))
+                .unwrap()
+                .then(ia.cmp(&ib))
+                .then(a.cmp(b))
+        });
+
+        self.rest_bound = vec![0.; self.order.len() + 1];
+        for i in (0..self.order.len()).rev() {
+            self.rest_bound[i] = self.rest_bound[i + 1] + bound(musicians[self.order[i]]);
+        }
+
+        self.nodes = 0;
+        self.best = None;
+        self.cur = vec![];
+        self.search(0);
+
+        let optimal = self.max_nodes.is_none_or(|limit| self.nodes <= limit);
+        let (_, positions) = self.best.clone()?;
+
+        for (i, m) in self.order.clone().into_iter().enumerate() {
+            self.place(m, positions[i]);
+        }
+        let solution = self.board.solution_with_optimized_volume().unwrap();
+        for m in self.order.clone() {
+            self.unplace(m);
+        }
+
+        Some(ExactResult {
+            score: common::evaluate(&self.problem, &solution),
+            solution,
+            nodes: self.nodes,
+            optimal,
+        })
+    }
+
+    fn search(&mut self, i: usize) {
+        self.nodes += 1;
+        if self.max_nodes.is_some_and(|limit| self.nodes > limit) {
+            return;
+        }
+
+        let score = self.board.score_ignore_negative();
+        if i == self.order.len() {
+            if self.best.as_ref().is_none_or(|(best, _)| score > *best) {
+                let ps = self.cur.iter().map(|p| self.points[*p]).collect();
+                self.best = Some((score, ps));
+            }
+            return;
+        }
+        if let Some((best, _)) = &self.best {
+            if self.pos_board.score_ignore_negative() + self.rest_bound[i] <= *best {
+                return;
+            }
+        }
+
+        let m = self.order[i];
+        let ins = self.board.prob.musicians[m];
+        // The rank in cands of the point taken by the previous musician of the same
+        // instrument.
+        let from = if i > 0 && self.board.prob.musicians[self.order[i - 1]] == ins {
+            let prev = self.cur[i - 1];
+            self.cands[ins].iter().position(|p| *p == prev).unwrap() + 1
+        } else {
+            0
+        };
+
+        for r in from..self.cands[ins].len() {
+            let p = self.cands[ins][r];
+            if !self.board.can_place(m, self.points[p]) {
+                continue;
+            }
+            self.place(m, self.points[p]);
+            self.cur.push(p);
+            self.search(i + 1);
+            self.cur.pop();
+            self.unplace(m);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::board::Board;
+
+    use crate::instances::tiny_instance;
+
+    use super::{ExactSolver, Point};
+
+    fn brute_force(board: &mut Board, points: &[Point], m: usize) -> f64 {
+        if m == board.prob.musicians.len() {
+            return board.score_ignore_negative();
+        }
+        let mut best = f64::MIN;
+        for p in points.iter() {
+            if board.try_place(m, *p).is_ok() {
+                best = best.max(brute_force(board, points, m + 1));
+                board.unplace(m);
+            }
+        }
+        best
+    }
+
+    #[test]
+    fn test_exact_solver() {
+        for seed in 0..3 {
+            let mut problem = tiny_instance(seed);
+            problem.musicians.truncate(3);
+
+            let mut solver = ExactSolver::new(0, problem.clone(), 5.);
+            let res = solver.solve().unwrap();
+            assert!(res.optimal);
+
+            let mut board = Board::new(0, problem.clone(), "test", false);
+            for m in 0..problem.musicians.len() {
+                board.set_volume(m, 10.);
+            }
+            let points = solver.points.clone();
+            assert_eq!(res.score, brute_force(&mut board, &points, 0));
+            assert_eq!(res.score, common::evaluate(&problem, &res.solution));
+        }
+    }
+
+    #[test]
+    fn test_max_nodes() {
+        let problem = tiny_instance(1);
+        let full = ExactSolver::new(0, problem.clone(), 5.).solve().unwrap();
+        let cut = ExactSolver::new(0, problem, 5.)
+            .with_max_nodes(10)
+            .solve()
+            .unwrap();
+        assert!(!cut.optimal);
+        assert!(cut.score <= full.score);
+    }
+}
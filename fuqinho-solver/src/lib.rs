@@ -2,6 +2,7 @@ pub mod sa;
 
 use bitset_fixed::BitSet;
 use common::board::Board;
+use common::layout::{open_edges, EdgeBand};
 use common::{evaluate, Placement, Problem, Solution};
 use euclid::default::Point2D;
 use rand::rngs::ThreadRng;
@@ -81,64 +82,20 @@ impl State {
     }
 }
 
-pub fn generate_line_points(start_x: f64, end_x: f64, y: f64, vertical: bool) -> Vec<CandidatePos> {
-    let mut result = vec![];
-    let mut x = start_x;
-    while x >= start_x.min(end_x) && x <= start_x.max(end_x) {
-        result.push(CandidatePos {
-            pos: if vertical {
-                Point2D::new(y, x)
-            } else {
-                Point2D::new(x, y)
-            },
-            score: 0.0,
-        });
-        if start_x <= end_x {
-            x += 10.0;
-        } else {
-            x -= 10.0;
-        }
-    }
-    result
-}
-
 pub fn generate_grid_points(problem: &Problem) -> Vec<CandidatePos> {
-    let mut result = vec![];
-    for iteration in 0..5 {
-        let min_x = problem.stage.min.x + 10.0 + 10.0 * iteration as f64;
-        let max_x = problem.stage.max.x - 10.0 - 10.0 * iteration as f64;
-        let min_y = problem.stage.min.y + 10.0 + 10.0 * iteration as f64;
-        let max_y = problem.stage.max.y - 10.0 - 10.0 * iteration as f64;
-        if problem.stage.min.y != 0.0 {
-            let sx = min_x;
-            let ex = max_x - 10.0;
-            let y = min_y;
-            result.append(&mut generate_line_points(sx, ex, y, false));
-        }
-        if problem.stage.max.x != problem.room.max.x {
-            let sy = min_y;
-            let ey = max_y - 10.0;
-            let x = max_x;
-            result.append(&mut generate_line_points(sy, ey, x, true));
-        }
-        if problem.stage.max.y != problem.room.max.y {
-            let sx = max_x;
-            let ex = min_x + 10.0;
-            let y = max_y;
-            result.append(&mut generate_line_points(sx, ex, y, false));
-        }
-        if problem.stage.min.x != 0.0 {
-            let sy = max_y;
-            let ey = min_y + 10.0;
-            let x = min_x;
-            result.append(&mut generate_line_points(sy, ey, x, true));
-        }
-        if iteration >= 3 && result.len() > problem.musicians.len() * 5 / 4 {
-            break;
-        }
+    let area = problem.stage.inflate(-10.0, -10.0);
+    let edges = open_edges(problem);
+
+    // 4 rows along the open edges if they have enough room, otherwise 5.
+    let mut points = EdgeBand::square(4).points(area, &edges);
+    if points.len() <= problem.musicians.len() * 5 / 4 {
+        points = EdgeBand::square(5).points(area, &edges);
     }
 
-    result
+    points
+        .into_iter()
+        .map(|pos| CandidatePos { pos, score: 0.0 })
+        .collect()
 }
 
 pub fn solve_one(problem: &Problem, problem_id: usize) -> Solution {
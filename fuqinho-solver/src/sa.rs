@@ -1,6 +1,6 @@
 use common::board::Board;
 use common::{Problem, RawSolution, Solution};
-use euclid::default::{Point2D, Vector2D};
+use euclid::default::Point2D;
 use rand::rngs::ThreadRng;
 use rand::{thread_rng, Rng};
 use std::path::PathBuf;
@@ -109,23 +109,9 @@ fn move_at_random_pos(board: &mut Board, m: usize, rng: &mut ThreadRng) -> bool
     true
 }
 
-fn gradient(board: &Board, m: usize) -> Vector2D<f64> {
-    let mut gradient = Vector2D::new(0., 0.);
-    let m_pos = board.musicians()[m].unwrap().0;
-    for a in 0..board.prob.attendees.len() {
-        if board.is_musician_seeing(m, a) {
-            let a_pos = board.prob.attendees[a].position;
-            let dist2 = (a_pos.x - m_pos.x).powi(2) + (a_pos.y - m_pos.y).powi(2);
-            let dq = (a_pos - m_pos).to_vector() * 2. / dist2.powi(2);
-            gradient += dq * 1000000. * board.prob.attendees[a].tastes[board.prob.musicians[m]];
-        }
-    }
-    gradient * board.q(m)
-}
-
 fn move_at_gradient_direction(board: &mut Board, m: usize, rng: &mut ThreadRng) -> bool {
     let m_pos = board.musicians()[m].unwrap().0;
-    let gradient = gradient(board, m);
+    let gradient = board.gradient(m);
     if gradient.square_length() < 0.0001 {
         return false;
     }
@@ -0,0 +1,8 @@
+#![allow(
+    dead_code,
+    unused_imports,
+    unused_variables,
+    clippy::needless_range_loop
+)]
+
+pub mod solver;
@@ -1,9 +1,10 @@
 use anyhow::Context;
 use common::{
     board::Board,
-    geom::{
-        circles_tangenting_line_and_circle, circles_tangenting_lines, new_line, tangent_circle,
+    candidates::{
+        pillar_tangent_candidates, shadow_gap_candidates, stage_segments, tangent_candidates,
     },
+    potential::PotentialMap,
     Problem,
 };
 use lyon_geom::{LineSegment, Point, Vector};
@@ -13,6 +14,8 @@ pub struct Solver {
     num_attendees: usize,   // num attendees (a)
     num_instruments: usize, // num instruments (i)
 
+    // Impacts ignoring blocking by the musicians, sampled on the edges of the stage.
+    potential: PotentialMap,
     // i -> maximal points sorted by score. (smaller first)
     maximal_points: Vec<Vec<(f64, P)>>,
 
@@ -29,7 +32,8 @@ const D: usize = 10;
 const KEEP_IMPORTANT_SEGS: usize = 100;
 const USE_IMPORTANT_SEGS: usize = 50;
 
-const EPS: f64 = 1e-9;
+// Number of attendees whose views between the pillars are used for the candidates.
+const SHADOW_ATTENDEES: usize = 10;
 
 const R: f64 = 5.0;
 
@@ -37,6 +41,12 @@ const INITIAL_SEARCH_STEP: f64 = 2.0;
 
 impl Solver {
     pub fn new(problem_id: u32, problem: Problem) -> Self {
+        // The maximal points are on the edges, where the optimal positions usually are.
+        // The coarse grid is only the corners of the stage.
+        let stage = problem.stage;
+        let potential = PotentialMap::new(&problem, stage.width().max(stage.height()))
+            .with_edge_refinement(&problem, INITIAL_SEARCH_STEP, 0.);
+
         let board = Board::new(problem_id, problem, "greedy-solver", false);
 
         let num_musicians = board.prob.musicians.len();
@@ -49,6 +59,7 @@ impl Solver {
             num_musicians,
             num_attendees,
             num_instruments,
+            potential,
             maximal_points,
             important_segs: vec![],
             board,
@@ -59,74 +70,16 @@ impl Solver {
         &self.board.prob
     }
 
-    fn top(&self) -> f64 {
-        self.board.prob.stage.max.y
-    }
-
-    fn bottom(&self) -> f64 {
-        self.board.prob.stage.min.y
-    }
-
-    fn right(&self) -> f64 {
-        self.board.prob.stage.max.x
-    }
-
-    fn left(&self) -> f64 {
-        self.board.prob.stage.min.x
-    }
-
-    fn is_on_stage(&self, p: P) -> bool {
-        p.x >= self.left() && p.x <= self.right() && p.y >= self.bottom() && p.y <= self.top()
-    }
-
-    fn is_narrow(&self) -> bool {
-        self.top() == self.bottom() || self.left() == self.right()
-    }
-
     fn init_maximal_points(&mut self) {
         let mut maximal_points = vec![vec![]; self.num_instruments];
 
         for ins in 0..self.num_instruments {
-            eprintln!("initializing max point: {} / {}", ins, self.num_instruments);
-
-            let m = self.board.available_musician_with_instrument(ins).unwrap();
-
-            let delta = INITIAL_SEARCH_STEP;
-
-            for (dx, dy, x, y) in [
-                (delta, 0., self.left(), self.bottom()),
-                (delta, 0., self.left(), self.top()),
-                (0., delta, self.left(), self.bottom()),
-                (0., delta, self.right(), self.bottom()),
-            ] {
-                let mut graph = vec![];
-
-                let mut p = P::new(x, y);
-
-                while self.is_on_stage(p) {
-                    let score = self
-                        .board
-                        .score_increase_if_put_musician_on(m, p.to_point())
-                        .unwrap();
-
-                    graph.push((score, p));
-
-                    p.x += dx;
-                    p.y += dy;
-                }
-
-                assert!(!graph.is_empty());
-
-                for i in 0..graph.len() {
-                    if i > 0 && graph[i].0 < graph[i - 1].0 {
-                        continue;
-                    }
-                    if i + 1 < graph.len() && graph[i].0 <= graph[i + 1].0 {
-                        continue;
-                    }
-                    maximal_points[ins].push(graph[i]);
-                }
-            }
+            maximal_points[ins] = self
+                .potential
+                .top_k(ins, usize::MAX)
+                .into_iter()
+                .map(|(score, p)| (score, P::new(p.x, p.y)))
+                .collect();
 
             eprintln!(
                 "number of maximal points for instrument {}: {}",
@@ -141,20 +94,9 @@ impl Solver {
     }
 
     fn init_important_segs(&mut self) {
-        let left = self.left() - R;
-        let right = self.right() + R;
-        let bottom = self.bottom() - R;
-        let top = self.top() + R;
-
-        let p00 = P::new(left, bottom);
-        let p01 = P::new(left, top);
-        let p10 = P::new(right, bottom);
-        let p11 = P::new(right, top);
-
-        self.important_segs.push((f64::INFINITY, p00, p01));
-        self.important_segs.push((f64::INFINITY, p00, p10));
-        self.important_segs.push((f64::INFINITY, p01, p11));
-        self.important_segs.push((f64::INFINITY, p10, p11));
+        for (p0, p1) in stage_segments(&self.board) {
+            self.important_segs.push((f64::INFINITY, p0, p1));
+        }
     }
 
     pub fn solve(&mut self) -> (f64, Board) {
@@ -246,65 +188,27 @@ impl Solver {
             self.maximal_points[ins].pop();
         }
 
-        // Tangent to two important segs
-        let r = if self.is_narrow() { R } else { R + EPS };
-
-        for i in 0..self.important_segs.len().min(USE_IMPORTANT_SEGS) {
-            for j in 0..i {
-                let (_, p0, p1) = self.important_segs[i];
-                let (_, q0, q1) = self.important_segs[j];
-
-                for c in circles_tangenting_lines(p0, p1, q0, q1, r) {
-                    if self.can_place(m, c) {
-                        res.push(c);
-                    }
-                }
-            }
-        }
-
-        // Tangent to an important seg and a circle
-        for i in 0..self.important_segs.len().min(USE_IMPORTANT_SEGS) {
-            let (_, p0, p1) = self.important_segs[i];
-
-            for mc in self.board.musicians() {
-                if let Some((mc, _)) = mc {
-                    for c in circles_tangenting_line_and_circle(p0, p1, *mc, R, r) {
-                        if self.can_place(m, c) {
-                            res.push(c);
-                        }
-                    }
-                }
-            }
-        }
-
-        // Tangent to two circles
-        for mc1 in self.board.musicians() {
-            if let Some((mc1, _)) = mc1 {
-                for mc2 in self.board.musicians() {
-                    if let Some((mc2, _)) = mc2 {
-                        if mc1 == mc2 {
-                            continue;
-                        }
-
-                        let c = tangent_circle(*mc1, *mc2, r);
-
-                        if let Some(c) = c {
-                            if self.can_place(m, c) {
-                                res.push(c);
-                            }
-                        }
-                    }
-                }
-            }
-        }
+        let segs = self
+            .important_segs
+            .iter()
+            .take(USE_IMPORTANT_SEGS)
+            .map(|(_, p0, p1)| (*p0, *p1))
+            .collect::<Vec<_>>();
+        let circles = self
+            .board
+            .musicians()
+            .iter()
+            .flatten()
+            .map(|(mc, _)| *mc)
+            .collect::<Vec<_>>();
+
+        res.extend(tangent_candidates(&self.board, m, &segs, &circles));
+        res.extend(pillar_tangent_candidates(&self.board, m, &segs, &circles));
+        res.extend(shadow_gap_candidates(&self.board, m, SHADOW_ATTENDEES));
 
         res
     }
 
-    fn can_place(&self, m: usize, p: P) -> bool {
-        self.is_on_stage(p) && self.board.can_place(m, p.to_point())
-    }
-
     fn place(&mut self, m: usize, ins: usize, p: P) {
         self.board
             .try_place(m, p.to_point())
@@ -1,18 +1,13 @@
-use std::{
-    cmp::Ordering,
-    collections::{BTreeSet, HashSet},
-    fmt::Display,
-    str::FromStr,
-};
+use std::{collections::BTreeSet, fmt::Display, str::FromStr};
 
 use anyhow::{bail, Context};
 use common::{
     api::{get_best_solution, Client},
     board::Board,
-    geom::tangent_circle,
+    layout::{open_edges, square_lattice, EdgeBand},
     Problem, Solution,
 };
-use lyon_geom::{Point, Vector};
+use lyon_geom::Point;
 use pathfinding::prelude::{kuhn_munkres, Matrix};
 use rand::{thread_rng, Rng};
 
@@ -172,31 +167,14 @@ impl Solver {
             }
         }
 
+        // Fill the rest inside the outer band.
         let bb = self.board.prob.stage;
-
-        for x in ((bb.min.x.ceil() as usize)..(bb.max.x.floor() as usize - D)).step_by(D) {
-            if to_place.is_empty() {
+        for p in square_lattice(bb.inflate(-(D as f64), -(D as f64)), D as f64) {
+            let Some(i) = to_place.first() else {
                 break;
-            }
-
-            for y in ((bb.min.y).ceil() as usize..bb.max.y.floor() as usize - D).step_by(D) {
-                if to_place.is_empty() {
-                    break;
-                }
-
-                if x == bb.min.x.ceil() as usize || y == bb.min.y.ceil() as usize {
-                    continue;
-                }
-
-                let i = to_place.first().unwrap();
-
-                if self
-                    .board
-                    .try_place(*i, Point::new(x as f64, y as f64))
-                    .is_ok()
-                {
-                    to_place.pop_first().unwrap();
-                }
+            };
+            if self.board.try_place(*i, p).is_ok() {
+                to_place.pop_first().unwrap();
             }
         }
 
@@ -207,151 +185,16 @@ impl Solver {
     }
 
     fn compute_outer(&mut self, algo: Algorithm) -> Vec<P> {
-        let mut outer = vec![];
-
         let bb = self.board.prob.stage;
+        let edges = open_edges(&self.orig_problem);
 
         match algo {
-            Algorithm::Normal => {
-                for x in ((bb.min.x.ceil() as usize)..(bb.max.x.floor() as usize)).step_by(D) {
-                    if bb.min.y > D as f64 {
-                        outer.push(Point::new(x as f64, bb.min.y));
-                    }
-                    outer.push(Point::new(x as f64, bb.max.y));
-                }
-
-                for y in ((bb.min.y).ceil() as usize + D..bb.max.y.floor() as usize - D).step_by(D)
-                {
-                    if bb.min.x > D as f64 {
-                        outer.push(Point::new(bb.min.x, y as f64));
-                    }
-                    outer.push(Point::new(bb.max.x, y as f64));
-                }
-            }
-            Algorithm::ZigZag => {
-                let mut queue = vec![];
-                let mut visited = HashSet::<Point<i64>>::new();
-
-                let mul = 1_000_000i64;
-                let sqrt2_5 = 7_071_068i64;
-
-                let init = Point::new(bb.min.x.ceil() as i64 * mul, bb.min.y.ceil() as i64 * mul);
-                queue.push(init);
-                visited.insert(init);
-
-                let eps = Vector::new(1e-9, 1e-9);
-                let mut bb_outer = bb;
-                bb_outer.max += eps;
-
-                let mut bb_inner = bb;
-                bb_inner.min +=
-                    Vector::new(sqrt2_5 as f64 / mul as f64, sqrt2_5 as f64 / mul as f64) + eps;
-                bb_inner.max -=
-                    Vector::new(sqrt2_5 as f64 / mul as f64, sqrt2_5 as f64 / mul as f64) * 2.
-                        + eps;
-
-                while let Some(p) = queue.pop() {
-                    for dx in [-1, 1] {
-                        for dy in [-1, 1] {
-                            let np = p + Vector::new(dx * sqrt2_5, dy * sqrt2_5);
-
-                            if visited.contains(&np) {
-                                continue;
-                            }
-
-                            let real_np = np.to_f64() / mul as f64;
-
-                            if bb_outer.contains(real_np) && !bb_inner.contains(real_np) {
-                                queue.push(np);
-                                visited.insert(np);
-                            }
-                        }
-                    }
-                }
-
-                outer = visited
-                    .into_iter()
-                    .map(|p| p.to_f64() / mul as f64)
-                    .collect();
-
-                outer.sort_by(|x, y| {
-                    let o = x.x.partial_cmp(&y.x).unwrap();
-                    if o == Ordering::Equal {
-                        x.y.partial_cmp(&y.y).unwrap()
-                    } else {
-                        o
-                    }
-                });
-            }
-            Algorithm::Gap => {
-                for x in ((bb.min.x.ceil() as usize)..(bb.max.x.floor() as usize)).step_by(D) {
-                    if bb.min.y > D as f64 {
-                        outer.push(Point::new(x as f64, bb.min.y));
-                    }
-                    outer.push(Point::new(x as f64, bb.max.y));
-                }
-
-                for y in ((bb.min.y).ceil() as usize + D..bb.max.y.floor() as usize - D).step_by(D)
-                {
-                    if bb.min.x > D as f64 {
-                        outer.push(Point::new(bb.min.x, y as f64));
-                    }
-                    outer.push(Point::new(bb.max.x, y as f64));
-                }
-
-                let mut min_x: f64 = 1e9;
-                let mut min_y: f64 = 1e9;
-                let mut max_x: f64 = -1e9;
-                let mut max_y: f64 = -1e9;
-
-                for p in outer.iter() {
-                    min_x = min_x.min(p.x);
-                    min_y = min_y.min(p.y);
-                    max_x = max_x.max(p.x);
-                    max_y = max_y.max(p.y);
-                }
-
-                let outmost = outer.clone();
-
-                for o1 in outmost.iter() {
-                    for o2 in outmost.iter() {
-                        if o1 == o2 {
-                            continue;
-                        }
-
-                        if o1.x != o2.x && o1.y != o2.y {
-                            continue;
-                        }
-
-                        if (*o1 - o2.to_vector()).to_vector().square_length() <= (D * D) as f64 {
-                            let c = tangent_circle(
-                                o1.to_vector(),
-                                o2.to_vector(),
-                                D as f64 / 2. + 1e-9,
-                            )
-                            .unwrap();
-
-                            if self.board.prob.stage.contains(c.to_point()) {
-                                // check collision
-                                let mut ok = true;
-                                for p in outer.iter() {
-                                    if (*p - c).to_vector().square_length() < (D * D) as f64 {
-                                        ok = false;
-                                        break;
-                                    }
-                                }
-
-                                if ok {
-                                    outer.push(c.to_point());
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+            Algorithm::Normal => EdgeBand::square(1).points(bb, &edges),
+            Algorithm::ZigZag => EdgeBand::zigzag(2).points(bb, &edges),
+            Algorithm::Gap => EdgeBand::hex(2).points(bb, &edges),
             Algorithm::Stdin => {
                 let solution = Solution::read_from_file("/dev/stdin").unwrap();
-                outer = solution.placements.iter().map(|p| p.position).collect();
+                solution.placements.iter().map(|p| p.position).collect()
             }
             Algorithm::FetchBest => {
                 let solution = get_best_solution(self.board.problem_id).unwrap();
@@ -360,34 +203,9 @@ impl Solver {
                     .solver
                     .push_str(format!("-{}", solution.solver).as_str());
 
-                outer = solution.placements.iter().map(|p| p.position).collect();
+                solution.placements.iter().map(|p| p.position).collect()
             }
         }
-
-        if algo == Algorithm::Gap || algo == Algorithm::Normal || algo == Algorithm::ZigZag {
-            let mut max_x: f64 = 0.;
-            let mut max_y: f64 = 0.;
-            for p in outer.iter() {
-                max_x = max_x.max(p.x);
-                max_y = max_y.max(p.y);
-            }
-
-            let bb = self.board.prob.stage;
-
-            for p in outer.iter_mut() {
-                p.x += bb.max.x - max_x;
-                p.y += bb.max.y - max_y;
-
-                if p.x > bb.max.x {
-                    p.x = bb.max.x
-                }
-                if p.y > bb.max.y {
-                    p.y = bb.max.y
-                }
-            }
-        }
-
-        outer
     }
 
     fn post_process(&mut self) {
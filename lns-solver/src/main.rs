@@ -0,0 +1,105 @@
+mod solver;
+
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use common::{evaluate, repair::repair, Problem, RawSolution, Solution};
+use rand::Rng;
+
+use crate::solver::{Params, Solver};
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[arg(short, long)]
+    problem_id: u32,
+    /// solution to start from
+    #[arg(long)]
+    initial_solution: PathBuf,
+    /// time limit in seconds
+    #[arg(long, default_value_t = 60.0)]
+    time_limit: f64,
+    /// number of threads
+    #[arg(long, default_value_t = 1)]
+    threads: usize,
+    /// number of musicians removed at once
+    #[arg(short, long, default_value_t = 8)]
+    k: usize,
+    /// 1 for the greedy re-insertion
+    #[arg(long, default_value_t = 3)]
+    beam_width: usize,
+    /// how far from the removed musicians they can be re-inserted
+    #[arg(long, default_value_t = 30.0)]
+    radius: f64,
+    #[arg(long)]
+    start_temp: Option<f64>,
+    #[arg(long, default_value_t = 1.0)]
+    limit_temp: f64,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let f = PathBuf::from(format!("../problems/{}.json", args.problem_id));
+    if !f.is_file() {
+        return Err(anyhow!("File not found: {}", f.display()));
+    }
+    let problem: Problem = Problem::read_from_file(f)?;
+
+    let s = std::fs::read_to_string(&args.initial_solution)?;
+    let initial_solution = Solution::from(RawSolution::from_json(&s)?);
+    let (initial_solution, report) = repair(&problem, &initial_solution)?;
+    if report.moved + report.relocated > 0 {
+        eprintln!(
+            "repaired the initial solution: {} moved, {} relocated, max displacement = {}",
+            report.moved, report.relocated, report.max_displacement
+        );
+    }
+    let initial_score = evaluate(&problem, &initial_solution);
+    eprintln!("initial score: {}", initial_score);
+
+    let solver = Solver {
+        problem_id: args.problem_id,
+        problem: problem.clone(),
+        initial_solution,
+        params: Params {
+            k: args.k,
+            beam_width: args.beam_width,
+            radius: args.radius,
+            start_temp: args.start_temp,
+        },
+    };
+
+    let result = saru::annealing(
+        &solver,
+        &saru::AnnealingOptions {
+            time_limit: args.time_limit,
+            limit_temp: args.limit_temp,
+            restart: 0,
+            silent: false,
+            header: format!("{}: ", args.problem_id),
+        },
+        rand::thread_rng().gen(),
+        args.threads,
+    );
+
+    let Some(mut sol) = result.solution else {
+        anyhow::bail!("Valid solution not found")
+    };
+    sol.problem_id = args.problem_id;
+
+    let score = evaluate(&problem, &sol);
+    eprintln!(
+        "final score: {} (diff = {}/{:.5}%)",
+        score,
+        score - initial_score,
+        (score - initial_score) / initial_score.abs() * 100.,
+    );
+
+    if !std::path::Path::new("results").is_dir() {
+        std::fs::create_dir_all("results")?;
+    }
+    let output = PathBuf::from(format!("results/{}-{}.json", args.problem_id, score));
+    Solution::write_to_file(output, sol)?;
+
+    Ok(())
+}
@@ -0,0 +1,278 @@
+use common::{
+    board::Board,
+    candidates::{important_segments, stage_segments, tangent_candidates},
+    Problem, Solution,
+};
+use euclid::default::Point2D;
+use lyon_geom::Vector;
+use rand::{seq::SliceRandom, Rng};
+
+pub const SOLVER_NAME: &str = "lns-solver";
+
+type P = Vector<f64>;
+
+// Number of the segments to the attendees used for the candidates.
+const USE_IMPORTANT_SEGS: usize = 10;
+
+// Attempts to find a repairable destroy before neighbour() gives up with an empty move.
+const MAX_TRIALS: usize = 100;
+
+#[derive(Debug, Clone)]
+pub struct Params {
+    // Number of musicians removed by a move.
+    pub k: usize,
+    // Number of partial placements kept while re-inserting. 1 is the plain greedy.
+    pub beam_width: usize,
+    // Candidates farther than this from all the removed positions are ignored.
+    pub radius: f64,
+    pub start_temp: Option<f64>,
+}
+
+// Destroy-and-repair on top of saru. A move removes k musicians chosen by region, by
+// instrument or at random, re-inserts them one by one on the tangent-circle candidates
+// around the removed positions by beam search, and exchanges their positions by the
+// hungarian algorithm.
+pub struct Solver {
+    pub problem_id: u32,
+    pub problem: Problem,
+    pub initial_solution: Solution,
+    pub params: Params,
+}
+
+pub struct State {
+    board: Board,
+}
+
+impl saru::State for State {
+    type Solution = Solution;
+
+    fn solution(&self) -> Self::Solution {
+        self.board.solution_with_optimized_volume().unwrap()
+    }
+}
+
+pub struct Move {
+    ms: Vec<usize>,
+    old: Vec<Point2D<f64>>,
+    new: Vec<Point2D<f64>>,
+}
+
+fn set_positions(board: &mut Board, ms: &[usize], ps: &[Point2D<f64>]) {
+    for m in ms.iter() {
+        board.unplace(*m);
+    }
+    for (m, p) in ms.iter().zip(ps.iter()) {
+        board.try_place(*m, *p).unwrap();
+    }
+}
+
+impl Solver {
+    fn destroy(&self, board: &Board, rng: &mut impl Rng) -> Vec<usize> {
+        let n = board.prob.musicians.len();
+        let k = self.params.k.min(n);
+
+        let mut ms = (0..n).collect::<Vec<_>>();
+        match rng.gen_range(0..3) {
+            // The nearest ones from a random musician
+            0 => {
+                let (c, _) = board.musicians()[rng.gen_range(0..n)].unwrap();
+                let d = |m: &usize| (board.musicians()[*m].unwrap().0 - c).square_length();
+                ms.sort_by(|a, b| d(a).partial_cmp(&d(b)).unwrap());
+            }
+            // Musicians with the same instrument
+            1 => {
+                let ins = board.prob.musicians[rng.gen_range(0..n)];
+                ms.retain(|m| board.prob.musicians[*m] == ins);
+                ms.shuffle(rng);
+            }
+            _ => {
+                ms.shuffle(rng);
+            }
+        }
+        ms.truncate(k);
+
+        // Larger contributions are re-inserted first.
+        ms.sort_by(|a, b| {
+            board
+                .contribution2(*b)
+                .partial_cmp(&board.contribution2(*a))
+                .unwrap()
+        });
+        ms
+    }
+
+    fn candidates(&self, board: &Board, m: usize, old: &[Point2D<f64>]) -> Vec<P> {
+        let near = |p: P| {
+            old.iter()
+                .any(|q| (p.to_point() - *q).length() <= self.params.radius)
+        };
+
+        let neighbors = (0..board.prob.musicians.len())
+            .filter(|m2| board.musicians()[*m2].is_some_and(|(p, _)| near(p)))
+            .collect::<Vec<_>>();
+        let circles = neighbors
+            .iter()
+            .map(|m2| board.musicians()[*m2].unwrap().0)
+            .collect::<Vec<_>>();
+
+        let mut segs = stage_segments(board);
+        segs.extend(important_segments(board, &neighbors, USE_IMPORTANT_SEGS));
+
+        let mut res = tangent_candidates(board, m, &segs, &circles);
+        res.retain(|p| near(*p));
+        res.extend(
+            old.iter()
+                .filter(|p| board.can_place(m, **p))
+                .map(|p| p.to_vector()),
+        );
+        res
+    }
+
+    // Re-inserts the unplaced musicians ms by beam search, and returns the best positions
+    // found, or None if some musician has no place to go.
+    fn repair(
+        &self,
+        board: &mut Board,
+        ms: &[usize],
+        old: &[Point2D<f64>],
+    ) -> Option<Vec<Point2D<f64>>> {
+        let mut beam = vec![(board.score(), vec![])];
+
+        for (i, m) in ms.iter().enumerate() {
+            let mut next = vec![];
+            for (_, ps) in beam.iter() {
+                for (m2, p) in ms.iter().zip(ps.iter()) {
+                    board.try_place(*m2, *p).unwrap();
+                }
+
+                let mut scored = vec![];
+                for c in self.candidates(board, *m, old) {
+                    board.try_place(*m, c.to_point()).unwrap();
+                    scored.push((board.score(), c.to_point()));
+                    board.unplace(*m);
+                }
+                scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+                scored.truncate(self.params.beam_width);
+
+                for (score, c) in scored {
+                    let mut ps = ps.clone();
+                    ps.push(c);
+                    next.push((score, ps));
+                }
+
+                for m2 in ms[..i].iter() {
+                    board.unplace(*m2);
+                }
+            }
+
+            if next.is_empty() {
+                return None;
+            }
+            next.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+            next.truncate(self.params.beam_width);
+            beam = next;
+        }
+
+        beam.into_iter().next().map(|(_, ps)| ps)
+    }
+}
+
+impl saru::StateInitializer for Solver {
+    type State = State;
+
+    fn init_state(&self, _rng: &mut impl Rng) -> Self::State {
+        let mut board = Board::new(self.problem_id, self.problem.clone(), SOLVER_NAME, false);
+
+        // All the volumes are 1 during the search so that score(), which the hungarian
+        // exchange of neighbour() also compares, is the objective throughout. The initial
+        // solution is made legal by main().
+        for (i, p) in self.initial_solution.placements.iter().enumerate() {
+            board.set_volume(i, 1.);
+            board.try_place(i, p.position).unwrap();
+        }
+
+        State { board }
+    }
+}
+
+impl saru::Annealer for Solver {
+    type State = State;
+
+    type Move = Move;
+
+    fn start_temp(&self, _init_score: f64) -> f64 {
+        // Scores are in millions, so this practically keeps only the improving moves.
+        self.params.start_temp.unwrap_or(1.)
+    }
+
+    fn eval(
+        &self,
+        state: &Self::State,
+        _progress_ratio: f64,
+        _best_score: f64,
+        _valid_best_score: f64,
+    ) -> (f64, Option<f64>) {
+        let score = -state.board.score();
+        (score, Some(score))
+    }
+
+    fn neighbour(
+        &self,
+        state: &mut Self::State,
+        rng: &mut impl Rng,
+        _progress_ratio: f64,
+    ) -> Self::Move {
+        let board = &mut state.board;
+        for _ in 0..MAX_TRIALS {
+            let ms = self.destroy(board, rng);
+            let old = ms
+                .iter()
+                .map(|m| board.musicians()[*m].unwrap().0.to_point())
+                .collect::<Vec<_>>();
+
+            for m in ms.iter() {
+                board.unplace(*m);
+            }
+            let new = self.repair(board, &ms, &old).map(|new| {
+                for (m, p) in ms.iter().zip(new.iter()) {
+                    board.try_place(*m, *p).unwrap();
+                }
+                board.hungarian_subset(&ms);
+                ms.iter()
+                    .map(|m| board.musicians()[*m].unwrap().0.to_point())
+                    .collect::<Vec<_>>()
+            });
+
+            // Restore the state. The move is applied by apply().
+            let placed = ms
+                .iter()
+                .filter(|m| board.musicians()[**m].is_some())
+                .copied()
+                .collect::<Vec<_>>();
+            for m in placed {
+                board.unplace(m);
+            }
+            for (m, p) in ms.iter().zip(old.iter()) {
+                board.try_place(*m, *p).unwrap();
+            }
+
+            if let Some(new) = new {
+                return Move { ms, old, new };
+            }
+        }
+
+        Move {
+            ms: vec![],
+            old: vec![],
+            new: vec![],
+        }
+    }
+
+    fn apply(&self, state: &mut Self::State, mov: &Self::Move) {
+        set_positions(&mut state.board, &mov.ms, &mov.new);
+    }
+
+    fn unapply(&self, state: &mut Self::State, mov: &Self::Move) {
+        set_positions(&mut state.board, &mov.ms, &mov.old);
+    }
+}
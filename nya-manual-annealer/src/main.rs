@@ -3,7 +3,7 @@ use std::sync::mpsc::Receiver;
 use anyhow::Result;
 use common::{api::Client, RawSolution, Solution};
 
-use tanakh_solver::solver::{Solver2, State2};
+use tanakh_solver::solver::{Solver2, State2, DEFAULT_GRID_LEVELS};
 use thousands::Separable;
 
 fn get_best_solution(problem_id: u32) -> Result<Solution> {
@@ -53,7 +53,13 @@ fn main(
     } else {
         format!("{}+nya", &initial_solution.solver)
     };
-    let mut state = State2::new(&initial_solution, &problem, &solver_name, false);
+    let mut state = State2::new(
+        &initial_solution,
+        &problem,
+        &solver_name,
+        false,
+        &Default::default(),
+    )?;
 
     let mut current_temp = 0.0001;
 
@@ -67,6 +73,8 @@ fn main(
         param: String::new(),
         use_visibility: false,
         use_contribution: false,
+        grid_levels: DEFAULT_GRID_LEVELS,
+        constraints: Default::default(),
     };
 
     let mut best_solution = initial_solution;
@@ -117,7 +125,13 @@ fn main(
                     current_temp /= 10.0;
                 }
                 b'r' => {
-                    state = State2::new(&best_solution, &problem, &solver_name, false);
+                    state = State2::new(
+                        &best_solution,
+                        &problem,
+                        &solver_name,
+                        false,
+                        &Default::default(),
+                    )?;
                     estimated_score = best_score;
                 }
                 b'x' => {
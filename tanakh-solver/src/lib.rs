@@ -1,3 +1,5 @@
 #[cfg(not(target_arch = "wasm32"))]
 pub mod api;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod multires;
 pub mod solver;
@@ -1,9 +1,12 @@
 use anyhow::Result;
-use common::{api::Client, RawSolution, Solution};
+use common::{api::Client, constraints::Constraints, RawSolution, Solution};
 use rand::Rng;
 use std::{fs::File, io::Write, path::PathBuf};
 
-use tanakh_solver::solver::{post_process, pre_process, Solver2};
+use tanakh_solver::{
+    multires::{default_levels, multires_annealing},
+    solver::{post_process, pre_process, Solver2, DEFAULT_GRID_LEVELS},
+};
 
 fn get_best_solution(problem_id: u32) -> Result<Solution> {
     let url = format!(
@@ -81,6 +84,16 @@ fn main(
     problem_id: u32,
     // Use blur
     #[opt(long)] use_visibility: bool,
+    /// anneal coarse to fine with the default levels
+    #[opt(long)]
+    multires: bool,
+    /// levels of the coarse to fine annealing in json (implies --multires)
+    #[opt(long)]
+    levels: Option<PathBuf>,
+    /// pinned musicians and regions in json (default: the .constraints.json of the
+    /// initial solution if any)
+    #[opt(long)]
+    constraints: Option<PathBuf>,
 ) -> Result<()> {
     let client = Client::new();
 
@@ -107,6 +120,19 @@ fn main(
         }
     );
 
+    let constraints_path = constraints.or_else(|| {
+        let path = Constraints::sidecar_of(initial_solution.as_ref()?);
+        path.is_file().then_some(path)
+    });
+    let constraints = match constraints_path {
+        Some(path) => {
+            eprintln!("Constraints:      {}", path.display());
+            Constraints::read_from_file(path)?
+        }
+        None => Constraints::default(),
+    };
+    constraints.validate(&problem)?;
+
     let initial_solution: Option<Solution> = if let Some(path) = initial_solution {
         let s = std::fs::read_to_string(path)?;
         let raw_solution: RawSolution = serde_json::from_str(&s)?;
@@ -133,26 +159,50 @@ fn main(
         use_contribution,
         param,
         use_visibility,
+        grid_levels: DEFAULT_GRID_LEVELS,
+        constraints: constraints.clone(),
     };
 
-    let solution = saru::annealing(
-        &solver,
-        &saru::AnnealingOptions {
-            time_limit,
-            limit_temp,
-            restart: 0,
-            silent: false,
-            header: format!("{problem_id}: "),
-        },
-        rand::thread_rng().gen(),
-        threads,
-    );
+    let options = saru::AnnealingOptions {
+        time_limit,
+        limit_temp,
+        restart: 0,
+        silent: false,
+        header: format!("{problem_id}: "),
+    };
+
+    let solution = if multires || levels.is_some() {
+        let levels = match levels {
+            Some(path) => serde_json::from_str(&std::fs::read_to_string(path)?)?,
+            None => default_levels(),
+        };
+        let (solution, reports) = multires_annealing(
+            &solver,
+            &levels,
+            &options,
+            rand::thread_rng().gen(),
+            threads,
+        );
+        for (i, r) in reports.iter().enumerate() {
+            eprintln!(
+                "Level {i}: {} attendees, {:.1}s, {} -> {} ({:+})",
+                r.attendees,
+                r.time,
+                r.score_before,
+                r.score_after,
+                r.gain()
+            );
+        }
+        solution
+    } else {
+        saru::annealing(&solver, &options, rand::thread_rng().gen(), threads).solution
+    };
 
-    let Some(mut solution) = solution.solution else {
+    let Some(mut solution) = solution else {
         anyhow::bail!("Valid solution not found")
     };
 
-    post_process(problem_id, &problem, &mut solution);
+    post_process(problem_id, &problem, &constraints, &mut solution)?;
 
     solution.problem_id = problem_id;
 
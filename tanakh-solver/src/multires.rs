@@ -0,0 +1,133 @@
+use common::{attendee_cluster::cluster_attendees, evaluate, Problem, Solution};
+use saru::Annealer;
+use serde::{Deserialize, Serialize};
+
+use crate::solver::Solver2;
+
+// A stage of the coarse-to-fine annealing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Level {
+    // (min_distance, angle) to merge the attendees far from the stage with (see
+    // common::attendee_cluster). None anneals on the exact attendees.
+    pub clustering: Option<(f64, f64)>,
+    // Solver2::grid_levels of this level.
+    pub grid_levels: (i32, i32),
+    // Share of the time limit, relative to the sum over the levels.
+    pub time_ratio: f64,
+    // Multiplied to the start temperature. The levels after the first start from the
+    // solution of the previous one, so they should start cooler.
+    pub temp_scale: f64,
+}
+
+// Anneals on the clustered attendees with coarse moves for most of the time, and
+// polishes on the exact problem at a low temperature for the rest.
+pub fn default_levels() -> Vec<Level> {
+    vec![
+        Level {
+            clustering: Some((30.0, 0.3)),
+            grid_levels: (-2, 4),
+            time_ratio: 0.7,
+            temp_scale: 1.0,
+        },
+        Level {
+            clustering: None,
+            grid_levels: (4, 8),
+            time_ratio: 0.3,
+            temp_scale: 0.001,
+        },
+    ]
+}
+
+#[derive(Debug, Clone)]
+pub struct LevelReport {
+    pub attendees: usize,
+    // Seconds spent on the level.
+    pub time: f64,
+    // Scores on the exact problem of the solutions before and after the level.
+    pub score_before: f64,
+    pub score_after: f64,
+}
+
+impl LevelReport {
+    pub fn gain(&self) -> f64 {
+        self.score_after - self.score_before
+    }
+}
+
+fn reduced_problem(problem: &Problem, level: &Level) -> Problem {
+    let Some((min_distance, angle)) = level.clustering else {
+        return problem.clone();
+    };
+    Problem {
+        attendees: cluster_attendees(&problem.attendees, problem.stage, min_distance, angle)
+            .attendees,
+        ..problem.clone()
+    }
+}
+
+// Anneals solver.problem level by level. Each level starts from the best solution so far
+// on the exact problem, which is what is returned (None if no level found a valid one).
+pub fn multires_annealing(
+    solver: &Solver2,
+    levels: &[Level],
+    options: &saru::AnnealingOptions,
+    seed: u64,
+    threads: usize,
+) -> (Option<Solution>, Vec<LevelReport>) {
+    let total_ratio = levels.iter().map(|l| l.time_ratio).sum::<f64>();
+
+    let mut best = solver.initial_solution.clone();
+    let mut best_score = best.as_ref().map_or(0.0, |s| evaluate(&solver.problem, s));
+
+    let mut reports = vec![];
+    for (i, level) in levels.iter().enumerate() {
+        let problem = reduced_problem(&solver.problem, level);
+        let start_temp = match &best {
+            Some(s) => Some(solver.start_temp(evaluate(&problem, s)) * level.temp_scale),
+            None => solver.start_temp.map(|t| t * level.temp_scale),
+        };
+        let attendees = problem.attendees.len();
+
+        let level_solver = Solver2 {
+            problem,
+            start_temp,
+            initial_solution: best.clone(),
+            grid_levels: level.grid_levels,
+            ..solver.clone()
+        };
+
+        let time_limit = options.time_limit * level.time_ratio / total_ratio;
+        let start = std::time::Instant::now();
+        let res = saru::annealing(
+            &level_solver,
+            &saru::AnnealingOptions {
+                time_limit,
+                limit_temp: options.limit_temp,
+                restart: options.restart,
+                silent: options.silent,
+                header: format!("{}L{} ", options.header, i),
+            },
+            seed.wrapping_add(i as u64),
+            threads,
+        );
+        let time = start.elapsed().as_secs_f64();
+
+        let score_before = best_score;
+        if let Some(s) = res.solution {
+            let score = evaluate(&solver.problem, &s);
+            if best.is_none() || score > best_score {
+                best = Some(s);
+                best_score = score;
+            }
+        }
+
+        reports.push(LevelReport {
+            attendees,
+            time,
+            score_before,
+            score_after: best_score,
+        });
+    }
+
+    (best, reports)
+}
@@ -1,10 +1,26 @@
-use common::{board::Board, Attendee, Pillar, Problem, Solution};
+use anyhow::Result;
+use common::{
+    board::Board,
+    constraints::Constraints,
+    finetune::{finetune, FinetuneOptions},
+    margin::{apply_margin, MarginOptions},
+    repair::{is_legal, repair_with_pinned},
+    Attendee, Pillar, Problem, Solution,
+};
 use euclid::{default::*, point2, vec2};
 use lyon_geom::{LineSegment, Point};
 use rand::Rng;
 
 const SOLVER_NAME: &str = "(´･_･`)v3";
 
+// Moves are on the grid of 1 / 2^8 at the end.
+pub const DEFAULT_GRID_LEVELS: (i32, i32) = (0, 8);
+
+// Attempts to generate a move before giving up, e.g. when the constraints leave no
+// musician to move or no pair to swap.
+const MAX_TRIALS: usize = 1000;
+
+#[derive(Clone)]
 pub struct Solver2 {
     pub problem_id: u32,
     pub problem: common::Problem,
@@ -15,6 +31,11 @@ pub struct Solver2 {
     pub use_contribution: bool,
     pub param: String,
     pub use_visibility: bool,
+    // Musicians move on the grid of 1 / 2^grid_level, where grid_level goes from the first
+    // to the second as the annealing progresses.
+    pub grid_levels: (i32, i32),
+    // Pinned musicians and regions, in the coordinates of problem.
+    pub constraints: Constraints,
 }
 
 pub struct State2 {
@@ -22,8 +43,34 @@ pub struct State2 {
 }
 
 impl State2 {
-    pub fn new(solution: &Solution, problem: &Problem, solver: &str, use_visibility: bool) -> Self {
+    // The pinned musicians of the constraints stay where the solution puts them.
+    pub fn new(
+        solution: &Solution,
+        problem: &Problem,
+        solver: &str,
+        use_visibility: bool,
+        constraints: &Constraints,
+    ) -> Result<Self> {
+        // Hand-edited solutions may be slightly illegal.
+        let repaired;
+        let solution = if is_legal(problem, solution) {
+            solution
+        } else {
+            let (s, report) = repair_with_pinned(problem, solution, &constraints.pinned)?;
+            eprintln!(
+                "Repaired the initial solution: {} moved, {} relocated (max {:.3}), score {:?} -> {}",
+                report.moved,
+                report.relocated,
+                report.max_displacement,
+                report.score_before,
+                report.score_after,
+            );
+            repaired = s;
+            &repaired
+        };
+
         let mut board = Board::new(solution.problem_id, problem.clone(), solver, use_visibility);
+        board.set_constraints(constraints.clone())?;
 
         for (i, v) in solution.volumes.iter().enumerate() {
             board.set_volume(i, *v);
@@ -32,8 +79,29 @@ impl State2 {
             board.try_place(i, p.position).unwrap();
         }
 
+        // They can only move into their regions afterwards.
+        let outside = solution
+            .placements
+            .iter()
+            .enumerate()
+            .filter(|(i, p)| !board.allows(*i, p.position))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        if !outside.is_empty() {
+            eprintln!("Musicians out of their regions in the initial solution: {outside:?}");
+        }
+
+        Ok(Self { board })
+    }
+
+    // Starts from an already built board, e.g. one restored from a snapshot.
+    pub fn from_board(board: Board) -> Self {
         Self { board }
     }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
 }
 
 impl saru::State for State2 {
@@ -71,19 +139,24 @@ impl Move {
         taste: Option<usize>,
         use_contribution: bool,
         progress_ratio: f64,
-    ) -> Self {
+        grid_levels: (i32, i32),
+    ) -> Option<Self> {
         let stage = &board.prob.stage;
 
         let scale_x = (100.0 * (1.0 - progress_ratio)).max(5.0);
         let scale_y = (100.0 * (1.0 - progress_ratio)).max(5.0);
 
-        let grid_level = (progress_ratio * 8.0).floor() as i32;
+        let (lo, hi) = grid_levels;
+        let grid_level = lo + (progress_ratio * (hi - lo) as f64).floor() as i32;
         let grid = 1.0 / 2.0_f64.powi(grid_level);
-        let scale_x = (scale_x / grid).round() as i32;
-        let scale_y = (scale_y / grid).round() as i32;
+        let scale_x = (scale_x / grid).round().max(1.0) as i32;
+        let scale_y = (scale_y / grid).round().max(1.0) as i32;
 
-        'outer: loop {
+        for _ in 0..MAX_TRIALS {
             let id = rng.gen_range(0..board.musicians().len());
+            if board.is_pinned(id) {
+                continue;
+            }
 
             if let Some(taste) = taste {
                 if taste != board.prob.musicians[id] {
@@ -98,15 +171,15 @@ impl Move {
             let old_pos = board.musicians()[id].unwrap().0.to_point();
 
             if use_contribution && board.contribution2(id).abs() < 1e-6 {
-                loop {
+                for _ in 0..MAX_TRIALS {
                     let x = rng.gen_range(board.prob.stage.min.x..=board.prob.stage.max.x);
                     let y = rng.gen_range(board.prob.stage.min.y..=board.prob.stage.max.y);
                     if board.can_place(id, Point::new(x, y)) {
-                        return Move::ChangePos {
+                        return Some(Move::ChangePos {
                             id,
                             new_pos: Point::new(x, y),
                             old_pos,
-                        };
+                        });
                     }
                 }
                 // continue;
@@ -133,24 +206,29 @@ impl Move {
                         continue;
                     }
 
-                    break 'outer Move::ChangePos {
+                    return Some(Move::ChangePos {
                         id,
                         new_pos,
                         old_pos,
-                    };
+                    });
                 }
             }
         }
+        None
     }
 
-    fn gen_swap(rng: &mut impl Rng, board: &Board) -> Self {
-        loop {
+    fn gen_swap(rng: &mut impl Rng, board: &Board) -> Option<Self> {
+        for _ in 0..MAX_TRIALS {
             let i = rng.gen_range(0..board.prob.musicians.len());
             let j = rng.gen_range(0..board.prob.musicians.len());
-            if i != j && board.prob.musicians[i] != board.prob.musicians[j] {
-                break Move::Swap { i, j };
+            if i != j
+                && board.prob.musicians[i] != board.prob.musicians[j]
+                && board.can_exchange(i, j)
+            {
+                return Some(Move::Swap { i, j });
             }
         }
+        None
     }
 
     fn gen_change_volume(rng: &mut impl Rng, board: &Board) -> Self {
@@ -175,13 +253,17 @@ impl saru::StateInitializer for Solver2 {
     fn init_state(&self, rng: &mut impl Rng) -> Self::State {
         let solver_name = format!("{SOLVER_NAME} ({})", self.param);
 
+        // The constraints are validated against the problem when the solver is built.
+
         if let Some(initial_solution) = &self.initial_solution {
             return State2::new(
                 initial_solution,
                 &self.problem,
                 &solver_name,
                 self.use_visibility,
-            );
+                &self.constraints,
+            )
+            .unwrap();
         }
 
         let mut board = Board::new(
@@ -190,6 +272,7 @@ impl saru::StateInitializer for Solver2 {
             &solver_name,
             self.use_visibility,
         );
+        board.set_constraints(self.constraints.clone()).unwrap();
 
         if self.better_initial {
             let g = board.prob.stage.center();
@@ -218,7 +301,9 @@ impl saru::StateInitializer for Solver2 {
                         let y: f64 = rng
                             .gen_range(board.prob.stage.min.y..=board.prob.stage.max.y)
                             .round();
-                        if board.try_place(i, Point::new(x, y)).is_ok() {
+                        if board.can_place(i, Point::new(x, y))
+                            && board.try_place(i, Point::new(x, y)).is_ok()
+                        {
                             let score = board.score();
                             if score > best.0 {
                                 best = (score, Point::new(x, y));
@@ -241,7 +326,9 @@ impl saru::StateInitializer for Solver2 {
                     let y: f64 = rng
                         .gen_range(board.prob.stage.min.y..=board.prob.stage.max.y)
                         .round();
-                    if board.try_place(i, Point::new(x, y)).is_ok() {
+                    if board.can_place(i, Point::new(x, y))
+                        && board.try_place(i, Point::new(x, y)).is_ok()
+                    {
                         break;
                     }
                 }
@@ -282,67 +369,80 @@ impl saru::Annealer for Solver2 {
         rng: &mut impl Rng,
         progress_ratio: f64,
     ) -> Self::Move {
+        // Falls back to the other kinds of moves when the constraints leave nothing to
+        // move or swap. gen_change_volume() always finds one.
         loop {
             match rng.gen_range(0..=5) {
                 0..=2 => {
-                    return Move::gen_change_pos(
+                    if let Some(mov) = Move::gen_change_pos(
                         rng,
                         &state.board,
                         self.taste,
                         self.use_contribution,
                         progress_ratio,
-                    )
+                        self.grid_levels,
+                    ) {
+                        return mov;
+                    }
                 }
 
-                3 => loop {
-                    let m1 = Move::gen_change_pos(
-                        rng,
-                        &state.board,
-                        self.taste,
-                        self.use_contribution,
-                        progress_ratio,
-                    );
-                    let m2 = Move::gen_change_pos(
-                        rng,
-                        &state.board,
-                        self.taste,
-                        self.use_contribution,
-                        progress_ratio,
-                    );
+                3 => {
+                    for _ in 0..MAX_TRIALS {
+                        let Some(m1) = Move::gen_change_pos(
+                            rng,
+                            &state.board,
+                            self.taste,
+                            self.use_contribution,
+                            progress_ratio,
+                            self.grid_levels,
+                        ) else {
+                            break;
+                        };
+                        let Some(m2) = Move::gen_change_pos(
+                            rng,
+                            &state.board,
+                            self.taste,
+                            self.use_contribution,
+                            progress_ratio,
+                            self.grid_levels,
+                        ) else {
+                            break;
+                        };
 
-                    match (&m1, &m2) {
-                        (
-                            Move::ChangePos {
-                                id: id1,
-                                new_pos: new_pos1,
-                                ..
-                            },
-                            Move::ChangePos {
-                                id: id2,
-                                new_pos: new_pos2,
-                                ..
-                            },
-                        ) => {
-                            if id1 == id2 {
-                                continue;
-                            }
-                            if new_pos1.distance_to(*new_pos2) < 10.0 {
-                                continue;
+                        match (&m1, &m2) {
+                            (
+                                Move::ChangePos {
+                                    id: id1,
+                                    new_pos: new_pos1,
+                                    ..
+                                },
+                                Move::ChangePos {
+                                    id: id2,
+                                    new_pos: new_pos2,
+                                    ..
+                                },
+                            ) => {
+                                if id1 == id2 {
+                                    continue;
+                                }
+                                if new_pos1.distance_to(*new_pos2) < 10.0 {
+                                    continue;
+                                }
                             }
+                            _ => unreachable!(),
                         }
-                        _ => unreachable!(),
-                    }
 
-                    return Move::Multiple {
-                        moves: vec![m1, m2],
-                    };
-                },
+                        return Move::Multiple {
+                            moves: vec![m1, m2],
+                        };
+                    }
+                }
 
                 4 => {
                     if self.taste.is_some() {
                         continue;
-                    } else {
-                        return Move::gen_swap(rng, &state.board);
+                    } else if let Some(mov) = Move::gen_swap(rng, &state.board) {
+                        return mov;
                     }
                 }
 
@@ -503,8 +603,14 @@ pub fn pre_process(p: &mut Problem, prune_far: Option<f64>) {
     }
 }
 
-pub fn post_process(problem_id: u32, p: &Problem, s: &mut Solution) {
+pub fn post_process(
+    problem_id: u32,
+    p: &Problem,
+    constraints: &Constraints,
+    s: &mut Solution,
+) -> Result<()> {
     let mut board = Board::new(problem_id, p.clone(), SOLVER_NAME, false);
+    board.set_constraints(constraints.clone())?;
 
     eprintln!("Post processing...");
 
@@ -516,61 +622,35 @@ pub fn post_process(problem_id: u32, p: &Problem, s: &mut Solution) {
     let init_score = board.score();
     let init_score_acc = common::evaluate(p, s);
 
-    for iter in 1.. {
-        let iter_score = board.score();
-
-        let mut changed = false;
-        for i in 0..s.placements.len() {
-            let mut pos = s.placements[i].position;
-            let mut score = board.score();
-
-            loop {
-                let mut local_changed = false;
-
-                for d in [
-                    vec2(1.0, 0.0),
-                    vec2(-1.0, 0.0),
-                    vec2(0.0, 1.0),
-                    vec2(0.0, -1.0),
-                ] {
-                    let new_pos = pos + d;
-                    if board.can_place(i, new_pos) {
-                        board.unplace(i);
-                        board.try_place(i, new_pos).unwrap();
-
-                        let new_score = board.score();
-                        if new_score > score {
-                            score = new_score;
-                            pos = new_pos;
-                            local_changed = true;
-                        } else {
-                            board.unplace(i);
-                            board.try_place(i, pos).unwrap();
-                        }
-                    }
-                }
-
-                if local_changed {
-                    changed = true;
-                } else {
-                    break;
-                }
-            }
-
-            s.placements[i].position = pos;
-        }
-
-        if !changed {
-            break;
-        } else {
+    for r in finetune(&mut board, &FinetuneOptions::default()) {
+        if r.moved > 0 {
             eprintln!(
-                "Post processing iter {iter}: {iter_score} -> {} ({:+.3}%)",
-                board.score(),
-                (board.score() - iter_score) / iter_score * 100.0,
+                "Post processing pass {} (step {}): {} -> {} ({:+.3}%)",
+                r.pass,
+                r.step,
+                r.score_before,
+                r.score_after,
+                r.gain() / r.score_before * 100.0,
             );
         }
     }
 
+    let r = apply_margin(&mut board, &MarginOptions::default());
+    if !r.found.is_empty() {
+        eprintln!(
+            "Safety margin: {} borderline cases, {} resolved, {} left: {} -> {}",
+            r.found.len(),
+            r.resolved(),
+            r.remaining.len(),
+            r.score_before,
+            r.score_after,
+        );
+    }
+
+    for i in 0..s.placements.len() {
+        s.placements[i].position = board.musicians()[i].unwrap().0.to_point();
+    }
+
     let post_score = board.score();
     let post_score_acc = common::evaluate(p, s);
     let final_solution = common::evaluate::fixup_volumes(p, s);
@@ -590,4 +670,122 @@ pub fn post_process(problem_id: u32, p: &Problem, s: &mut Solution) {
         "Post processed score: {init_score_acc} -> {final_score_acc} ({:+.3}%)",
         (final_score_acc - init_score_acc) / init_score * 100.0,
     );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{
+        constraints::{Constraints, Region, RegionRule},
+        testing::{self, attendee},
+        Problem,
+    };
+    use euclid::default::{Box2D, Point2D};
+
+    use super::{Solver2, State2, DEFAULT_GRID_LEVELS};
+
+    fn problem() -> Problem {
+        Problem {
+            room: Box2D::new(Point2D::new(0., 0.), Point2D::new(400., 300.)),
+            stage: Box2D::new(Point2D::new(100., 100.), Point2D::new(300., 200.)),
+            ..testing::problem(
+                vec![0, 1, 0, 1, 0, 1],
+                vec![
+                    attendee(20., 150., vec![-100., 1000.]),
+                    attendee(380., 150., vec![1000., -100.]),
+                ],
+            )
+        }
+    }
+
+    fn solver(constraints: Constraints) -> Solver2 {
+        Solver2 {
+            problem_id: 0,
+            problem: problem(),
+            start_temp: None,
+            better_initial: false,
+            initial_solution: None,
+            taste: None,
+            use_contribution: false,
+            param: String::new(),
+            use_visibility: false,
+            grid_levels: DEFAULT_GRID_LEVELS,
+            constraints,
+        }
+    }
+
+    fn anneal(solver: &Solver2) -> common::Solution {
+        let options = saru::AnnealingOptions {
+            time_limit: 0.2,
+            limit_temp: 1.0,
+            restart: 0,
+            silent: true,
+            header: String::new(),
+        };
+        saru::annealing(solver, &options, 1, 1).solution.unwrap()
+    }
+
+    #[test]
+    fn test_annealing_with_disjoint_regions() {
+        // The attendees like the instruments on the other side, so that no pair of
+        // musicians can be swapped.
+        let rule = |ins, min, max| RegionRule {
+            musicians: vec![],
+            instruments: vec![ins],
+            region: Region::Rect { min, max },
+        };
+        let constraints = Constraints {
+            pinned: vec![],
+            regions: vec![
+                rule(0, (110., 110.), (190., 190.)),
+                rule(1, (210., 110.), (290., 190.)),
+            ],
+        };
+        let solution = anneal(&solver(constraints.clone()));
+        for (m, p) in solution.placements.iter().enumerate() {
+            let rule = &constraints.regions[problem().musicians[m]];
+            assert!(rule.region.contains(p.position), "{} {:?}", m, p.position);
+        }
+
+        // Only the volumes can change.
+        let mut constraints = constraints;
+        constraints.pinned = (0..6).collect();
+        let pinned = anneal(&Solver2 {
+            initial_solution: Some(solution.clone()),
+            ..solver(constraints)
+        });
+        for (a, b) in pinned.placements.iter().zip(solution.placements.iter()) {
+            assert_eq!(a.position, b.position);
+        }
+    }
+
+    #[test]
+    fn test_repair_keeps_pinned() {
+        let constraints = Constraints {
+            pinned: vec![1],
+            regions: vec![],
+        };
+        // 1 overlaps 0 and 2.
+        let ps = [(150., 150.), (155., 150.), (160., 150.), (250., 150.)];
+        let solution = common::Solution {
+            problem_id: 0,
+            solver: "test".to_owned(),
+            placements: ps
+                .iter()
+                .chain([(200., 120.), (200., 180.)].iter())
+                .map(|(x, y)| common::Placement {
+                    position: Point2D::new(*x, *y),
+                })
+                .collect(),
+            volumes: vec![10.; 6],
+        };
+        let state = State2::new(&solution, &problem(), "test", false, &constraints).unwrap();
+        let board = state.board();
+        assert!(board.is_pinned(1));
+        assert_eq!(
+            board.musicians()[1].unwrap().0.to_point(),
+            Point2D::new(155., 150.)
+        );
+        assert!(board.solution().is_ok());
+    }
 }
@@ -0,0 +1,385 @@
+use std::{collections::BTreeMap, fs::File, path::Path};
+
+use anyhow::Result;
+use common::Problem;
+use log::info;
+use rand::{rngs::SmallRng, seq::SliceRandom, Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::Serialize;
+
+// Range of a parameter to search.
+#[derive(Debug, Clone)]
+pub struct ParamRange {
+    pub name: &'static str,
+    pub min: f64,
+    pub max: f64,
+    // Searched uniformly in the log space. min has to be positive.
+    pub log: bool,
+    pub integer: bool,
+}
+
+impl ParamRange {
+    pub fn linear(name: &'static str, min: f64, max: f64) -> Self {
+        Self {
+            name,
+            min,
+            max,
+            log: false,
+            integer: false,
+        }
+    }
+
+    pub fn log(name: &'static str, min: f64, max: f64) -> Self {
+        assert!(min > 0., "{}: log range has to be positive", name);
+        Self {
+            log: true,
+            ..Self::linear(name, min, max)
+        }
+    }
+
+    pub fn integer(mut self) -> Self {
+        self.integer = true;
+        self
+    }
+
+    // Maps v in the range to [0, 1].
+    pub fn to_unit(&self, v: f64) -> f64 {
+        let v = v.clamp(self.min, self.max);
+        let u = if self.log {
+            (v / self.min).ln() / (self.max / self.min).ln()
+        } else {
+            (v - self.min) / (self.max - self.min)
+        };
+        if u.is_finite() {
+            u
+        } else {
+            0.
+        }
+    }
+
+    pub fn from_unit(&self, u: f64) -> f64 {
+        let u = u.clamp(0., 1.);
+        let v = if self.log {
+            self.min * (self.max / self.min).powf(u)
+        } else {
+            self.min + (self.max - self.min) * u
+        };
+        if self.integer {
+            v.round()
+        } else {
+            v
+        }
+    }
+}
+
+// Configs of solvers which can be tuned. values() and with_values() take the values of
+// the parameters in the order of ranges().
+pub trait Tunable: Clone + Send + Sync + Serialize {
+    fn ranges() -> Vec<ParamRange>;
+
+    fn values(&self) -> Vec<f64>;
+
+    // A copy with the parameters replaced.
+    fn with_values(&self, values: &[f64]) -> Self;
+}
+
+#[derive(Debug, Clone)]
+pub struct TunerOptions {
+    // Number of the candidates raced in a generation.
+    candidates: usize,
+    generations: usize,
+    // Number of the problems added to the race at once.
+    batch: usize,
+    // Standard deviation of the mutations in the unit space, halved every generation.
+    sigma: f64,
+    seed: u64,
+}
+
+impl Default for TunerOptions {
+    fn default() -> Self {
+        Self {
+            candidates: 8,
+            generations: 3,
+            batch: 2,
+            sigma: 0.2,
+            seed: 0,
+        }
+    }
+}
+
+impl TunerOptions {
+    pub fn with_candidates(mut self, candidates: usize) -> Self {
+        self.candidates = candidates.max(1);
+        self
+    }
+
+    pub fn with_generations(mut self, generations: usize) -> Self {
+        self.generations = generations.max(1);
+        self
+    }
+
+    pub fn with_batch(mut self, batch: usize) -> Self {
+        self.batch = batch.max(1);
+        self
+    }
+
+    pub fn with_sigma(mut self, sigma: f64) -> Self {
+        self.sigma = sigma;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TuneResult<T> {
+    pub params: T,
+    // Average of the ranks in the races, from 0 (the best) to 1 (the worst).
+    pub mean_rank: f64,
+    // Number of the problems the params were evaluated on in the last generation.
+    pub evaluated: usize,
+}
+
+// Racing search over the ranges of T, starting from base.
+// Each generation races the candidates on the problems added batch by batch, dropping
+// the worse half by the mean rank after each batch. The next generation keeps the
+// survivors and samples new candidates around them with a narrower spread. The first
+// generation also samples uniformly from the whole space.
+// eval(params, problem_id) returns the score, and is called in parallel.
+pub fn tune<T: Tunable>(
+    base: &T,
+    problems: &[u32],
+    options: &TunerOptions,
+    eval: impl Fn(&T, u32) -> f64 + Sync,
+) -> TuneResult<T> {
+    assert!(!problems.is_empty());
+
+    let ranges = T::ranges();
+    let to_params = |u: &[f64]| {
+        let values = ranges
+            .iter()
+            .zip(u.iter())
+            .map(|(r, u)| r.from_unit(*u))
+            .collect::<Vec<_>>();
+        base.with_values(&values)
+    };
+
+    let mut rng = SmallRng::seed_from_u64(options.seed);
+    let mut order = problems.to_vec();
+
+    let base_unit = ranges
+        .iter()
+        .zip(base.values())
+        .map(|(r, v)| r.to_unit(v))
+        .collect::<Vec<_>>();
+    let mut survivors = vec![base_unit];
+
+    for generation in 0..options.generations {
+        let sigma = options.sigma / (1 << generation) as f64;
+
+        let mut pool = survivors.clone();
+        while pool.len() < options.candidates {
+            let u = if generation == 0 && rng.gen_bool(0.5) {
+                ranges.iter().map(|_| rng.gen_range(0.0..=1.0)).collect()
+            } else {
+                let parent = survivors.choose(&mut rng).unwrap();
+                parent
+                    .iter()
+                    .map(|u| (u + sigma * gaussian(&mut rng)).clamp(0., 1.))
+                    .collect()
+            };
+            pool.push(u);
+        }
+
+        // Problems are raced in a different order every generation.
+        order.shuffle(&mut rng);
+
+        let mut alive = (0..pool.len()).collect::<Vec<_>>();
+        let mut ranks = vec![vec![]; pool.len()];
+        let mean_rank = |ranks: &Vec<f64>| ranks.iter().sum::<f64>() / ranks.len() as f64;
+
+        let batches = order.chunks(options.batch).collect::<Vec<_>>();
+        for (b, batch) in batches.iter().enumerate() {
+            let jobs = alive
+                .iter()
+                .flat_map(|c| batch.iter().map(move |p| (*c, *p)))
+                .collect::<Vec<_>>();
+            let scores = jobs
+                .par_iter()
+                .map(|(c, p)| eval(&to_params(&pool[*c]), *p))
+                .collect::<Vec<_>>();
+
+            for p in batch.iter() {
+                let mut res = jobs
+                    .iter()
+                    .zip(scores.iter())
+                    .filter(|((_, q), _)| q == p)
+                    .map(|((c, _), s)| (*s, *c))
+                    .collect::<Vec<_>>();
+                res.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+
+                let n = res.len().max(2) - 1;
+                for (i, (_, c)) in res.iter().enumerate() {
+                    ranks[*c].push(i as f64 / n as f64);
+                }
+            }
+
+            alive.sort_by(|a, b| {
+                mean_rank(&ranks[*a])
+                    .partial_cmp(&mean_rank(&ranks[*b]))
+                    .unwrap()
+            });
+            if b + 1 < batches.len() {
+                alive.truncate(alive.len().div_ceil(2));
+            }
+        }
+
+        let (winner, rank) = (alive[0], mean_rank(&ranks[alive[0]]));
+        info!(
+            "generation {}: best mean rank {:.3} of {} candidates",
+            generation,
+            rank,
+            pool.len()
+        );
+
+        if generation + 1 == options.generations {
+            return TuneResult {
+                params: to_params(&pool[winner]),
+                mean_rank: rank,
+                evaluated: ranks[winner].len(),
+            };
+        }
+
+        survivors = alive.iter().map(|c| pool[*c].clone()).collect();
+        survivors.truncate((options.candidates / 4).max(1));
+    }
+
+    unreachable!()
+}
+
+// Standard normal distribution by the Box-Muller transform.
+fn gaussian(rng: &mut impl Rng) -> f64 {
+    let u1: f64 = rng.gen_range(f64::EPSILON..1.0);
+    let u2: f64 = rng.gen_range(0.0..1.0);
+    (-2. * u1.ln()).sqrt() * (2. * std::f64::consts::PI * u2).cos()
+}
+
+// Problems are tuned separately by their class, i.e. v1 or v2 and the size.
+pub fn problem_class(problem: &Problem) -> String {
+    format!(
+        "{}-{}",
+        if problem.is_v2() { "v2" } else { "v1" },
+        if problem.musicians.len() <= 100 {
+            "small"
+        } else {
+            "large"
+        }
+    )
+}
+
+// Tunes the params for each class of the problems, and writes the best ones to
+// out_dir/{class}.json.
+pub fn tune_by_class<T: Tunable>(
+    base: &T,
+    problems: &[(u32, Problem)],
+    options: &TunerOptions,
+    out_dir: impl AsRef<Path>,
+    eval: impl Fn(&T, u32) -> f64 + Sync,
+) -> Result<BTreeMap<String, TuneResult<T>>> {
+    let mut classes = BTreeMap::new();
+    for (id, problem) in problems.iter() {
+        classes
+            .entry(problem_class(problem))
+            .or_insert_with(Vec::new)
+            .push(*id);
+    }
+
+    std::fs::create_dir_all(&out_dir)?;
+
+    let mut res = BTreeMap::new();
+    for (class, ids) in classes {
+        info!("tuning {} with {} problems", class, ids.len());
+        let r = tune(base, &ids, options, &eval);
+
+        let path = out_dir.as_ref().join(format!("{}.json", class));
+        serde_json::to_writer_pretty(File::create(&path)?, &r.params)?;
+        info!(
+            "{}: mean rank {:.3}, written to {}",
+            class,
+            r.mean_rank,
+            path.display()
+        );
+
+        res.insert(class, r);
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+
+    use super::{tune, ParamRange, Tunable, TunerOptions};
+
+    #[derive(Debug, Clone, Serialize)]
+    struct Quad {
+        x: f64,
+        n: usize,
+    }
+
+    impl Tunable for Quad {
+        fn ranges() -> Vec<ParamRange> {
+            vec![
+                ParamRange::log("x", 0.01, 100.),
+                ParamRange::linear("n", 0., 20.).integer(),
+            ]
+        }
+
+        fn values(&self) -> Vec<f64> {
+            vec![self.x, self.n as f64]
+        }
+
+        fn with_values(&self, values: &[f64]) -> Self {
+            Self {
+                x: values[0],
+                n: values[1] as usize,
+            }
+        }
+    }
+
+    #[test]
+    fn test_param_range() {
+        for r in Quad::ranges() {
+            for u in [0., 0.3, 1.] {
+                let v = r.from_unit(u);
+                assert!(r.min <= v && v <= r.max);
+                if !r.integer {
+                    assert!((r.to_unit(v) - u).abs() < 1e-9);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_tune() {
+        // The best is x = 1, n = 7 on every problem, shifted by the problem id.
+        let eval =
+            |q: &Quad, p: u32| p as f64 * 100. - q.x.ln().powi(2) - (q.n as f64 - 7.).powi(2) / 10.;
+        let base = Quad { x: 50., n: 20 };
+        let problems = (1..=8).collect::<Vec<_>>();
+
+        let options = TunerOptions::default()
+            .with_candidates(12)
+            .with_generations(4);
+        let r = tune(&base, &problems, &options, eval);
+
+        assert!(problems
+            .iter()
+            .all(|p| eval(&r.params, *p) > eval(&base, *p)));
+        assert!(r.mean_rank <= 0.5);
+        assert!(r.evaluated > 0);
+    }
+}
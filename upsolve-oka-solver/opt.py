@@ -69,6 +69,10 @@ def objective(
                 "v2_place": trial.suggest_int("v2_place", 1, 20),
                 "v2_move_dir": trial.suggest_int("v2_move_dir", 1, 20),
                 "v2_swap": trial.suggest_int("v2_swap", 1, 20),
+                "v2_local_hungarian": trial.suggest_int("v2_local_hungarian", 0, 20),
+                "local_hungarian_range": trial.suggest_int(
+                    "local_hungarian_range", 30, 100
+                ),
             }
         )
     else:
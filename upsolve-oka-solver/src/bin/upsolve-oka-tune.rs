@@ -0,0 +1,103 @@
+use std::{collections::HashMap, fs::read_to_string};
+
+use anyhow::{bail, Result};
+use common::{evaluate, Problem};
+use log::warn;
+use tuner::{tune_by_class, TunerOptions};
+use upsolve_oka_solver::{params::Params, solver::Solver, solver2::Solver2, solver3};
+
+const PARAMS: &str = include_str!("../../params.json");
+
+// "1-3,5" -> [1, 2, 3, 5]
+fn parse_ids(s: &str) -> Result<Vec<u32>> {
+    let mut res = vec![];
+    for part in s.split(',').filter(|p| !p.is_empty()) {
+        if let Some((from, to)) = part.split_once('-') {
+            res.extend(from.trim().parse::<u32>()?..=to.trim().parse::<u32>()?);
+        } else {
+            res.push(part.trim().parse()?);
+        }
+    }
+    Ok(res)
+}
+
+fn solve(
+    problem_id: u32,
+    problem: &Problem,
+    num_iter: usize,
+    params: Params,
+    version: usize,
+) -> f64 {
+    let board = match version {
+        1 => Solver::new(problem_id, problem.clone(), num_iter, params, None).solve(),
+        2 => Solver2::new(problem_id, problem.clone(), num_iter, params, None).solve(),
+        3 => solver3::solve(problem_id, problem.clone(), num_iter, params, None),
+        _ => panic!("Unknown solver version: {}", version),
+    };
+    let solution = board.solution_with_optimized_volume().unwrap();
+    evaluate(problem, &solution)
+}
+
+/// Tunes Params on the problems, and writes the best ones for each problem class.
+#[argopt::cmd]
+fn main(
+    /// problem ids, e.g. "1-55" or "1,3,5"
+    problems: String,
+    #[opt(long, short, default_value = "1000000")] num_iter: usize,
+    /// params to start from. The bundled one by default.
+    #[opt(long, default_value = "")]
+    params: String,
+    #[opt(long, short, default_value = "1")] version: usize, // solver version
+    #[opt(long, default_value = "8")] candidates: usize,
+    #[opt(long, default_value = "3")] generations: usize,
+    /// number of problems added to the race at once
+    #[opt(long, default_value = "2")]
+    batch: usize,
+    #[opt(long, default_value = "0")] seed: u64,
+    /// directory to write {class}.json
+    #[opt(long, short, default_value = "tuned-params")]
+    out_dir: String,
+) -> Result<()> {
+    env_logger::init();
+
+    let params_str = if params.is_empty() {
+        PARAMS.to_string()
+    } else {
+        read_to_string(params)?
+    };
+    let base: Params = serde_json::from_str(&params_str)?;
+
+    let mut problems_by_id = HashMap::new();
+    for id in parse_ids(&problems)? {
+        match Problem::read_from_file(format!("problems/{}.json", id)) {
+            Ok(problem) => {
+                problems_by_id.insert(id, problem);
+            }
+            Err(e) => warn!("skipping problem {}: {}", id, e),
+        }
+    }
+    if problems_by_id.is_empty() {
+        bail!("No problems to tune on");
+    }
+    let mut problems = problems_by_id.clone().into_iter().collect::<Vec<_>>();
+    problems.sort_by_key(|(id, _)| *id);
+
+    let options = TunerOptions::default()
+        .with_candidates(candidates)
+        .with_generations(generations)
+        .with_batch(batch)
+        .with_seed(seed);
+
+    let res = tune_by_class(&base, &problems, &options, &out_dir, |params, id| {
+        solve(id, &problems_by_id[&id], num_iter, params.clone(), version)
+    })?;
+
+    for (class, r) in res.iter() {
+        eprintln!(
+            "{}: mean rank {:.3} on {} problems",
+            class, r.mean_rank, r.evaluated
+        );
+    }
+
+    Ok(())
+}
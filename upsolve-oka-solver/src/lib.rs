@@ -0,0 +1,6 @@
+pub mod output;
+pub mod params;
+pub mod pretty;
+pub mod solver;
+pub mod solver2;
+pub mod solver3;
@@ -1,25 +1,18 @@
-pub mod output;
-pub mod params;
-pub mod pretty;
-pub mod solver;
-pub mod solver2;
-pub mod solver3;
-
 use std::{
     fs::{read_to_string, File},
     io::Write,
 };
 
+use anyhow::{bail, Result};
 use chrono::Local;
 use env_logger::Builder;
 use log::LevelFilter;
 
-use common::{evaluate, Problem, Solution};
+use common::{constraints::Constraints, evaluate, Problem, Solution};
 use pprof::protos::Message;
-use pretty::pretty;
-use solver::Solver;
-
-use crate::{params::Params, solver2::Solver2};
+use upsolve_oka_solver::{
+    output, params::Params, pretty::pretty, solver::Solver, solver2::Solver2, solver3,
+};
 
 const PARAMS: &str = include_str!("../params.json");
 
@@ -33,7 +26,8 @@ fn main(
     #[opt(long)] quiet: bool,
     #[opt(long, default_value = "")] initial_solution: String,
     #[opt(long, short, default_value = "1")] version: usize, // solver version
-) {
+    #[opt(long, default_value = "")] constraints: String,    // default: sidecar of initial_solution
+) -> Result<()> {
     Builder::new()
         .format(|buf, record| {
             writeln!(
@@ -64,6 +58,16 @@ fn main(
 
     let params: Params = serde_json::from_str(&params_str).unwrap();
 
+    let sidecar = Constraints::sidecar_of(&initial_solution);
+    let constraints = if !constraints.is_empty() {
+        Constraints::read_from_file(constraints).unwrap()
+    } else if !initial_solution.is_empty() && sidecar.is_file() {
+        Constraints::read_from_file(sidecar).unwrap()
+    } else {
+        Constraints::default()
+    };
+    constraints.validate(&problem).unwrap();
+
     let initial_solution = if !initial_solution.is_empty() {
         Some(Solution::read_from_file(initial_solution).unwrap())
     } else {
@@ -84,6 +88,7 @@ fn main(
             params,
             initial_solution,
         )
+        .with_constraints(&constraints)?
         .solve(),
         2 => Solver2::new(
             problem_id,
@@ -92,7 +97,9 @@ fn main(
             params,
             initial_solution,
         )
+        .with_constraints(&constraints)?
         .solve(),
+        3 if !constraints.is_empty() => bail!("Solver version 3 does not support constraints"),
         3 => solver3::solve(
             problem_id,
             problem.clone(),
@@ -100,7 +107,7 @@ fn main(
             params,
             initial_solution,
         ),
-        _ => panic!("Unknown solver version: {}", version),
+        _ => bail!("Unknown solver version: {}", version),
     };
 
     if let Some(guard) = guard {
@@ -138,4 +145,5 @@ fn main(
     if !output.is_empty() {
         serde_json::to_writer(File::create(output).unwrap(), &output::Output { score }).unwrap();
     }
+    Ok(())
 }
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use tuner::{ParamRange, Tunable};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Params {
@@ -27,4 +28,81 @@ pub struct Params {
     pub v2_place: usize,    // 1 - 20
     pub v2_move_dir: usize, // 1 - 20
     pub v2_swap: usize,     // 1 - 20
+
+    pub v2_local_hungarian: usize,  // 0 - 20
+    pub local_hungarian_range: f64, // 30 - 100
+}
+
+impl Tunable for Params {
+    fn ranges() -> Vec<ParamRange> {
+        vec![
+            ParamRange::linear("placed_musicians_ratio", 0.4, 0.6),
+            ParamRange::linear("important_attendees_ratio", 0.2, 0.3),
+            ParamRange::linear("important_musician_range", 300., 500.),
+            ParamRange::log("max_temp", 1_000_000., 20_000_000.),
+            ParamRange::linear("min_temp", 0., 100_000.),
+            ParamRange::linear("temp_func_power", 1.0, 3.0),
+            ParamRange::linear("max_move_dist", 40., 100.),
+            ParamRange::linear("min_move_dist", 1., 40.),
+            ParamRange::linear("forbidden_area_coeff", 0.5, 1.0),
+            ParamRange::log("hungarian_rarity", 1_000_000., 100_000_000.).integer(),
+            ParamRange::linear("swap", 1., 20.).integer(),
+            ParamRange::linear("move_random", 1., 20.).integer(),
+            ParamRange::linear("move_dir", 1., 20.).integer(),
+            ParamRange::linear("v2_unplace", 1., 20.).integer(),
+            ParamRange::linear("v2_place", 1., 20.).integer(),
+            ParamRange::linear("v2_move_dir", 1., 20.).integer(),
+            ParamRange::linear("v2_swap", 1., 20.).integer(),
+            ParamRange::linear("v2_local_hungarian", 0., 20.).integer(),
+            ParamRange::linear("local_hungarian_range", 30., 100.),
+        ]
+    }
+
+    fn values(&self) -> Vec<f64> {
+        vec![
+            self.placed_musicians_ratio,
+            self.important_attendees_ratio,
+            self.important_musician_range,
+            self.max_temp,
+            self.min_temp,
+            self.temp_func_power,
+            self.max_move_dist,
+            self.min_move_dist,
+            self.forbidden_area_coeff,
+            self.hungarian_rarity as f64,
+            self.swap as f64,
+            self.move_random as f64,
+            self.move_dir as f64,
+            self.v2_unplace as f64,
+            self.v2_place as f64,
+            self.v2_move_dir as f64,
+            self.v2_swap as f64,
+            self.v2_local_hungarian as f64,
+            self.local_hungarian_range,
+        ]
+    }
+
+    fn with_values(&self, v: &[f64]) -> Self {
+        Params {
+            placed_musicians_ratio: v[0],
+            important_attendees_ratio: v[1],
+            important_musician_range: v[2],
+            max_temp: v[3],
+            min_temp: v[4],
+            temp_func_power: v[5],
+            max_move_dist: v[6],
+            min_move_dist: v[7],
+            forbidden_area_coeff: v[8],
+            hungarian_rarity: v[9] as usize,
+            swap: v[10] as usize,
+            move_random: v[11] as usize,
+            move_dir: v[12] as usize,
+            v2_unplace: v[13] as usize,
+            v2_place: v[14] as usize,
+            v2_move_dir: v[15] as usize,
+            v2_swap: v[16] as usize,
+            v2_local_hungarian: v[17] as usize,
+            local_hungarian_range: v[18],
+        }
+    }
 }
@@ -1,6 +1,9 @@
 use std::f64::consts::PI;
 
-use common::{board_options::BoardOptions, float, Problem, Solution};
+use common::{
+    board_options::BoardOptions, constraints::Constraints, float, transform::Transform, Problem,
+    Solution,
+};
 use log::info;
 use lyon_geom::{Box2D, LineSegment, Vector};
 use rand::{rngs::SmallRng, Rng, SeedableRng};
@@ -12,6 +15,9 @@ type Board = common::board::Board<float::F64>;
 type P = Vector<f64>;
 
 pub struct Solver {
+    problem: Problem,
+    // Maps problem to orig_problem, which has the stage at the origin.
+    transform: Transform,
     orig_problem: Problem,
 
     board: Board,
@@ -35,11 +41,10 @@ impl Solver {
         params: Params,
         initial_solution: Option<Solution>,
     ) -> Self {
-        if problem.stage.min.x > 0. || problem.stage.min.y > 0. {
-            panic!("Unsupported stage min: {:?}", problem.stage.min);
-        }
-
-        let orig_problem = problem.clone();
+        // The search assumes the stage is in the bottom left corner of the room.
+        let transform = Transform::stage_at_origin(&problem);
+        let initial_solution = initial_solution.map(|s| transform.solution(&s));
+        let orig_problem = transform.problem(&problem);
 
         let options = BoardOptions::default()
             .with_important_attendees_ratio(params.important_attendees_ratio)
@@ -47,7 +52,7 @@ impl Solver {
 
         let board = Board::new_with_options(
             problem_id,
-            problem,
+            orig_problem.clone(),
             "upsolve-oka-solver",
             false,
             vec![],
@@ -66,6 +71,8 @@ impl Solver {
         let musicians = vec![board.prob.stage.min.to_vector(); board.musicians().len()];
 
         Self {
+            problem,
+            transform,
             orig_problem,
             board,
             num_iter,
@@ -78,6 +85,13 @@ impl Solver {
         }
     }
 
+    // The constraints are in the coordinates of problem.
+    pub fn with_constraints(mut self, constraints: &Constraints) -> Result<Self> {
+        self.board
+            .set_constraints(constraints.transformed(&self.transform))?;
+        Ok(self)
+    }
+
     pub fn initialize(&mut self) {
         for i in 0..self.board.musicians().len() {
             self.board.set_volume(i, 10.0);
@@ -88,18 +102,25 @@ impl Solver {
 
         if let Some(initial_solution) = self.initial_solution.clone() {
             for (i, p) in initial_solution.placements.iter().enumerate() {
-                self.move_musician_to(i, p.position.to_vector()).unwrap();
+                // Not move_musician_to(), which rejects the positions out of the regions.
+                self.musicians[i] = p.position.to_vector();
 
                 self.set_visibility(i, true).unwrap();
             }
+            // The pinned musicians stay visible.
             let mut scores = vec![];
             for i in 0..self.board.musicians().len() {
-                scores.push((self.board.contribution2(i) as i64, i));
+                if !self.board.is_pinned(i) {
+                    scores.push((self.board.contribution2(i) as i64, i));
+                }
             }
             scores.sort();
 
-            for i in 0..self.board.musicians().len() - initial_visible_musicians_count {
-                self.set_visibility(scores[i].1, false).unwrap();
+            for (_, i) in scores
+                .iter()
+                .take(self.board.musicians().len() - initial_visible_musicians_count)
+            {
+                self.set_visibility(*i, false).unwrap();
             }
 
             info!("Initial solution score: {}", self.board.score());
@@ -108,12 +129,15 @@ impl Solver {
         }
         // Initialize with random positions
         for i in 0..initial_visible_musicians_count {
-            loop {
-                let p = self.random_place();
-
-                self.move_musician_to(i, p).unwrap();
-
-                if self.set_visibility(i, true).is_ok() {
+            for k in 0..1000 {
+                // The regions of i may be out of the area of random_place().
+                let p = if k < 100 {
+                    self.random_place()
+                } else {
+                    self.random_stage_place()
+                };
+
+                if self.move_musician_to(i, p).is_ok() && self.set_visibility(i, true).is_ok() {
                     break;
                 }
             }
@@ -144,6 +168,9 @@ impl Solver {
             "upsolve-oka-solver",
             false,
         );
+        res_board
+            .set_constraints(self.board.constraints().clone())
+            .unwrap();
 
         for i in 0..self.board.musicians().len() {
             res_board.set_volume(i, 10.0);
@@ -175,24 +202,37 @@ impl Solver {
 
                 let p = P::new(x as f64, y as f64);
 
-                let m = remaining_musicians.last().unwrap();
+                let Some(k) = remaining_musicians
+                    .iter()
+                    .rposition(|m| res_board.can_place(*m, p.to_point()))
+                else {
+                    continue;
+                };
+                let m = remaining_musicians[k];
 
                 let prev_score = res_board.score();
 
-                if res_board.try_place(*m, p.to_point()).is_err() {
+                if res_board.try_place(m, p.to_point()).is_err() {
                     continue;
                 }
                 if res_board.score() < prev_score {
-                    res_board.unplace(*m);
+                    res_board.unplace(m);
                     continue;
                 }
 
-                remaining_musicians.pop();
+                remaining_musicians.remove(k);
             }
         }
 
         res_board.hungarian();
 
+        if self.transform != Transform::identity() {
+            res_board = self
+                .transform
+                .board_back(&res_board, &self.problem)
+                .unwrap();
+        }
+
         res_board
     }
 
@@ -217,6 +257,10 @@ impl Solver {
             bail!("Out of stage");
         }
 
+        if !self.board.allows(m, p.to_point()) {
+            bail!("Out of the regions");
+        }
+
         if !self.is_visible[m] {
             self.musicians[m] = p;
             return Ok(());
@@ -235,6 +279,14 @@ impl Solver {
         Ok(())
     }
 
+    // Same as Board::can_exchange, with the positions of the invisible musicians.
+    fn can_exchange(&self, x: usize, y: usize) -> bool {
+        !self.board.is_pinned(x)
+            && !self.board.is_pinned(y)
+            && self.board.allows(x, self.musicians[y].to_point())
+            && self.board.allows(y, self.musicians[x].to_point())
+    }
+
     fn temp(&self, iter: usize) -> f64 {
         let max_temp = self.params.max_temp;
         let min_temp = self.params.min_temp;
@@ -321,7 +373,8 @@ impl Solver {
             let v = self.rng.gen_range(0..60);
 
             if (0..self.params.swap).contains(&v) {
-                loop {
+                // The constraints may leave no pair to swap.
+                for _ in 0..100 {
                     let x = self.random_musician();
                     let y = self.random_musician();
 
@@ -331,6 +384,9 @@ impl Solver {
                     if !self.is_visible[x] && !self.is_visible[y] {
                         continue;
                     }
+                    if !self.can_exchange(x, y) {
+                        continue;
+                    }
 
                     return Action::Swap(x, y);
                 }
@@ -388,14 +444,7 @@ impl Solver {
 
     fn random_place(&mut self) -> P {
         loop {
-            let x = self
-                .rng
-                .gen_range(self.board.prob.stage.min.x..self.board.prob.stage.max.x);
-            let y = self
-                .rng
-                .gen_range(self.board.prob.stage.min.y..self.board.prob.stage.max.y);
-
-            let p = P::new(x, y);
+            let p = self.random_stage_place();
 
             if !self.forbidden_area.contains(p.to_point()) {
                 return p;
@@ -403,6 +452,17 @@ impl Solver {
         }
     }
 
+    fn random_stage_place(&mut self) -> P {
+        let x = self
+            .rng
+            .gen_range(self.board.prob.stage.min.x..self.board.prob.stage.max.x);
+        let y = self
+            .rng
+            .gen_range(self.board.prob.stage.min.y..self.board.prob.stage.max.y);
+
+        P::new(x, y)
+    }
+
     fn move_to_dir(&self, p: P, dir: P) -> P {
         if self.forbidden_area.contains(p.to_point()) {
             return p;
@@ -1,6 +1,9 @@
 use std::f64::consts::PI;
 
-use common::{board_options::BoardOptions, float, Problem, Solution};
+use common::{
+    board_options::BoardOptions, constraints::Constraints, float, transform::Transform, Problem,
+    Solution,
+};
 use log::info;
 use lyon_geom::{Box2D, LineSegment, Vector};
 use rand::{rngs::SmallRng, Rng, SeedableRng};
@@ -12,6 +15,9 @@ type Board = common::board::Board<float::F64>;
 type P = Vector<f64>;
 
 pub struct Solver2 {
+    problem: Problem,
+    // Maps problem to orig_problem, which has the stage at the origin.
+    transform: Transform,
     orig_problem: Problem,
 
     board: Board,
@@ -37,11 +43,10 @@ impl Solver2 {
         params: Params,
         initial_solution: Option<Solution>,
     ) -> Self {
-        if problem.stage.min.x > 0. || problem.stage.min.y > 0. {
-            panic!("Unsupported stage min: {:?}", problem.stage.min);
-        }
-
-        let orig_problem = problem.clone();
+        // The search assumes the stage is in the bottom left corner of the room.
+        let transform = Transform::stage_at_origin(&problem);
+        let initial_solution = initial_solution.map(|s| transform.solution(&s));
+        let orig_problem = transform.problem(&problem);
 
         let options = BoardOptions::default()
             .with_important_attendees_ratio(params.important_attendees_ratio)
@@ -49,7 +54,7 @@ impl Solver2 {
 
         let board = Board::new_with_options(
             problem_id,
-            problem,
+            orig_problem.clone(),
             "upsolve-oka-solver",
             false,
             vec![],
@@ -68,6 +73,8 @@ impl Solver2 {
         let musicians = vec![board.prob.stage.min.to_vector(); board.musicians().len()];
 
         Self {
+            problem,
+            transform,
             orig_problem,
             board,
             num_iter,
@@ -81,6 +88,13 @@ impl Solver2 {
         }
     }
 
+    // The constraints are in the coordinates of problem.
+    pub fn with_constraints(mut self, constraints: &Constraints) -> Result<Self> {
+        self.board
+            .set_constraints(constraints.transformed(&self.transform))?;
+        Ok(self)
+    }
+
     pub fn initialize(&mut self) {
         for i in 0..self.board.musicians().len() {
             self.board.set_volume(i, 10.0);
@@ -91,18 +105,25 @@ impl Solver2 {
 
         if let Some(initial_solution) = self.initial_solution.clone() {
             for (i, p) in initial_solution.placements.iter().enumerate() {
-                self.move_musician_to(i, p.position.to_vector()).unwrap();
+                // Not move_musician_to(), which rejects the positions out of the regions.
+                self.musicians[i] = p.position.to_vector();
 
                 self.set_visibility(i, true).unwrap();
             }
+            // The pinned musicians stay visible.
             let mut scores = vec![];
             for i in 0..self.board.musicians().len() {
-                scores.push((self.board.contribution2(i) as i64, i));
+                if !self.board.is_pinned(i) {
+                    scores.push((self.board.contribution2(i) as i64, i));
+                }
             }
             scores.sort();
 
-            for i in 0..self.board.musicians().len() - initial_visible_musicians_count {
-                self.set_visibility(scores[i].1, false).unwrap();
+            for (_, i) in scores
+                .iter()
+                .take(self.board.musicians().len() - initial_visible_musicians_count)
+            {
+                self.set_visibility(*i, false).unwrap();
             }
 
             info!("Initial solution score: {}", self.board.score());
@@ -111,12 +132,15 @@ impl Solver2 {
         }
         // Initialize with random positions
         for i in 0..initial_visible_musicians_count {
-            loop {
-                let p = self.random_place();
-
-                self.move_musician_to(i, p).unwrap();
-
-                if self.set_visibility(i, true).is_ok() {
+            for k in 0..1000 {
+                // The regions of i may be out of the area of random_place().
+                let p = if k < 100 {
+                    self.random_place()
+                } else {
+                    self.random_stage_place()
+                };
+
+                if self.move_musician_to(i, p).is_ok() && self.set_visibility(i, true).is_ok() {
                     break;
                 }
             }
@@ -147,6 +171,9 @@ impl Solver2 {
             "upsolve-oka-solver",
             false,
         );
+        res_board
+            .set_constraints(self.board.constraints().clone())
+            .unwrap();
 
         for i in 0..self.board.musicians().len() {
             res_board.set_volume(i, 10.0);
@@ -178,23 +205,36 @@ impl Solver2 {
 
                 let p = P::new(x as f64, y as f64);
 
-                let m = remaining_musicians.last().unwrap();
+                let Some(k) = remaining_musicians
+                    .iter()
+                    .rposition(|m| res_board.can_place(*m, p.to_point()))
+                else {
+                    continue;
+                };
+                let m = remaining_musicians[k];
 
                 let prev_score = res_board.score();
 
-                if res_board.try_place(*m, p.to_point()).is_err() {
+                if res_board.try_place(m, p.to_point()).is_err() {
                     continue;
                 }
                 if res_board.score() < prev_score {
-                    res_board.unplace(*m);
+                    res_board.unplace(m);
                     continue;
                 }
 
-                remaining_musicians.pop();
+                remaining_musicians.remove(k);
             }
         }
 
-        res_board.hungarian();
+        res_board.hungarian_v2(3);
+
+        if self.transform != Transform::identity() {
+            res_board = self
+                .transform
+                .board_back(&res_board, &self.problem)
+                .unwrap();
+        }
 
         res_board
     }
@@ -222,6 +262,10 @@ impl Solver2 {
             bail!("Out of stage");
         }
 
+        if !self.board.allows(m, p.to_point()) {
+            bail!("Out of the regions");
+        }
+
         if !self.is_visible[m] {
             self.musicians[m] = p;
             return Ok(());
@@ -240,6 +284,14 @@ impl Solver2 {
         Ok(())
     }
 
+    // Same as Board::can_exchange, with the positions of the invisible musicians.
+    fn can_exchange(&self, x: usize, y: usize) -> bool {
+        !self.board.is_pinned(x)
+            && !self.board.is_pinned(y)
+            && self.board.allows(x, self.musicians[y].to_point())
+            && self.board.allows(y, self.musicians[x].to_point())
+    }
+
     fn temp(&self, iter: usize) -> f64 {
         let max_temp = self.params.max_temp;
         let min_temp = self.params.min_temp;
@@ -288,8 +340,7 @@ impl Solver2 {
             }
             Action::Place(x, p) => {
                 debug_assert!(!self.is_visible[x]);
-                self.move_musician_to(x, p).unwrap();
-                self.set_visibility(x, true).is_ok()
+                self.move_musician_to(x, p).is_ok() && self.set_visibility(x, true).is_ok()
             }
             Action::Swap(x, y) => {
                 let x_vis = self.is_visible[x];
@@ -310,32 +361,49 @@ impl Solver2 {
             }
             Action::MoveTo(m, _, p) => self.move_musician_to(m, p).is_ok(),
             Action::Hungarian => {
-                self.board.hungarian();
-
-                for (m, p) in self.board.musicians().iter().enumerate() {
-                    if let Some((p, _)) = p {
-                        self.musicians[m] = *p;
-                        self.is_visible[m] = true;
-                    } else {
-                        self.is_visible[m] = false;
-                    }
+                self.board.hungarian_v2(3);
+                self.sync_with_board();
+                true
+            }
+            Action::HungarianLocal(x) => {
+                let d = Vector::new(
+                    self.params.local_hungarian_range,
+                    self.params.local_hungarian_range,
+                );
+                let p = self.musicians[x].to_point();
+                if !self.board.hungarian_in_region(Box2D::new(p - d, p + d)) {
+                    return false;
                 }
-
+                self.sync_with_board();
                 true
             }
         }
     }
 
+    fn sync_with_board(&mut self) {
+        for (m, p) in self.board.musicians().iter().enumerate() {
+            if let Some((p, _)) = p {
+                self.musicians[m] = *p;
+                self.is_visible[m] = true;
+            } else {
+                self.is_visible[m] = false;
+            }
+        }
+    }
+
     fn random_action(&mut self, iter: usize) -> Action {
         if self.rng.gen_range(0..self.params.hungarian_rarity) == 0 {
             return Action::Hungarian;
         }
 
         loop {
-            let v = self.rng.gen_range(0..80);
+            let v = self.rng.gen_range(0..100);
 
             if (0..self.params.v2_unplace).contains(&v) {
                 let Some(x) = self.random_visible_musician() else {continue};
+                if self.board.is_pinned(x) {
+                    continue;
+                }
                 return Action::Unplace(x, self.musicians[x]);
             } else if (20..20 + self.params.v2_place).contains(&v) {
                 let Some(x) = self.random_invisible_musician() else {continue};
@@ -356,7 +424,11 @@ impl Solver2 {
 
                 return Action::MoveTo(x, orig, dest);
             } else if (60..60 + self.params.v2_swap).contains(&v) {
-                loop {
+                if self.visible_musicians_count == 0 {
+                    continue;
+                }
+                // The constraints may leave no pair to swap.
+                for _ in 0..100 {
                     let x = self.random_musician();
                     let y = self.random_musician();
 
@@ -366,9 +438,15 @@ impl Solver2 {
                     if !self.is_visible[x] && !self.is_visible[y] {
                         continue;
                     }
+                    if !self.can_exchange(x, y) {
+                        continue;
+                    }
 
                     return Action::Swap(x, y);
                 }
+            } else if (80..80 + self.params.v2_local_hungarian).contains(&v) {
+                let Some(x) = self.random_visible_musician() else {continue};
+                return Action::HungarianLocal(x);
             }
         }
     }
@@ -419,14 +497,7 @@ impl Solver2 {
 
     fn random_place(&mut self) -> P {
         loop {
-            let x = self
-                .rng
-                .gen_range(self.board.prob.stage.min.x..self.board.prob.stage.max.x);
-            let y = self
-                .rng
-                .gen_range(self.board.prob.stage.min.y..self.board.prob.stage.max.y);
-
-            let p = P::new(x, y);
+            let p = self.random_stage_place();
 
             if !self.forbidden_area.contains(p.to_point()) {
                 return p;
@@ -434,6 +505,17 @@ impl Solver2 {
         }
     }
 
+    fn random_stage_place(&mut self) -> P {
+        let x = self
+            .rng
+            .gen_range(self.board.prob.stage.min.x..self.board.prob.stage.max.x);
+        let y = self
+            .rng
+            .gen_range(self.board.prob.stage.min.y..self.board.prob.stage.max.y);
+
+        P::new(x, y)
+    }
+
     fn move_to_dir(&self, p: P, dir: P) -> P {
         if self.forbidden_area.contains(p.to_point()) {
             return p;
@@ -498,6 +580,8 @@ enum Action {
     Swap(usize, usize),
     MoveTo(usize, /* from */ P, /* to */ P),
     Hungarian,
+    // Never decreases the score, so it is never inverted.
+    HungarianLocal(/* center */ usize),
 }
 
 impl Action {
@@ -508,6 +592,7 @@ impl Action {
             Action::Swap(x, y) => Action::Swap(x, y),
             Action::MoveTo(m, orig, p) => Action::MoveTo(m, p, orig),
             Action::Hungarian => Action::Hungarian,
+            Action::HungarianLocal(x) => Action::HungarianLocal(x),
         }
     }
 }
@@ -1,4 +1,8 @@
-use common::{geom::rotate90, Pillar, Problem};
+use common::{
+    geom::rotate90,
+    layout::{open_edges, Edge},
+    Pillar, Problem,
+};
 use lyon_geom::{Box2D, LineSegment, Vector};
 
 use super::types::P;
@@ -8,9 +12,6 @@ pub struct ExtProblem {
     pub problem: Problem,
     pub walls: Vec<LineSegment<f64>>,
     pub extra_pillars: Vec<Pillar>,
-
-    pub is_top: bool,
-    pub is_right: bool,
 }
 
 impl ExtProblem {
@@ -24,30 +25,102 @@ impl ExtProblem {
     }
 }
 
-pub fn split_problem(problem: Problem, side: f64) -> Vec<ExtProblem> {
+// The range of the cut positions, which is the stage shrunk by 5.
+pub fn cut_range(problem: &Problem) -> Box2D<f64> {
     let margin = P::new(5., 5.);
-    let inner_box = Box2D::new(problem.stage.min + margin, problem.stage.max - margin);
+    Box2D::new(problem.stage.min + margin, problem.stage.max - margin)
+}
+
+// Number of cells of about side long, but not shorter than min_side, in lo..hi.
+fn num_cells(lo: f64, hi: f64, side: f64, min_side: f64) -> usize {
+    let n = ((hi - lo) / side).ceil() as usize;
+    n.min(((hi - lo) / min_side) as usize).max(1)
+}
 
-    let nw = (inner_box.size().width / side).ceil() as usize;
-    let nh = (inner_box.size().height / side).ceil() as usize;
+pub fn uniform_cuts(lo: f64, hi: f64, side: f64, min_side: f64) -> Vec<f64> {
+    let n = num_cells(lo, hi, side, min_side);
+    let w = (hi - lo) / n as f64 - 1e-9;
 
-    let w = inner_box.size().width / nw as f64 - 1e-9;
-    let h = inner_box.size().height / nh as f64 - 1e-9;
+    (1..n).map(|i| lo + w * i as f64).collect()
+}
 
-    let dw = P::new(w, 0.);
-    let dh = P::new(0., h);
+// Cuts dividing the sum of weights of (position, weight) in lo..hi evenly, where half of
+// the total weight is spread uniformly. Cells are not shorter than min_side.
+pub fn density_cuts(lo: f64, hi: f64, side: f64, min_side: f64, ws: &[(f64, f64)]) -> Vec<f64> {
+    let n = num_cells(lo, hi, side, min_side);
 
-    let mut cut_x = vec![];
-    let mut cut_y = vec![];
+    let mut ws = ws
+        .iter()
+        .map(|(x, w)| (x.clamp(lo, hi), *w))
+        .collect::<Vec<_>>();
+    ws.sort_by(|a, b| a.partial_cmp(b).unwrap());
 
-    for i in 1..nw {
-        cut_x.push((inner_box.min + dw * i as f64).x);
+    let total = ws.iter().map(|(_, w)| w).sum::<f64>();
+    if total <= 0. {
+        return uniform_cuts(lo, hi, side, min_side);
     }
-    for j in 1..nh {
-        cut_y.push((inner_box.min + dh * j as f64).y);
+    let uniform = total / (hi - lo);
+
+    // Weight in lo..x.
+    let mass = |x: f64| {
+        uniform * (x - lo)
+            + ws.iter()
+                .take_while(|(p, _)| *p < x)
+                .map(|(_, w)| w)
+                .sum::<f64>()
+    };
+
+    let mut cuts = vec![];
+    let mut prev = lo;
+    for i in 1..n {
+        let target = 2. * total * i as f64 / n as f64;
+
+        // Leave room for the remaining cells.
+        let (mut l, mut r) = (prev + min_side, hi - min_side * (n - i) as f64);
+        if l > r {
+            return uniform_cuts(lo, hi, side, min_side);
+        }
+        for _ in 0..50 {
+            let m = (l + r) / 2.;
+            if mass(m) < target {
+                l = m;
+            } else {
+                r = m;
+            }
+        }
+        cuts.push(r);
+        prev = r;
     }
+    cuts
+}
+
+// (position, weight) pairs along an axis.
+type Weights = Vec<(f64, f64)>;
+
+// Weights of the attendees to decide the cuts by density, which are the sums of the
+// positive tastes divided by the squared distances to the stage. The attendees in front
+// of the edges parallel to the axis of the cuts are used. Returns the ones for cut_x and
+// cut_y.
+pub fn attendee_weights(problem: &Problem) -> (Weights, Weights) {
+    let stage = problem.stage;
 
-    split_problem_from_cut(problem, cut_x, cut_y, None)
+    let mut wx = vec![];
+    let mut wy = vec![];
+    for a in problem.attendees.iter() {
+        let p = a.position;
+        let taste = a.tastes.iter().filter(|t| **t > 0.).sum::<f64>();
+
+        let dx = (stage.min.x - p.x).max(p.x - stage.max.x);
+        let dy = (stage.min.y - p.y).max(p.y - stage.max.y);
+
+        if dx <= 0. && dy > 0. {
+            wx.push((p.x, taste / dy.powi(2)));
+        }
+        if dy <= 0. && dx > 0. {
+            wy.push((p.y, taste / dx.powi(2)));
+        }
+    }
+    (wx, wy)
 }
 
 pub fn split_problem_from_cut(
@@ -56,12 +129,8 @@ pub fn split_problem_from_cut(
     cut_y: Vec<f64>,
     pillar_cands: Option<Vec<P>>,
 ) -> Vec<ExtProblem> {
-    if problem.stage.min.x > 0. || problem.stage.min.y > 0. {
-        panic!("Unsupported problem");
-    }
-
     let margin = P::new(5., 5.);
-    let inner_box = Box2D::new(problem.stage.min + margin, problem.stage.max - margin);
+    let inner_box = cut_range(&problem);
 
     let mut xs = vec![inner_box.min.x]
         .into_iter()
@@ -81,12 +150,25 @@ pub fn split_problem_from_cut(
 
     let mut mini_problems = vec![];
 
-    let is_right = |i: usize| i + 1 == xs.len();
-    let is_top = |j: usize| j + 1 == ys.len();
+    // Only the cells along the edges with attendees behind are solved. The sides of the
+    // cells on these edges are open, and the others are walls.
+    let edges = open_edges(&problem);
+    let on_edge = |edge: &Edge, (i, j): (usize, usize)| match edge {
+        Edge::Bottom => j == 0,
+        Edge::Top => j + 1 == ys.len(),
+        Edge::Left => i == 0,
+        Edge::Right => i + 1 == xs.len(),
+    };
+    let is_open = |c: (usize, usize)| edges.iter().any(|e| on_edge(e, c));
+    let is_open_side =
+        |c1: (usize, usize), c2| edges.iter().any(|e| on_edge(e, c1) && on_edge(e, c2));
+
+    let ds = [(0, 0), (1, 0), (1, 1), (0, 1)];
 
     for i in 0..xs.len() - 1 {
         for j in 0..ys.len() - 1 {
-            if !is_right(i + 1) && !is_top(j + 1) {
+            let corners = ds.map(|(di, dj)| (i + di, j + dj));
+            if !(0..4).any(|k| is_open_side(corners[k], corners[(k + 1) % 4])) {
                 continue;
             }
 
@@ -95,30 +177,24 @@ pub fn split_problem_from_cut(
 
             let mini_stage = Box2D::new(ll - margin, ur + margin);
 
-            let ds = [(0, 0), (1, 0), (1, 1), (0, 1)];
-
             let mut walls = vec![];
             let mut extra_pillars = vec![];
 
             for k in 0..4 {
-                let (di1, dj1) = ds[k];
-                let (di2, dj2) = ds[(k + 1) % 4];
-
-                let (i1, j1) = (i + di1, j + dj1);
-                let (i2, j2) = (i + di2, j + dj2);
+                let (c1, c2) = (corners[k], corners[(k + 1) % 4]);
 
-                if is_right(i1) && is_right(i2) || is_top(j1) && is_top(j2) {
+                if is_open_side(c1, c2) {
                     continue;
                 }
 
-                let mut p1 = P::new(xs[i1], ys[j1]).to_point();
-                let mut p2 = P::new(xs[i2], ys[j2]).to_point();
+                let mut p1 = P::new(xs[c1.0], ys[c1.1]).to_point();
+                let mut p2 = P::new(xs[c2.0], ys[c2.1]).to_point();
 
                 let pillar_dir = rotate90(p2 - p1).normalize() * -5.;
 
                 let mut limit_point = None;
 
-                if is_right(i1) || is_top(j1) {
+                if is_open(c1) {
                     p1 += (p2 - p1).normalize() * 5.;
 
                     limit_point = p1.into();
@@ -130,7 +206,7 @@ pub fn split_problem_from_cut(
                         });
                     }
                 }
-                if is_right(i2) || is_top(j2) {
+                if is_open(c2) {
                     p2 += (p1 - p2).normalize() * 5.;
 
                     limit_point = p2.into();
@@ -174,11 +250,7 @@ pub fn split_problem_from_cut(
                 }
             }
 
-            assert!(0 < walls.len() && walls.len() < 4);
-
-            if pillar_cands.is_none() {
-                assert_eq!(extra_pillars.len(), 2);
-            }
+            assert!(walls.len() < 4);
 
             mini_problems.push(ExtProblem {
                 problem: Problem {
@@ -187,8 +259,6 @@ pub fn split_problem_from_cut(
                 },
                 walls,
                 extra_pillars,
-                is_right: is_right(i + 1),
-                is_top: is_top(j + 1),
             });
         }
     }
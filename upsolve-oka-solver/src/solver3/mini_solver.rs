@@ -299,7 +299,8 @@ impl MiniSolver {
             let v = self.rng.gen_range(0..60);
 
             if (0..self.params.swap).contains(&v) {
-                loop {
+                // Small cells may have no pair to swap.
+                for _ in 0..100 {
                     let x = self.random_musician();
                     let y = self.random_musician();
 
@@ -370,10 +371,10 @@ impl MiniSolver {
     pub fn random_place(&mut self) -> P {
         let x = self
             .rng
-            .gen_range(self.board.prob.stage.min.x..self.board.prob.stage.max.x);
+            .gen_range(self.board.prob.stage.min.x..=self.board.prob.stage.max.x);
         let y = self
             .rng
-            .gen_range(self.board.prob.stage.min.y..self.board.prob.stage.max.y);
+            .gen_range(self.board.prob.stage.min.y..=self.board.prob.stage.max.y);
 
         let p = P::new(x, y);
 
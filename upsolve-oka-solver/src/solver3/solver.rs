@@ -1,7 +1,9 @@
 use anyhow::Context;
-use common::{Problem, Solution};
+use common::{
+    finetune::{finetune, FinetuneOptions},
+    Problem, Solution,
+};
 use log::info;
-use lyon_geom::Size;
 use rand::{rngs::SmallRng, seq::SliceRandom, SeedableRng};
 use rand_distr::{Distribution, Normal};
 use rayon::prelude::*;
@@ -10,7 +12,9 @@ use crate::{
     params::Params,
     pretty::pretty,
     solver3::{
-        ext_problem::{split_problem, split_problem_from_cut},
+        ext_problem::{
+            attendee_weights, cut_range, density_cuts, split_problem_from_cut, uniform_cuts,
+        },
         mini_solver::MiniSolver,
         types::P,
     },
@@ -25,6 +29,12 @@ const MOVE_CUT_POSITIONS: bool = false;
 
 const MOVE_CUT_STD_DEV: f64 = 10.0;
 
+// Make the cells smaller where more attendees are in front of them.
+const CUT_BY_DENSITY: bool = true;
+
+// Musicians closer than this to the cuts are finetuned after all.
+const BOUNDARY_DIST: f64 = 10.0;
+
 pub fn solve(
     problem_id: u32,
     problem: Problem,
@@ -36,17 +46,22 @@ pub fn solve(
     let mut rng = SmallRng::seed_from_u64(seed);
     seed += 1;
 
-    let mut mini_problems = split_problem(problem.clone(), D);
-
-    let corner_stage = mini_problems
-        .iter()
-        .find(|mp| mp.walls.len() == 2)
-        .unwrap()
-        .problem
-        .stage;
+    let range = cut_range(&problem);
+    let (mut cut_x, mut cut_y) = if CUT_BY_DENSITY {
+        let (wx, wy) = attendee_weights(&problem);
+        (
+            density_cuts(range.min.x, range.max.x, D, MIN_SIDE, &wx),
+            density_cuts(range.min.y, range.max.y, D, MIN_SIDE, &wy),
+        )
+    } else {
+        (
+            uniform_cuts(range.min.x, range.max.x, D, MIN_SIDE),
+            uniform_cuts(range.min.y, range.max.y, D, MIN_SIDE),
+        )
+    };
 
-    let last_cut_pos = corner_stage.min + P::new(5., 5.);
-    let base_cell_size = corner_stage.size() - Size::new(10., 10.);
+    let mut mini_problems =
+        split_problem_from_cut(problem.clone(), cut_x.clone(), cut_y.clone(), None);
 
     let mut fixed_positions: Vec<(usize, P)> = vec![];
 
@@ -91,16 +106,13 @@ pub fn solve(
 
         (available_musicians, initial_locations)
     } else {
-        let mut available_musicians = ms
-            .chunks((ms.len() + mini_problems.len() - 1) / mini_problems.len())
-            .into_iter()
-            .map(|v| v.into_iter().map(|&i| i).collect::<Vec<_>>())
-            .collect::<Vec<_>>();
+        let mut available_musicians = vec![vec![]; mini_problems.len()];
+        for (i, m) in ms.iter().enumerate() {
+            available_musicians[i % mini_problems.len()].push(*m);
+        }
 
         available_musicians.iter_mut().for_each(|x| x.sort());
 
-        assert_eq!(mini_problems.len(), available_musicians.len());
-
         let initial_locations = available_musicians
             .iter()
             .map(|ms| ms.iter().map(|_| None).collect::<Vec<_>>())
@@ -141,9 +153,12 @@ pub fn solve(
             solvers.push(solver);
         }
 
+        // Cells may be left without musicians when there are more cells than musicians.
         let mini_boards = solvers
             .par_iter_mut()
-            .map(|solver| solver.solve())
+            .zip(available_musicians.par_iter())
+            .filter(|(_, ms)| !ms.is_empty())
+            .map(|(solver, _)| solver.solve())
             .collect::<Vec<_>>();
 
         let mut board = Board::new(problem_id, problem.clone(), "upsolver-oka-solver3", false);
@@ -164,7 +179,7 @@ pub fn solve(
             board.try_place(*m, p.to_point()).unwrap();
         }
 
-        board.hungarian();
+        board.hungarian_v2(3);
 
         info!(
             "{:>3}%  score: {:>14}",
@@ -227,62 +242,53 @@ pub fn solve(
         }
 
         if outer_iter == num_outer_iter - 1 {
+            // Musicians around the cuts were optimized without the ones on the other side.
+            let boundary = (0..board.musicians().len())
+                .filter(|m| {
+                    board.musicians()[*m].is_some_and(|(p, _)| {
+                        cut_x.iter().any(|x| (p.x - x).abs() < BOUNDARY_DIST)
+                            || cut_y.iter().any(|y| (p.y - y).abs() < BOUNDARY_DIST)
+                    })
+                })
+                .collect::<Vec<_>>();
+            info!("finetuning {} musicians on the boundaries", boundary.len());
+
+            let reports = finetune(
+                &mut board,
+                &FinetuneOptions::default()
+                    .with_max_passes(20)
+                    .with_musicians(boundary),
+            );
+            if let Some(r) = reports.last() {
+                info!("finetuned score: {:>14}", pretty(r.score_after as i64));
+            }
+
             return board;
         }
 
         // Update mini problems.
-        let num_x_cut = mini_problems.iter().filter(|prob| prob.is_top).count() - 1;
-        let num_y_cut = mini_problems.iter().filter(|prob| prob.is_right).count() - 1;
-
-        let mut x_cut = vec![];
-        let mut y_cut = vec![];
-
-        for (min, cut, n, last, base_step) in [
-            (
-                problem.stage.min.x + 5.0,
-                &mut x_cut,
-                num_x_cut,
-                last_cut_pos.x,
-                base_cell_size.width,
-            ),
-            (
-                problem.stage.min.y + 5.0,
-                &mut y_cut,
-                num_y_cut,
-                last_cut_pos.y,
-                base_cell_size.height,
-            ),
-        ] {
-            loop {
-                cut.clear();
-                cut.push(min);
-                for i in 0..n - 1 {
-                    let c = min + base_step * (i + 1) as f64;
-
-                    let d = if MOVE_CUT_POSITIONS {
-                        Normal::new(c, MOVE_CUT_STD_DEV).unwrap().sample(&mut rng)
-                    } else {
-                        c
-                    };
-
-                    cut.push(d);
-                }
-                cut.push(last);
-
-                cut.sort_by(|x, y| x.partial_cmp(&y).unwrap());
-
-                let mut ok = true;
-                for i in 0..cut.len() - 1 {
-                    let d = cut[i + 1] - cut[i];
-                    if d < MIN_SIDE {
-                        ok = false;
+        if MOVE_CUT_POSITIONS {
+            for (lo, hi, cut) in [
+                (range.min.x, range.max.x, &mut cut_x),
+                (range.min.y, range.max.y, &mut cut_y),
+            ] {
+                loop {
+                    let mut next = cut
+                        .iter()
+                        .map(|c| Normal::new(*c, MOVE_CUT_STD_DEV).unwrap().sample(&mut rng))
+                        .collect::<Vec<_>>();
+                    next.sort_by(|x, y| x.partial_cmp(y).unwrap());
+
+                    let ok = [lo]
+                        .iter()
+                        .chain(next.iter())
+                        .zip(next.iter().chain([hi].iter()))
+                        .all(|(a, b)| b - a >= MIN_SIDE);
+                    if ok {
+                        *cut = next;
                         break;
                     }
                 }
-                if ok {
-                    cut.remove(0);
-                    break;
-                }
             }
         }
 
@@ -300,7 +306,8 @@ pub fn solve(
 
         let mini_problems_len = mini_problems.len();
 
-        mini_problems = split_problem_from_cut(problem.clone(), x_cut, y_cut, pillar_cands);
+        mini_problems =
+            split_problem_from_cut(problem.clone(), cut_x.clone(), cut_y.clone(), pillar_cands);
 
         assert_eq!(mini_problems_len, mini_problems.len());
 
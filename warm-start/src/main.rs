@@ -0,0 +1,64 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use common::{
+    evaluate, similarity::SimilarityIndex, warm_start::transfer, Problem, RawSolution, Solution,
+};
+
+// Lists the problems similar to the given one, or maps a solution of another problem onto
+// it to make an initial solution for the solvers.
+#[derive(Parser, Debug)]
+struct Args {
+    #[arg(short, long)]
+    problem_id: u32,
+    /// number of similar problems listed
+    #[arg(long, default_value_t = 5)]
+    similar: usize,
+    /// solution of another problem to start from
+    #[arg(long)]
+    from: Option<PathBuf>,
+}
+
+fn read_problem(problem_id: u32) -> Result<Problem> {
+    let f = PathBuf::from(format!("../problems/{}.json", problem_id));
+    if !f.is_file() {
+        return Err(anyhow!("File not found: {}", f.display()));
+    }
+    Problem::read_from_file(f)
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let problem = read_problem(args.problem_id)?;
+
+    let Some(from) = args.from else {
+        let index = SimilarityIndex::from_dir("../problems")?;
+        for (problem_id, distance) in index.nearest(args.problem_id, &problem, args.similar) {
+            println!("{}\t{:.4}", problem_id, distance);
+        }
+        return Ok(());
+    };
+
+    let s = std::fs::read_to_string(&from)?;
+    let solution = Solution::from(RawSolution::from_json(&s)?);
+    let source = read_problem(solution.problem_id)?;
+
+    let (board, report) = transfer(&source, &solution, args.problem_id, &problem)?;
+    eprintln!(
+        "transferred from problem {}: {} moved, {} relocated (max {:.3})",
+        solution.problem_id, report.moved, report.relocated, report.max_displacement
+    );
+
+    let sol = board.solution_with_optimized_volume()?;
+    let score = evaluate(&problem, &sol);
+    eprintln!("score: {}", score);
+
+    if !std::path::Path::new("results").is_dir() {
+        std::fs::create_dir_all("results")?;
+    }
+    let output = PathBuf::from(format!("results/{}-{}.json", args.problem_id, score));
+    Solution::write_to_file(output, sol)?;
+
+    Ok(())
+}
@@ -1,8 +1,10 @@
+use anyhow::anyhow;
+use common::board::Board;
 use saru::{annealing_single_thread, State};
-use tanakh_solver::solver::{Solver2, State2};
+use tanakh_solver::solver::{Solver2, State2, DEFAULT_GRID_LEVELS};
 use wasm_bindgen::prelude::*;
 
-use crate::{ProblemHandle, SolutionHandle};
+use crate::{ProblemHandle, Result, SolutionHandle};
 
 #[wasm_bindgen]
 pub struct SolverHandle {
@@ -12,7 +14,7 @@ pub struct SolverHandle {
 
 #[wasm_bindgen]
 impl SolverHandle {
-    pub fn new(problem: &ProblemHandle, initial_solution: &SolutionHandle) -> Self {
+    pub fn new(problem: &ProblemHandle, initial_solution: &SolutionHandle) -> Result<SolverHandle> {
         let solver = Solver2 {
             problem_id: initial_solution.real.problem_id,
             problem: problem.real.clone(),
@@ -23,16 +25,63 @@ impl SolverHandle {
             param: String::new(),
             use_visibility: false,
             use_contribution: false,
+            grid_levels: DEFAULT_GRID_LEVELS,
+            constraints: Default::default(),
         };
         let mut solver_name = initial_solution.real.solver.clone();
         if !solver_name.ends_with("+anneal") {
             solver_name = format!("{}+anneal", solver_name);
         };
-        let state = State2::new(&initial_solution.real, &problem.real, &solver_name, false);
-        Self {
+        let state = State2::new(
+            &initial_solution.real,
+            &problem.real,
+            &solver_name,
+            false,
+            &Default::default(),
+        )?;
+        Ok(Self {
             solver,
             state: Some(state),
+        })
+    }
+
+    /// Warm-starts from a board snapshot (see `common::snapshot`), skipping the rebuild.
+    /// Fails if the snapshot is not of the given problem.
+    pub fn from_snapshot(
+        problem_id: u32,
+        problem: &ProblemHandle,
+        snapshot: &[u8],
+    ) -> Result<SolverHandle> {
+        let board = Board::from_snapshot(snapshot, problem.real.clone())?;
+        if board.problem_id != problem_id {
+            return Err(anyhow!(
+                "snapshot of problem {} instead of {}",
+                board.problem_id,
+                problem_id
+            )
+            .into());
         }
+        let solver = Solver2 {
+            problem_id: board.problem_id,
+            problem: problem.real.clone(),
+            start_temp: None,
+            better_initial: false,
+            initial_solution: None,
+            taste: None,
+            param: String::new(),
+            use_visibility: false,
+            use_contribution: false,
+            grid_levels: DEFAULT_GRID_LEVELS,
+            constraints: board.constraints().clone(),
+        };
+        Ok(Self {
+            solver,
+            state: Some(State2::from_board(board)),
+        })
+    }
+
+    pub fn snapshot(&self) -> Result<Vec<u8>> {
+        Ok(self.state.as_ref().unwrap().board().to_snapshot()?)
     }
 
     pub fn run(&mut self, temp: f64, time_limit: f64, seed: u64) {